@@ -0,0 +1,154 @@
+use crate::dsp::DspSettings;
+use crate::meter::LevelMeter;
+use crate::recording::Tap;
+use crate::volume::Volume;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::future::Future;
+use tokio::sync::broadcast;
+
+/// Устройство-источник звука (телефон), найденное бэкендом.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BTDevice {
+    pub id: String,
+    pub name: String,
+}
+
+impl BTDevice {
+    /// Ключ для id пунктов меню. `DeviceInformation::Id()` длинный и содержит
+    /// `#`, `\` и `{}`, поэтому в меню кладём его хэш (FNV-1a), а не сам id.
+    pub fn menu_key(&self) -> String {
+        menu_key(&self.id)
+    }
+}
+
+/// Устройство вывода звука (колонки, USB-ЦАП, HDMI), на которое можно направить принятый звук.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AudioOutput {
+    pub id: String,
+    pub name: String,
+}
+
+impl AudioOutput {
+    /// Id звуковых устройств тоже длинные, см. `BTDevice::menu_key`.
+    pub fn menu_key(&self) -> String {
+        menu_key(&self.id)
+    }
+}
+
+/// Ключ пункта меню для любого id, см. `BTDevice::menu_key`.
+pub fn menu_key(id: &str) -> String {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in id.bytes() {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    // Id различаются в основном последними символами (адрес телефона в конце), а у FNV они
+    // почти не задевают старшие разряды — по ним `display_names` различает одинаковые имена.
+    // Перемешиваем, как в конце MurmurHash3
+    hash ^= hash >> 33;
+    hash = hash.wrapping_mul(0xff51afd7ed558ccd);
+    hash ^= hash >> 33;
+    format!("{:016x}", hash)
+}
+
+/// Имена для отображения в том же порядке, что и `devices`.
+/// Одинаковые имена (два «iPhone») дополняются началом ключа устройства.
+pub fn display_names(devices: &[BTDevice]) -> Vec<String> {
+    devices
+        .iter()
+        .map(|device| {
+            let duplicates = devices.iter().filter(|d| d.name == device.name).count();
+            if duplicates > 1 {
+                format!("{} #{}", device.name, &device.menu_key()[..4])
+            } else {
+                device.name.clone()
+            }
+        })
+        .collect()
+}
+
+/// События, которые бэкенд сообщает воркеру независимо от команд.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum BackendEvent {
+    Connected(String),
+    Disconnected(String),
+    // Соединение пропало без команды пользователя (телефон ушёл из зоны, выключил BT и т.п.)
+    ConnectionLost(String),
+    // События наблюдателя устройств (после `watch_devices`)
+    DeviceAdded(BTDevice),
+    DeviceUpdated(BTDevice),
+    DeviceRemoved(String),
+    // Устройство вывода, на которое шёл звук устройства `id`, пропало (выдернули ЦАП)
+    OutputLost(String),
+}
+
+/// Всё, что воркеру нужно от приёмника. Реализуется `BTReceiver` (WinRT)
+/// и `FakeBackend` (для тестов без Windows и телефона).
+///
+/// Бэкенд держит любое число соединений одновременно; сколько их разрешено —
+/// решает воркер.
+pub trait AudioSinkBackend: Send {
+    fn list_devices(&self) -> impl Future<Output = Result<Vec<BTDevice>>> + Send;
+
+    /// Открывает соединение с устройством, не трогая остальные.
+    fn connect(&mut self, device: &BTDevice) -> impl Future<Output = Result<()>> + Send;
+
+    fn reconnect(&mut self, device: &BTDevice) -> impl Future<Output = Result<()>> + Send;
+
+    /// Закрывает соединение с устройством `id` (если оно есть).
+    fn disconnect(&mut self, id: &str) -> impl Future<Output = ()> + Send;
+
+    /// Громкость принятого звука устройства `id`. Если соединения нет — ничего не делает:
+    /// воркер применит громкость снова при подключении. Если у соединения громкость не
+    /// меняется (звук не удалось провести через граф) — ошибка `Msg::VolumeUnavailable`.
+    fn set_volume(&mut self, id: &str, volume: Volume) -> Result<()>;
+
+    /// Устройства вывода, доступные сейчас.
+    fn list_outputs(&self) -> impl Future<Output = Result<Vec<AudioOutput>>> + Send;
+
+    /// Направляет звук устройства `id` на выход `output` (`AudioOutput::id`); `None` — на
+    /// системный по умолчанию. Если соединения нет — ничего не делает. Если выход недоступен,
+    /// звук остаётся на выходе по умолчанию и возвращается ошибка.
+    fn set_output(&mut self, id: &str, output: Option<&str>) -> impl Future<Output = Result<()>> + Send;
+
+    /// Отвод принятого звука устройства `id` (для записи); `None` — убрать. Если соединения
+    /// нет — ничего не делает. Бэкенд отпускает отправитель при отключении устройства.
+    fn set_tap(&mut self, id: &str, tap: Option<Tap>) -> Result<()>;
+
+    /// Обработка принятого звука устройства `id` (эквалайзер); пустые настройки — звук идёт
+    /// без обработки. Если соединения нет — ничего не делает: воркер применит настройки
+    /// снова при подключении.
+    fn set_dsp(&mut self, id: &str, settings: DspSettings) -> impl Future<Output = Result<()>> + Send;
+
+    /// Уровень принятого звука всех соединений сразу (для значка в трее). Клон общий
+    /// с бэкендом: его можно забрать до того, как бэкенд уйдёт в воркер.
+    fn level_meter(&self) -> LevelMeter;
+
+    /// Запускает наблюдение за устройствами: дальше бэкенд сам присылает
+    /// `DeviceAdded`/`DeviceUpdated`/`DeviceRemoved` через `subscribe`.
+    fn watch_devices(&mut self) -> Result<()>;
+
+    /// Подписка на события бэкенда. Можно вызывать сколько угодно раз.
+    fn subscribe(&self) -> broadcast::Receiver<BackendEvent>;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn device(id: &str, name: &str) -> BTDevice {
+        BTDevice { id: id.to_string(), name: name.to_string() }
+    }
+
+    #[test]
+    fn same_names_differ_even_when_ids_differ_at_the_end() {
+        let prefix = r"Bluetooth#Bluetooth00:1a:7d:da:71:13-a4:50:46:0c:21:1";
+        let devices: Vec<BTDevice> = "0123456789abcdef".chars().map(|c| device(&format!("{prefix}{c}"), "iPhone")).collect();
+        let names = display_names(&devices);
+        for (i, name) in names.iter().enumerate() {
+            assert_eq!(names.iter().filter(|n| *n == name).count(), 1, "{name} у устройства {i} не уникально");
+        }
+        assert_eq!(display_names(&[device("a", "iPhone"), device("b", "Pixel")]), ["iPhone", "Pixel"]);
+    }
+}
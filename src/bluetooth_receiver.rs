@@ -1,166 +1,568 @@
+use crate::backend::{AudioOutput, AudioSinkBackend, BTDevice, BackendEvent};
+use crate::config::{QuantumSize, ReceiverConfig};
+use crate::dsp::{DspChain, DspSettings};
+use crate::i18n::Msg;
+use crate::meter::LevelMeter;
+use crate::recording::{AudioChunk, AudioFormat, Tap};
+use crate::volume::Volume;
 use anyhow::{Result, Context};
 use std::sync::Arc;
 use std::sync::atomic::{AtomicBool, Ordering};
-use windows::core::{w, HSTRING};
-use windows::Devices::Enumeration::DeviceInformation;
+use windows::core::{ComInterface, IInspectable, GUID, HSTRING};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use windows::Devices::Enumeration::{DeviceClass, DeviceInformation, DeviceInformationUpdate, DeviceWatcher};
+use windows::Foundation::Collections::IIterable;
+use windows::Foundation::{EventRegistrationToken, IReference, TypedEventHandler};
 use windows::Media::Audio::*;
+use windows::Media::Capture::MediaCategory;
+use windows::Media::Devices::MediaDevice;
 use windows::Media::Render::AudioRenderCategory;
+use windows::Media::{AudioBufferAccessMode, AudioFrame};
+use windows::Win32::System::WinRT::IMemoryBufferByteAccess;
 use windows::Win32::Foundation::HANDLE;
 use windows::Win32::System::Threading::AvSetMmThreadCharacteristicsW;
+use tokio::sync::broadcast;
 
-#[derive(Clone)]
-pub struct BTDevice {
-    pub name: String,
-    pub info: DeviceInformation,
+/// Менеджер соединений: держит сколько угодно одновременных `AudioPlaybackConnection`,
+/// у каждого свой якорный граф и свой монитор.
+pub struct BTReceiver {
+    sessions: HashMap<String, Session>,
+    // MMCSS нужен, пока есть хотя бы одно соединение
+    avrt_handle: Option<HANDLE>,
+    events: broadcast::Sender<BackendEvent>,
+    watcher: Option<DeviceWatcher>,
+    settings: ReceiverConfig,
+    meter: LevelMeter,
 }
 
-pub struct BTReceiver {
-    pub connection: Option<AudioPlaybackConnection>,
-    pub graph: Option<AudioGraph>,
+// Отвод для записи. Отправитель общий у сеанса и обработчика кванта графа,
+// поэтому его можно заменить, не пересобирая граф
+type TapSlot = Arc<Mutex<Option<Tap>>>;
+
+// Цепочка обработки, общая у сеанса и обработчика кванта: настройки меняются на лету
+type DspSlot = Arc<Mutex<DspChain>>;
+
+// Всё, что относится к соединению с одним телефоном
+struct Session {
+    connection: AudioPlaybackConnection,
+    graph: Option<AudioGraph>,
+    // Звук телефона в якорном графе; через его усиление меняется громкость
+    stream: Option<AudioDeviceInputNode>,
+    // Нужна, чтобы восстановить громкость, когда граф пересобирается на другом выходе
+    volume: Volume,
+    // Выход, на котором играет граф; `None` — по умолчанию
+    output: Option<String>,
+    tap: TapSlot,
+    dsp: DspSlot,
     // Используем AtomicBool для мгновенного и безопасного управления потоком мониторинга
     is_monitoring: Arc<AtomicBool>,
-    pub device_id: Option<HSTRING>,
-    avrt_handle: Option<HANDLE>,
+    state_token: Option<EventRegistrationToken>,
 }
 
 impl BTReceiver {
-    pub fn new() -> Self {
+    pub fn new(settings: ReceiverConfig) -> Self {
+        let (events, _) = broadcast::channel(64);
         Self {
-            connection: None,
-            graph: None,
-            is_monitoring: Arc::new(AtomicBool::new(false)),
-            device_id: None,
+            sessions: HashMap::new(),
             avrt_handle: None,
+            events,
+            watcher: None,
+            settings,
+            meter: LevelMeter::new(),
         }
     }
 
-    pub async fn connect(&mut self, device: &BTDevice) -> Result<()> {
-        let id = device.info.Id()?;
-        self.device_id = Some(id.clone());
-        println!("[INIT] Подключение к {}...", device.name);
-        self.perform_connect().await
-    }
-
-    pub async fn reconnect(&mut self, device: &BTDevice) -> Result<()> {
-        println!("[INIT] Переподключение к {}...", device.name);
-        self.disconnect().await;
-        // Заменяем expect на оператор ?, чтобы не «ронять» приложение при ошибке
-        self.perform_connect().await.context("Ошибка переподключения")?;
-        Ok(())
-    }
-
-    async fn perform_connect(&mut self) -> Result<()> {
-        let id = self.device_id.as_ref().ok_or_else(|| anyhow::anyhow!("ID не найден"))?;
-        let conn = AudioPlaybackConnection::TryCreateFromId(id)?;
+    async fn perform_connect(&mut self, id: &str) -> Result<()> {
+        let conn = AudioPlaybackConnection::TryCreateFromId(&HSTRING::from(id))?;
         conn.Start()?;
 
         let result = conn.OpenAsync()?.await?;
         if result.Status()? != AudioPlaybackConnectionOpenResultStatus::Success {
-            anyhow::bail!("Статус открытия: {:?}", result.Status()?);
+            anyhow::bail!("{}", Msg::OpenStatus { status: &format!("{:?}", result.Status()?) });
         }
 
-        println!("[CONN] Соединение активно");
-        self.connection = Some(conn);
+        log::info!(target: "receiver", "{}", Msg::LogConnectionActive { id: &id });
+        let mut session = Session {
+            state_token: Some(self.watch_connection_state(&conn, id)?),
+            connection: conn,
+            graph: None,
+            stream: None,
+            volume: Volume::default(),
+            output: None,
+            tap: TapSlot::default(),
+            dsp: DspSlot::default(),
+            is_monitoring: Arc::new(AtomicBool::new(true)),
+        };
 
-        // 1. Удержание канала через AudioGraph (Prevent Sleep)
-        self.prevent_sleep_with_anchor().await?;
+        // 1. Удержание канала через AudioGraph (Prevent Sleep) и звук телефона через него же
+        match self.build_graph(id, None, &session.tap, &session.dsp).await {
+            Ok((graph, stream)) => {
+                session.graph = Some(graph);
+                session.stream = stream;
+            }
+            Err(e) => {
+                session.close();
+                return Err(e);
+            }
+        }
+        let _ = self.events.send(BackendEvent::Connected(id.to_string()));
 
         // 2. Мониторинг (Heartbeat)
-        self.start_heartbeat_monitor();
+        self.start_heartbeat_monitor(&session, id);
+        self.sessions.insert(id.to_string(), session);
 
         // 3. MMCSS (Multi-Media Class Scheduler Service)
-        unsafe {
-            let mut task_index = 0u32;
-            if let Ok(handle) = AvSetMmThreadCharacteristicsW(w!("Pro Audio"), &mut task_index) {
-                self.avrt_handle = Some(handle);
+        if self.avrt_handle.is_none() {
+            unsafe {
+                let mut task_index = 0u32;
+                let task = HSTRING::from(self.settings.mmcss_task.as_str());
+                if let Ok(handle) = AvSetMmThreadCharacteristicsW(&task, &mut task_index) {
+                    self.avrt_handle = Some(handle);
+                }
             }
         }
 
         Ok(())
     }
 
-    fn start_heartbeat_monitor(&self) {
-        // Останавливаем предыдущий монитор, если он был
-        self.is_monitoring.store(false, Ordering::SeqCst);
-
-        let is_monitoring = self.is_monitoring.clone();
-        let device_id = self.device_id.clone();
+    // Телефон может закрыть соединение сам (ушёл из зоны, выключил Bluetooth).
+    // Windows сообщает об этом через StateChanged — пересылаем воркеру.
+    fn watch_connection_state(&self, conn: &AudioPlaybackConnection, id: &str) -> Result<EventRegistrationToken> {
+        let events = self.events.clone();
+        let id = id.to_string();
+        let handler = TypedEventHandler::new(move |sender: &Option<AudioPlaybackConnection>, _| {
+            if let Some(conn) = sender {
+                if conn.State()? == AudioPlaybackConnectionState::Closed {
+                    log::warn!(target: "receiver", "{}", Msg::LogClosedByWindows { id: &id });
+                    let _ = events.send(BackendEvent::ConnectionLost(id.clone()));
+                }
+            }
+            Ok(())
+        });
+        Ok(conn.StateChanged(&handler)?)
+    }
 
-        is_monitoring.store(true, Ordering::SeqCst);
+    // Страховка на случай, если StateChanged не пришёл: раз в интервал смотрим на State().
+    // Само соединение не трогаем — переподключением занимается воркер.
+    fn start_heartbeat_monitor(&self, session: &Session, id: &str) {
+        let is_monitoring = session.is_monitoring.clone();
+        let device_id = HSTRING::from(id);
+        let connection = session.connection.clone();
+        let events = self.events.clone();
+        let interval = std::time::Duration::from_secs(self.settings.heartbeat_interval_secs);
 
         tokio::spawn(async move {
-            let mut tick = 0u64;
-            println!("[MONITOR] Heartbeat запущен.");
+            log::debug!(target: "monitor", "{}", Msg::LogHeartbeatStarted { id: &device_id });
 
             while is_monitoring.load(Ordering::SeqCst) {
-                tick += 1;
-
-                if let Some(ref id) = device_id {
-                    if let Ok(conn) = AudioPlaybackConnection::TryCreateFromId(id) {
-                        let _ = conn.Start();
-                        // Раз в 30 секунд делаем более "тяжелый" пинг для сброса таймеров Windows
-                        if tick % 15 == 0 {
-                            let _ = conn.OpenAsync();
-                        }
-                    }
+                if connection.State().is_ok_and(|s| s == AudioPlaybackConnectionState::Closed) {
+                    log::warn!(target: "monitor", "{}", Msg::LogHeartbeatClosed { id: &device_id });
+                    let _ = events.send(BackendEvent::ConnectionLost(device_id.to_string()));
+                    break;
                 }
-                tokio::time::sleep(std::time::Duration::from_secs(2)).await;
+                tokio::time::sleep(interval).await;
+            }
+            log::debug!(target: "monitor", "{}", Msg::LogHeartbeatStopped { id: &device_id });
+        });
+    }
+
+    // Якорный граф соединения со звуком телефона на выходе `output` (`None` — по умолчанию)
+    async fn build_graph(
+        &self,
+        id: &str,
+        output: Option<&str>,
+        tap: &TapSlot,
+        dsp: &DspSlot,
+    ) -> Result<(AudioGraph, Option<AudioDeviceInputNode>)> {
+        let (graph, output_node) = self.prevent_sleep_with_anchor(output).await?;
+
+        // Звук телефона — через тот же граф, иначе громкость не изменить
+        let mut stream = None;
+        if let Some(output_node) = &output_node {
+            match self.attach_stream(&graph, output_node, id, dsp).await {
+                Ok(node) => stream = Some(node),
+                Err(e) => log::warn!(target: "receiver", "{}", Msg::LogStreamPathUnavailable { id: &id, error: &e }),
+            }
+        }
+        if let Some(stream) = &stream {
+            if let Err(e) = self.attach_tap(&graph, stream, tap) {
+                log::warn!(target: "recording", "{}", Msg::LogTapFailed { name: &id, error: &e });
+            }
+        }
+
+        let started = self.watch_graph(&graph, id).and_then(|()| Ok(graph.Start()?));
+        if let Err(e) = started {
+            let _ = graph.Close();
+            return Err(e);
+        }
+        Ok((graph, stream))
+    }
+
+    // Копия звука телефона на каждом кванте графа идёт в индикатор уровня и в отвод, если он есть
+    fn attach_tap(&self, graph: &AudioGraph, stream: &AudioDeviceInputNode, tap: &TapSlot) -> Result<()> {
+        let frame_output = graph.CreateFrameOutputNode()?;
+        stream.AddOutgoingConnection(&frame_output)?;
+        let properties = graph.EncodingProperties()?;
+        let format = AudioFormat { sample_rate: properties.SampleRate()?, channels: properties.ChannelCount()? as u16 };
+
+        let tap = tap.clone();
+        let meter = self.meter.clone();
+        let handler = TypedEventHandler::new(move |_: &Option<AudioGraph>, _: &Option<IInspectable>| {
+            // Кадр забираем, даже когда записи нет: иначе узел копит звук
+            let samples = read_frame(&frame_output.GetFrame()?)?;
+            meter.feed(&samples);
+            if let Some(tap) = tap.lock().unwrap().as_ref() {
+                tap.send(AudioChunk { format, samples });
             }
-            println!("[MONITOR] Heartbeat остановлен.");
+            Ok(())
         });
+        graph.QuantumStarted(&handler)?;
+        Ok(())
     }
 
-    async fn prevent_sleep_with_anchor(&mut self) -> Result<()> {
+    // Звук идёт на выход не напрямую, а через цепочку обработки: на каждом кванте забираем
+    // кадр, обрабатываем и отдаём узлу, который играет на выход. Это стоит кванта задержки,
+    // поэтому без обработки звук идёт напрямую.
+    fn attach_dsp(&self, graph: &AudioGraph, stream: &AudioDeviceInputNode, output_node: &AudioDeviceOutputNode, dsp: &DspSlot) -> Result<()> {
+        let properties = graph.EncodingProperties()?;
+        let format = AudioFormat { sample_rate: properties.SampleRate()?, channels: properties.ChannelCount()? as u16 };
+        let frame_output = graph.CreateFrameOutputNode()?;
+        let frame_input = graph.CreateFrameInputNode()?;
+        frame_input.AddOutgoingConnection(output_node)?;
+        stream.AddOutgoingConnection(&frame_output)?;
+
+        let dsp = dsp.clone();
+        let handler = TypedEventHandler::new(move |_: &Option<AudioGraph>, _: &Option<IInspectable>| {
+            let mut samples = read_frame(&frame_output.GetFrame()?)?;
+            dsp.lock().unwrap().process(&mut samples, format);
+            frame_input.AddFrame(&write_frame(&samples)?)?;
+            Ok(())
+        });
+        graph.QuantumStarted(&handler)?;
+        Ok(())
+    }
+
+    // Выход графа пропал (выдернули ЦАП, выключили монитор) — дальше граф не играет
+    fn watch_graph(&self, graph: &AudioGraph, id: &str) -> Result<()> {
+        let events = self.events.clone();
+        let id = id.to_string();
+        let handler = TypedEventHandler::new(
+            move |_: &Option<AudioGraph>, args: &Option<AudioGraphUnrecoverableErrorOccurredEventArgs>| {
+                if let Some(args) = args {
+                    if args.Error()? == AudioGraphUnrecoverableError::AudioDeviceLost {
+                        log::warn!(target: "receiver", "{}", Msg::LogGraphOutputLost { id: &id });
+                        let _ = events.send(BackendEvent::OutputLost(id.clone()));
+                    }
+                }
+                Ok(())
+            },
+        );
+        graph.UnrecoverableErrorOccurred(&handler)?;
+        Ok(())
+    }
+
+    // Граф ещё не запущен: запускает его `build_graph`, когда соберёт все узлы
+    async fn prevent_sleep_with_anchor(&self, output: Option<&str>) -> Result<(AudioGraph, Option<AudioDeviceOutputNode>)> {
         let settings = AudioGraphSettings::Create(AudioRenderCategory::Media)?;
+        if let Some(output) = output {
+            let unavailable = || anyhow::anyhow!("{}", Msg::OutputUnavailable { id: &output });
+            let device = DeviceInformation::CreateFromIdAsync(&HSTRING::from(output))?.await.map_err(|_| unavailable())?;
+            settings.SetPrimaryRenderDevice(&device)?;
+        }
         // Устанавливаем квант времени для уменьшения нагрузки
-        settings.SetQuantumSizeSelectionMode(QuantumSizeSelectionMode::LowestLatency)?;
+        settings.SetQuantumSizeSelectionMode(match self.settings.quantum_size {
+            QuantumSize::LowestLatency => QuantumSizeSelectionMode::LowestLatency,
+            QuantumSize::SystemDefault => QuantumSizeSelectionMode::SystemDefault,
+        })?;
 
         let create_result = AudioGraph::CreateAsync(&settings)?.await?;
+        if create_result.Status()? != AudioGraphCreationStatus::Success {
+            anyhow::bail!("{:?}", create_result.Status()?);
+        }
         let graph = create_result.Graph()?;
 
         let output_result = graph.CreateDeviceOutputNodeAsync()?.await?;
-        if output_result.Status()? == AudioDeviceNodeCreationStatus::Success {
-            let output_node = output_result.DeviceOutputNode()?;
+        if output_result.Status()? != AudioDeviceNodeCreationStatus::Success {
+            // Без выбранного выхода граф бесполезен, а выход по умолчанию может и подождать
+            if let Some(output) = output {
+                let _ = graph.Close();
+                anyhow::bail!("{}: {:?}", Msg::OutputUnavailable { id: &output }, output_result.Status()?);
+            }
+            return Ok((graph, None));
+        }
+        let output_node = output_result.DeviceOutputNode()?;
 
-            // Создаем тишину
-            let frame_input = graph.CreateFrameInputNode()?;
-            frame_input.AddOutgoingConnection(&output_node)?;
+        // Создаем тишину
+        let frame_input = graph.CreateFrameInputNode()?;
+        frame_input.AddOutgoingConnection(&output_node)?;
 
-            // Уровень усиления 0.0001 достаточен, чтобы Windows считала поток активным,
-            // но пользователь ничего не слышал.
-            output_node.SetOutgoingGain(0.0001)?;
+        // Уровня усиления порядка 0.0001 достаточно, чтобы Windows считала поток активным,
+        // но пользователь ничего не слышал. Усиление ставится на саму тишину, а не на выход:
+        // через выход идёт и звук телефона.
+        frame_input.SetOutgoingGain(self.settings.anchor_gain)?;
+
+        Ok((graph, Some(output_node)))
+    }
+
+    // Телефон после OpenAsync виден как звуковой вход с тем же контейнером, что и
+    // Bluetooth-устройство; находим его и подаём на выход графа
+    async fn attach_stream(
+        &self,
+        graph: &AudioGraph,
+        output_node: &AudioDeviceOutputNode,
+        id: &str,
+        dsp: &DspSlot,
+    ) -> Result<AudioDeviceInputNode> {
+        const CONTAINER_ID: &str = "System.Devices.Aep.ContainerId";
+        // IIterable не Send, поэтому не должен дожить до await
+        let lookup = DeviceInformation::CreateFromIdAsyncAdditionalProperties(
+            &HSTRING::from(id),
+            &IIterable::<HSTRING>::try_from(vec![HSTRING::from(CONTAINER_ID)])?,
+        )?;
+        let info = lookup.await?;
+        let container: GUID = info.Properties()?.Lookup(&HSTRING::from(CONTAINER_ID))?.cast::<IReference<GUID>>()?.Value()?;
+
+        let selector = format!("{} AND System.Devices.ContainerId:=\"{{{:?}}}\"", MediaDevice::GetAudioCaptureSelector()?, container);
+        let endpoints = DeviceInformation::FindAllAsyncAqsFilter(&HSTRING::from(selector))?.await?;
+        let endpoint = endpoints.First()?.Current().map_err(|_| anyhow::anyhow!("{}", Msg::NoCaptureEndpoint))?;
+
+        let result = graph
+            .CreateDeviceInputNodeWithFormatOnDeviceAsync(MediaCategory::Media, &graph.EncodingProperties()?, &endpoint)?
+            .await?;
+        if result.Status()? != AudioDeviceNodeCreationStatus::Success {
+            anyhow::bail!("{:?}", result.Status()?);
+        }
+        let stream = result.DeviceInputNode()?;
+        if dsp.lock().unwrap().is_empty() {
+            stream.AddOutgoingConnection(output_node)?;
+        } else {
+            self.attach_dsp(graph, &stream, output_node, dsp)?;
         }
+        Ok(stream)
+    }
+}
+
+// Отсчёты кадра графа; формат графа — 32-битные float
+fn read_frame(frame: &AudioFrame) -> windows::core::Result<Vec<f32>> {
+    let buffer = frame.LockBuffer(AudioBufferAccessMode::Read)?;
+    let reference = buffer.CreateReference()?;
+    let access: IMemoryBufferByteAccess = reference.cast()?;
+    let (mut data, mut capacity) = (std::ptr::null_mut(), 0u32);
+    unsafe { access.GetBuffer(&mut data, &mut capacity)? };
+    let length = buffer.Length()?.min(capacity) as usize / std::mem::size_of::<f32>();
+    // Буфер заблокирован на чтение, пока живут `buffer` и `reference`
+    let samples = unsafe { std::slice::from_raw_parts(data as *const f32, length) }.to_vec();
+    Ok(samples)
+}
 
-        graph.Start()?;
-        self.graph = Some(graph);
+// Кадр графа с отсчётами `samples`
+fn write_frame(samples: &[f32]) -> windows::core::Result<AudioFrame> {
+    let bytes = std::mem::size_of_val(samples) as u32;
+    let frame = AudioFrame::Create(bytes)?;
+    // Кадр нельзя отдать графу, пока буфер заблокирован: блокировка живёт только в этом блоке
+    {
+        let buffer = frame.LockBuffer(AudioBufferAccessMode::Write)?;
+        let reference = buffer.CreateReference()?;
+        let access: IMemoryBufferByteAccess = reference.cast()?;
+        let (mut data, mut capacity) = (std::ptr::null_mut(), 0u32);
+        unsafe { access.GetBuffer(&mut data, &mut capacity)? };
+        let length = samples.len().min(capacity as usize / std::mem::size_of::<f32>());
+        unsafe { std::ptr::copy_nonoverlapping(samples.as_ptr(), data as *mut f32, length) };
+        buffer.SetLength(bytes.min(capacity))?;
+    }
+    Ok(frame)
+}
+
+impl Session {
+    // Останавливает монитор и освобождает ресурсы соединения
+    fn close(mut self) {
+        // Сигнализируем монитору остановиться
+        self.is_monitoring.store(false, Ordering::SeqCst);
+
+        self.close_graph();
+        if let Some(token) = self.state_token {
+            let _ = self.connection.RemoveStateChanged(token);
+        }
+        let _ = self.connection.Close();
+    }
+
+    fn close_graph(&mut self) {
+        self.stream = None;
+        if let Some(graph) = self.graph.take() {
+            let _ = graph.Stop();
+            let _ = graph.Close(); // Явное закрытие ресурсов
+        }
+    }
+}
+
+impl Default for BTReceiver {
+    fn default() -> Self {
+        Self::new(ReceiverConfig::default())
+    }
+}
+
+impl AudioSinkBackend for BTReceiver {
+    async fn connect(&mut self, device: &BTDevice) -> Result<()> {
+        // Повторное подключение того же устройства не должно оставлять висеть старый граф и монитор
+        if self.sessions.contains_key(&device.id) {
+            self.disconnect(&device.id).await;
+        }
+        log::info!(target: "receiver", "{}", Msg::LogConnecting { name: &device.name });
+        self.perform_connect(&device.id).await
+    }
+
+    async fn reconnect(&mut self, device: &BTDevice) -> Result<()> {
+        log::info!(target: "receiver", "{}", Msg::LogReconnecting { name: &device.name });
+        self.disconnect(&device.id).await;
+        // Заменяем expect на оператор ?, чтобы не «ронять» приложение при ошибке
+        self.perform_connect(&device.id).await.with_context(|| Msg::ReconnectFailed.to_string())?;
         Ok(())
     }
 
-    pub async fn list_devices(&self) -> Result<Vec<BTDevice>> {
+    async fn list_devices(&self) -> Result<Vec<BTDevice>> {
         let selector = AudioPlaybackConnection::GetDeviceSelector()?;
         let devices = DeviceInformation::FindAllAsyncAqsFilter(&selector)?.await?;
 
         let mut result = Vec::new();
         for info in devices {
             if let Ok(name) = info.Name() {
-                result.push(BTDevice { name: name.to_string(), info });
+                result.push(BTDevice { id: info.Id()?.to_string(), name: name.to_string() });
             }
         }
         Ok(result)
     }
 
-    pub async fn disconnect(&mut self) {
-        // Сигнализируем монитору остановиться
-        self.is_monitoring.store(false, Ordering::SeqCst);
+    async fn disconnect(&mut self, id: &str) {
+        let Some(session) = self.sessions.remove(id) else { return };
+        session.close();
 
-        if let Some(graph) = self.graph.take() {
-            let _ = graph.Stop();
-            let _ = graph.Close(); // Явное закрытие ресурсов
+        if self.sessions.is_empty() {
+            self.avrt_handle = None;
         }
 
-        self.connection = None;
-        self.avrt_handle = None;
+        let _ = self.events.send(BackendEvent::Disconnected(id.to_string()));
+        log::info!(target: "receiver", "{}", Msg::LogConnectionClosed { id: &id });
+    }
 
-        println!("[DISCONN] Соединение закрыто.");
+    fn set_volume(&mut self, id: &str, volume: Volume) -> Result<()> {
+        let Some(session) = self.sessions.get_mut(id) else { return Ok(()) };
+        session.volume = volume;
+        let Some(stream) = &session.stream else { anyhow::bail!("{}", Msg::VolumeUnavailable) };
+        stream.SetOutgoingGain(volume.gain())?;
+        Ok(())
     }
-}
\ No newline at end of file
+
+    fn set_tap(&mut self, id: &str, tap: Option<Tap>) -> Result<()> {
+        if let Some(session) = self.sessions.get(id) {
+            *session.tap.lock().unwrap() = tap;
+        }
+        Ok(())
+    }
+
+    fn level_meter(&self) -> LevelMeter {
+        self.meter.clone()
+    }
+
+    async fn list_outputs(&self) -> Result<Vec<AudioOutput>> {
+        let devices = DeviceInformation::FindAllAsyncDeviceClass(DeviceClass::AudioRender)?.await?;
+
+        let mut result = Vec::new();
+        for info in devices {
+            if let Ok(name) = info.Name() {
+                result.push(AudioOutput { id: info.Id()?.to_string(), name: name.to_string() });
+            }
+        }
+        Ok(result)
+    }
+
+    async fn set_output(&mut self, id: &str, output: Option<&str>) -> Result<()> {
+        // Старый граф закрываем до сборки нового, чтобы звук не шёл на два выхода сразу
+        let (volume, tap, dsp) = match self.sessions.get_mut(id) {
+            Some(session) => {
+                session.close_graph();
+                (session.volume, session.tap.clone(), session.dsp.clone())
+            }
+            None => return Ok(()),
+        };
+
+        let (result, (graph, stream)) = match self.build_graph(id, output, &tap, &dsp).await {
+            Ok(built) => (Ok(()), built),
+            // Выбранного выхода нет — соединение не должно остаться без графа
+            Err(e) if output.is_some() => (Err(e), self.build_graph(id, None, &tap, &dsp).await?),
+            Err(e) => return Err(e),
+        };
+        if let Some(stream) = &stream {
+            stream.SetOutgoingGain(volume.gain())?;
+        }
+        if let Some(session) = self.sessions.get_mut(id) {
+            session.graph = Some(graph);
+            session.stream = stream;
+            session.output = output.filter(|_| result.is_ok()).map(str::to_string);
+        }
+        result
+    }
+
+    async fn set_dsp(&mut self, id: &str, settings: DspSettings) -> Result<()> {
+        let Some(session) = self.sessions.get(id) else { return Ok(()) };
+        let rebuild = session.dsp.lock().unwrap().is_empty() != settings.is_empty();
+        let output = session.output.clone();
+        *session.dsp.lock().unwrap() = DspChain::new(settings);
+        // Идёт ли звук через цепочку, решается при сборке графа
+        if rebuild {
+            self.set_output(id, output.as_deref()).await?;
+        }
+        Ok(())
+    }
+
+    fn watch_devices(&mut self) -> Result<()> {
+        let selector = AudioPlaybackConnection::GetDeviceSelector()?;
+        let watcher = DeviceInformation::CreateWatcherAqsFilter(&selector)?;
+
+        // DeviceInformationUpdate содержит только изменившиеся свойства,
+        // поэтому храним полные DeviceInformation и обновляем их через Update()
+        let known: Arc<Mutex<HashMap<String, DeviceInformation>>> = Arc::default();
+
+        let (events, devices) = (self.events.clone(), known.clone());
+        watcher.Added(&TypedEventHandler::new(move |_: &Option<DeviceWatcher>, info: &Option<DeviceInformation>| {
+            if let Some(info) = info {
+                let device = BTDevice { id: info.Id()?.to_string(), name: info.Name()?.to_string() };
+                devices.lock().unwrap().insert(device.id.clone(), info.clone());
+                let _ = events.send(BackendEvent::DeviceAdded(device));
+            }
+            Ok(())
+        }))?;
+
+        let (events, devices) = (self.events.clone(), known.clone());
+        watcher.Updated(&TypedEventHandler::new(move |_: &Option<DeviceWatcher>, update: &Option<DeviceInformationUpdate>| {
+            if let Some(update) = update {
+                let id = update.Id()?.to_string();
+                if let Some(info) = devices.lock().unwrap().get(&id) {
+                    info.Update(update)?;
+                    let device = BTDevice { id, name: info.Name()?.to_string() };
+                    let _ = events.send(BackendEvent::DeviceUpdated(device));
+                }
+            }
+            Ok(())
+        }))?;
+
+        let (events, devices) = (self.events.clone(), known);
+        watcher.Removed(&TypedEventHandler::new(move |_: &Option<DeviceWatcher>, update: &Option<DeviceInformationUpdate>| {
+            if let Some(update) = update {
+                let id = update.Id()?.to_string();
+                devices.lock().unwrap().remove(&id);
+                let _ = events.send(BackendEvent::DeviceRemoved(id));
+            }
+            Ok(())
+        }))?;
+
+        watcher.Start()?;
+        log::info!(target: "receiver", "{}", Msg::LogWatchStarted);
+        self.watcher = Some(watcher);
+        Ok(())
+    }
+
+    fn subscribe(&self) -> broadcast::Receiver<BackendEvent> {
+        self.events.subscribe()
+    }
+}
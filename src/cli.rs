@@ -0,0 +1,104 @@
+use crate::backend::BTDevice;
+use crate::i18n::Msg;
+use crate::volume::VolumeChange;
+use anyhow::Result;
+use clap::{Parser, Subcommand};
+use std::path::PathBuf;
+
+/// Приёмник звука с телефона по Bluetooth. Без команды запускается в трее.
+#[derive(Debug, Parser)]
+#[command(name = "BT-Audio-Receiver", version)]
+pub struct Cli {
+    /// Путь к файлу настроек вместо `%APPDATA%\BT-Audio-Receiver\config.toml`.
+    #[arg(long, global = true, value_name = "ПУТЬ")]
+    pub config: Option<PathBuf>,
+
+    /// Завершить уже запущенный экземпляр и занять его место.
+    #[arg(long)]
+    pub replace: bool,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Subcommand)]
+pub enum Command {
+    /// Показать доступные устройства.
+    List {
+        #[arg(long)]
+        json: bool,
+    },
+    /// Подключиться к устройству по id или имени. Если приёмник не запущен,
+    /// команда сама держит соединение до Ctrl+C.
+    Connect {
+        /// `BTDevice::id` или имя устройства (без учёта регистра).
+        device: String,
+    },
+    /// Отключить устройство, а без аргумента — все.
+    Disconnect {
+        /// `BTDevice::id` или имя устройства.
+        device: Option<String>,
+    },
+    /// Изменить громкость принятого звука: `80`, `+10`, `-10`, `mute`, `unmute` или `toggle`.
+    Volume {
+        #[arg(allow_hyphen_values = true)]
+        change: VolumeChange,
+        /// `BTDevice::id` или имя; по умолчанию — последнее подключённое устройство.
+        #[arg(long)]
+        device: Option<String>,
+    },
+    /// Показать состояние соединений.
+    Status {
+        #[arg(long)]
+        json: bool,
+        /// Печатать каждое изменение состояния до Ctrl+C.
+        #[arg(long)]
+        watch: bool,
+    },
+    /// Применить настройки реестра для стабильного A2DP (нужны права администратора).
+    FixRegistry,
+    /// Проверить наличие новой версии.
+    CheckUpdate {
+        /// Сразу установить найденное обновление, без вопросов.
+        #[arg(long)]
+        install: bool,
+    },
+}
+
+/// Почему не удалось выбрать устройство: HTTP API отвечает на это по-разному.
+#[derive(Debug)]
+pub enum DeviceLookupError {
+    NotFound { query: String },
+    /// Под этим именем несколько устройств; их id по строке с отступом.
+    Ambiguous { query: String, ids: String },
+}
+
+impl std::fmt::Display for DeviceLookupError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DeviceLookupError::NotFound { query } => write!(f, "{}", Msg::DeviceNotFound { query }),
+            DeviceLookupError::Ambiguous { query, ids } => write!(f, "{}", Msg::DeviceAmbiguous { query, ids }),
+        }
+    }
+}
+
+impl std::error::Error for DeviceLookupError {}
+
+/// Находит устройство по точному id, а если такого нет — по имени без учёта регистра.
+/// Несколько устройств с одним именем — ошибка: подключаться наугад нельзя.
+pub fn resolve_device(devices: &[BTDevice], query: &str) -> Result<BTDevice, DeviceLookupError> {
+    if let Some(device) = devices.iter().find(|d| d.id == query) {
+        return Ok(device.clone());
+    }
+
+    let query_lower = query.to_lowercase();
+    let by_name: Vec<&BTDevice> = devices.iter().filter(|d| d.name.to_lowercase() == query_lower).collect();
+    match by_name.as_slice() {
+        [device] => Ok((*device).clone()),
+        [] => Err(DeviceLookupError::NotFound { query: query.to_string() }),
+        several => {
+            let ids = several.iter().map(|d| format!("  {}", d.id)).collect::<Vec<_>>().join("\n");
+            Err(DeviceLookupError::Ambiguous { query: query.to_string(), ids })
+        }
+    }
+}
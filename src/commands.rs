@@ -0,0 +1,187 @@
+use crate::updater::{UpdateCheck, Updater};
+use crate::utils;
+use anyhow::Result;
+use bt_audio_receiver::backend::{AudioSinkBackend, BTDevice};
+use bt_audio_receiver::bluetooth_receiver::BTReceiver;
+use bt_audio_receiver::cli::{resolve_device, Command};
+use bt_audio_receiver::config::Config;
+use bt_audio_receiver::i18n::Msg;
+use bt_audio_receiver::ipc::{IpcClient, Request, Response};
+use bt_audio_receiver::state::{ConnectionState, ReceiverState, StatusReport};
+use bt_audio_receiver::worker::{background_worker, AppCommand, WorkerOptions};
+use tokio::sync::{mpsc, watch};
+
+/// В релизе у exe подсистема `windows`, и без этого вывод команд не попал бы в консоль,
+/// из которой их запустили.
+pub fn attach_console() {
+    use windows::Win32::System::Console::{AttachConsole, ATTACH_PARENT_PROCESS};
+    unsafe {
+        let _ = AttachConsole(ATTACH_PARENT_PROCESS);
+    }
+}
+
+/// Выполняет команды, которым не нужен ни приёмник, ни запущенный экземпляр.
+pub async fn run_standalone(command: Command) -> Result<()> {
+    match command {
+        Command::FixRegistry => utils::run_registry_fix(),
+        Command::CheckUpdate { install } => check_update(install).await,
+        _ => unreachable!("команды приёмника передаются через forward или run_local"),
+    }
+}
+
+/// Передаёт запуск уже работающему экземпляру: команду, а без неё — просьбу показаться.
+pub async fn forward(command: Option<Command>, mut client: IpcClient) -> Result<()> {
+    match command {
+        Some(command) => run_remote(command, client).await,
+        None => match client.request(&Request::Activate).await? {
+            Response::Error { message, .. } => anyhow::bail!(message),
+            _ => Ok(()),
+        },
+    }
+}
+
+/// Выполняет команду без трея, когда приёмник не запущен.
+pub async fn run_local(command: Command, config: &Config) -> Result<()> {
+    match command {
+        Command::List { json } => {
+            let devices = BTReceiver::new(config.receiver.clone()).list_devices().await?;
+            print_devices(&devices, json)
+        }
+        Command::Connect { device } => connect(config, &device).await,
+        Command::Disconnect { .. } => {
+            println!("{}", Msg::CliNothingToDisconnect);
+            Ok(())
+        }
+        Command::Volume { .. } => {
+            println!("{}", Msg::CliNotRunning);
+            Ok(())
+        }
+        Command::Status { json, .. } => print_status(&StatusReport::default(), json),
+        Command::FixRegistry | Command::CheckUpdate { .. } => unreachable!("выполняются без приёмника"),
+    }
+}
+
+async fn run_remote(command: Command, mut client: IpcClient) -> Result<()> {
+    let request = match &command {
+        Command::List { .. } => Request::List,
+        Command::Connect { device } => Request::Connect { device: device.clone() },
+        Command::Disconnect { device } => Request::Disconnect { device: device.clone() },
+        Command::Volume { change, device } => Request::SetVolume { device: device.clone(), change: *change },
+        Command::Status { watch: false, .. } => Request::Status,
+        Command::Status { watch: true, .. } => Request::Subscribe,
+        Command::FixRegistry | Command::CheckUpdate { .. } => unreachable!("выполняются без приёмника"),
+    };
+    let json = matches!(command, Command::List { json: true } | Command::Status { json: true, .. });
+
+    match client.request(&request).await? {
+        Response::Error { message, .. } => anyhow::bail!(message),
+        Response::Devices { devices } => print_devices(&devices, json),
+        Response::Status(report) => print_status(&report, json),
+        Response::Ok if request == Request::Subscribe => {
+            loop {
+                tokio::select! {
+                    _ = tokio::signal::ctrl_c() => return Ok(()),
+                    message = client.next() => match message? {
+                        Some(Response::Event(report)) => print_status(&report, json)?,
+                        Some(_) => {}
+                        None => return Ok(()),
+                    },
+                }
+            }
+        }
+        Response::Ok | Response::Event(_) => Ok(()),
+    }
+}
+
+fn print_devices(devices: &[BTDevice], json: bool) -> Result<()> {
+    if json {
+        println!("{}", serde_json::to_string_pretty(&devices)?);
+    } else if devices.is_empty() {
+        println!("{}", Msg::MenuNoDevices);
+    } else {
+        for device in devices {
+            println!("{}\t{}", device.name, device.id);
+        }
+    }
+    Ok(())
+}
+
+// Соединение живёт, пока жив процесс, поэтому команда не завершается до Ctrl+C.
+// Переподключение работает по тем же правилам, что и в трее.
+async fn connect(config: &Config, query: &str) -> Result<()> {
+    let mut receiver = BTReceiver::new(config.receiver.clone());
+    let device = resolve_device(&receiver.list_devices().await?, query)?;
+
+    // Только указанное устройство: автоподключение и приоритеты здесь ни к чему
+    let options = WorkerOptions {
+        auto_connect: Default::default(),
+        priority: Vec::new(),
+        preempt: false,
+        ..config.worker_options()
+    };
+    let (tx_dev, _) = watch::channel(Vec::<BTDevice>::new());
+    let (tx_state, mut rx_state) = watch::channel(ReceiverState::default());
+    let (cmd_tx, cmd_rx) = mpsc::channel::<AppCommand>(10);
+
+    let worker = tokio::spawn(async move { background_worker(&mut receiver, options, tx_dev, tx_state, cmd_rx).await });
+    cmd_tx.send(AppCommand::Connect(device.id.clone())).await?;
+
+    // Состояние меняется и от громкости, и от того, что играет: печатаем только переходы
+    let mut last = ConnectionState::Idle;
+    let result = loop {
+        tokio::select! {
+            _ = tokio::signal::ctrl_c() => break Ok(()),
+            changed = rx_state.changed() => {
+                if changed.is_err() {
+                    break Ok(());
+                }
+                let state = rx_state.borrow_and_update().get(&device.id).clone();
+                if state == last {
+                    continue;
+                }
+                if let ConnectionState::Failed { reason, .. } = state {
+                    break Err(anyhow::anyhow!(reason));
+                }
+                if let Some(label) = state.label(&device.name) {
+                    println!("{label}");
+                }
+                if state.is_connected() {
+                    println!("{}", Msg::CliHoldingConnection);
+                }
+                last = state;
+            }
+        }
+    };
+
+    let _ = cmd_tx.send(AppCommand::DisconnectAll).await;
+    drop(cmd_tx);
+    let _ = worker.await;
+    result
+}
+
+pub fn print_status(report: &StatusReport, json: bool) -> Result<()> {
+    if json {
+        println!("{}", serde_json::to_string_pretty(report)?);
+        return Ok(());
+    }
+    for line in report.lines() {
+        println!("{line}");
+    }
+    Ok(())
+}
+
+async fn check_update(install: bool) -> Result<()> {
+    let current = self_update::cargo_crate_version!();
+    match Updater::check().await? {
+        UpdateCheck::NoReleases => println!("{}", Msg::NoReleasesText),
+        UpdateCheck::UpToDate => println!("{}", Msg::CliUpToDate { version: &current }),
+        UpdateCheck::Available(latest) => {
+            println!("{}", Msg::CliUpdateAvailable { latest: &latest, current: &current });
+            if install {
+                Updater::perform_update().await?;
+                log::info!(target: "updater", "{}", Msg::LogUpdateInstalled { version: &latest });
+            }
+        }
+    }
+    Ok(())
+}
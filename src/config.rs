@@ -0,0 +1,1028 @@
+use crate::dsp::DspSettings;
+use crate::dynamics::{CompressorSettings, LimiterSettings};
+use crate::equalizer::EqPreset;
+use crate::i18n::{Lang, Msg};
+use crate::reconnect::ReconnectPolicy;
+use crate::recording::{self, RecordingFormat, RecordingSettings};
+use crate::state::ReceiverState;
+use crate::volume::Volume;
+use crate::worker::WorkerOptions;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::watch;
+
+/// Текущая версия схемы файла. Увеличивается при несовместимых изменениях,
+/// старые файлы приводятся к ней через `MIGRATIONS`.
+pub const CONFIG_VERSION: u32 = 1;
+
+const APP_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub version: u32,
+    /// Версия программы, которая последней записала файл.
+    pub app_version: String,
+    /// Последнее устройство, к которому удалось подключиться (`BTDevice::id`).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_device: Option<String>,
+    pub receiver: ReceiverConfig,
+    pub autostart: AutostartConfig,
+    pub reconnect: ReconnectConfig,
+    pub auto_connect: AutoConnectConfig,
+    pub log: LogConfig,
+    pub ui: UiConfig,
+    pub http: HttpConfig,
+    pub mqtt: MqttConfig,
+    pub media: MediaConfig,
+    pub recording: RecordingConfig,
+    pub limiter: LimiterConfig,
+    pub night_mode: NightModeConfig,
+    /// Пресеты эквалайзера по имени; устройству назначаются через `devices.<id>.equalizer`.
+    pub equalizer: BTreeMap<String, EqPreset>,
+    /// Настройки отдельных устройств по `BTDevice::id`.
+    pub devices: BTreeMap<String, DeviceConfig>,
+    /// Настройки устройств вывода по `AudioOutput::id`.
+    pub outputs: BTreeMap<String, OutputConfig>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ReceiverConfig {
+    /// Как часто проверять, не закрылось ли соединение, о чём Windows не сообщила.
+    pub heartbeat_interval_secs: u64,
+    /// Громкость «якорного» потока тишины, который не даёт Windows усыпить канал.
+    pub anchor_gain: f64,
+    pub quantum_size: QuantumSize,
+    /// Задача MMCSS для потока приёмника.
+    pub mmcss_task: String,
+    /// Сколько телефонов может быть подключено одновременно.
+    pub max_connections: usize,
+    /// Громкость принятого звука (0–100) для устройств, у которых она не запомнена.
+    pub volume: u8,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum QuantumSize {
+    LowestLatency,
+    SystemDefault,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AutostartConfig {
+    /// Имя значения в HKCU\...\Run.
+    pub key_name: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ReconnectConfig {
+    pub initial_delay_ms: u64,
+    pub max_delay_ms: u64,
+    pub multiplier: f64,
+    pub jitter: f64,
+    pub max_attempts: u32,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AutoConnectConfig {
+    /// Как часто повторять попытку, пока устройство с автоподключением недоступно.
+    pub retry_interval_secs: u64,
+    /// Устройства (`BTDevice::id`) от самого желанного. Свободный приёмник
+    /// подключается к первому доступному из них.
+    pub priority: Vec<String>,
+    /// Разрывать соединение с менее важным устройством, когда появилось более важное.
+    pub preempt: bool,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct UiConfig {
+    pub language: Language,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MediaConfig {
+    /// Перехватывать медиаклавиши клавиатуры и отдавать их подключённому телефону.
+    /// Пока телефон не подключён, они достаются тому, кто играет на компьютере.
+    pub global_keys: bool,
+}
+
+/// Запись принятого звука (включается в трее).
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RecordingConfig {
+    /// Папка для записей; по умолчанию — `BT-Audio-Receiver` в папке «Музыка».
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub folder: Option<PathBuf>,
+    pub format: RecordingFormat,
+    /// Начинать новый файл каждые столько мегабайт.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub split_size_mb: Option<u64>,
+    /// Начинать новый файл каждые столько минут.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub split_minutes: Option<u64>,
+}
+
+impl RecordingConfig {
+    pub fn settings(&self) -> RecordingSettings {
+        RecordingSettings {
+            folder: self.folder.clone().unwrap_or_else(recording::default_folder),
+            format: self.format,
+            max_bytes: self.split_size_mb.map(|mb| mb * 1024 * 1024),
+            max_duration: self.split_minutes.map(|minutes| Duration::from_secs(minutes * 60)),
+        }
+    }
+}
+
+/// Защита слуха: лимитер пиков и потолок громкости для всего принятого звука.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LimiterConfig {
+    pub enabled: bool,
+    /// Потолок пиков, дБFS.
+    pub ceiling_db: f64,
+    pub lookahead_ms: u64,
+    pub release_ms: u64,
+    /// Потолок средней громкости, дБFS; без него ограничиваются только пики.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_loudness_db: Option<f64>,
+}
+
+impl LimiterConfig {
+    pub fn settings(&self) -> Option<LimiterSettings> {
+        self.enabled.then(|| LimiterSettings {
+            ceiling_db: self.ceiling_db,
+            lookahead: Duration::from_millis(self.lookahead_ms),
+            release: Duration::from_millis(self.release_ms),
+            max_loudness_db: self.max_loudness_db,
+        })
+    }
+}
+
+/// Ночной режим: компрессор, который выравнивает громкость. Включается в трее.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NightModeConfig {
+    pub enabled: bool,
+    /// дБFS
+    pub threshold_db: f64,
+    pub ratio: f64,
+    pub attack_ms: u64,
+    pub release_ms: u64,
+    /// дБ
+    pub makeup_db: f64,
+}
+
+impl NightModeConfig {
+    pub fn settings(&self) -> CompressorSettings {
+        CompressorSettings {
+            threshold_db: self.threshold_db,
+            ratio: self.ratio,
+            attack: Duration::from_millis(self.attack_ms),
+            release: Duration::from_millis(self.release_ms),
+            makeup_db: self.makeup_db,
+            ..CompressorSettings::night_mode()
+        }
+    }
+}
+
+/// HTTP API для состояния и управления. По умолчанию выключен.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HttpConfig {
+    pub enabled: bool,
+    pub address: SocketAddr,
+    /// Если задан, запросы без `Authorization: Bearer <token>` отклоняются.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+}
+
+/// Публикация состояния в MQTT и обнаружение в Home Assistant. По умолчанию выключено.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MqttConfig {
+    pub enabled: bool,
+    pub host: String,
+    pub port: u16,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub username: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub password: Option<String>,
+    /// Он же идентификатор устройства в Home Assistant.
+    pub client_id: String,
+    /// Префикс топиков состояния и команд.
+    pub base_topic: String,
+    /// Префикс обнаружения Home Assistant; пустой — не публиковать.
+    pub discovery_prefix: String,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Language {
+    /// Язык системы.
+    #[default]
+    Auto,
+    Ru,
+    En,
+}
+
+impl Language {
+    pub fn resolve(self) -> Lang {
+        match self {
+            Language::Auto => Lang::detect(),
+            Language::Ru => Lang::Ru,
+            Language::En => Lang::En,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LogConfig {
+    pub level: LogLevel,
+    /// Уровни отдельных целей (`receiver`, `monitor`, ...), если нужны подробности только по ним.
+    pub targets: BTreeMap<String, LogLevel>,
+    /// Размер файла, после которого начинается новый.
+    pub max_file_size_kb: u64,
+    /// Сколько старых файлов хранить рядом с текущим.
+    pub max_files: u32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LogLevel {
+    Off,
+    Error,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DeviceConfig {
+    /// Подключаться автоматически при запуске и при появлении устройства.
+    pub auto_connect: bool,
+    /// Переопределения `[reconnect]` только для этого устройства.
+    pub reconnect: ReconnectOverride,
+    /// Последняя выставленная громкость (0–100); без неё — `receiver.volume`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub volume: Option<u8>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub muted: bool,
+    /// Устройство вывода (id звукового устройства Windows); без него — выход по умолчанию.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output: Option<String>,
+    /// Имя пресета из `[equalizer]`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub equalizer: Option<String>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct OutputConfig {
+    /// Имя пресета из `[equalizer]` для всего, что играет на этом выходе. Если у источника
+    /// есть свой пресет, сначала работает он.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub equalizer: Option<String>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ReconnectOverride {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub initial_delay_ms: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_delay_ms: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub multiplier: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub jitter: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_attempts: Option<u32>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            version: CONFIG_VERSION,
+            app_version: APP_VERSION.to_string(),
+            last_device: None,
+            receiver: ReceiverConfig::default(),
+            autostart: AutostartConfig::default(),
+            reconnect: ReconnectConfig::default(),
+            auto_connect: AutoConnectConfig::default(),
+            log: LogConfig::default(),
+            ui: UiConfig::default(),
+            http: HttpConfig::default(),
+            mqtt: MqttConfig::default(),
+            media: MediaConfig::default(),
+            recording: RecordingConfig::default(),
+            limiter: LimiterConfig::default(),
+            night_mode: NightModeConfig::default(),
+            equalizer: BTreeMap::new(),
+            devices: BTreeMap::new(),
+            outputs: BTreeMap::new(),
+        }
+    }
+}
+
+impl Default for ReceiverConfig {
+    fn default() -> Self {
+        Self {
+            heartbeat_interval_secs: 2,
+            anchor_gain: 0.0001,
+            quantum_size: QuantumSize::LowestLatency,
+            mmcss_task: "Pro Audio".to_string(),
+            max_connections: 1,
+            volume: 100,
+        }
+    }
+}
+
+impl Default for AutostartConfig {
+    fn default() -> Self {
+        Self { key_name: "BTAudioReceiver".to_string() }
+    }
+}
+
+impl Default for HttpConfig {
+    fn default() -> Self {
+        Self { enabled: false, address: SocketAddr::from(([127, 0, 0, 1], 8765)), token: None }
+    }
+}
+
+impl Default for MediaConfig {
+    fn default() -> Self {
+        Self { global_keys: true }
+    }
+}
+
+impl Default for LimiterConfig {
+    fn default() -> Self {
+        let limiter = LimiterSettings::default();
+        Self {
+            enabled: false,
+            ceiling_db: limiter.ceiling_db,
+            lookahead_ms: limiter.lookahead.as_millis() as u64,
+            release_ms: limiter.release.as_millis() as u64,
+            max_loudness_db: None,
+        }
+    }
+}
+
+impl Default for NightModeConfig {
+    fn default() -> Self {
+        let night = CompressorSettings::night_mode();
+        Self {
+            enabled: false,
+            threshold_db: night.threshold_db,
+            ratio: night.ratio,
+            attack_ms: night.attack.as_millis() as u64,
+            release_ms: night.release.as_millis() as u64,
+            makeup_db: night.makeup_db,
+        }
+    }
+}
+
+impl Default for MqttConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            host: "localhost".to_string(),
+            port: 1883,
+            username: None,
+            password: None,
+            client_id: "bt-audio-receiver".to_string(),
+            base_topic: "bt-audio-receiver".to_string(),
+            discovery_prefix: "homeassistant".to_string(),
+        }
+    }
+}
+
+impl Default for LogConfig {
+    fn default() -> Self {
+        Self {
+            level: LogLevel::Info,
+            targets: BTreeMap::new(),
+            max_file_size_kb: 1024,
+            max_files: 5,
+        }
+    }
+}
+
+impl Default for AutoConnectConfig {
+    fn default() -> Self {
+        Self { retry_interval_secs: 30, priority: Vec::new(), preempt: false }
+    }
+}
+
+impl Default for ReconnectConfig {
+    fn default() -> Self {
+        let policy = ReconnectPolicy::default();
+        Self {
+            initial_delay_ms: policy.initial_delay.as_millis() as u64,
+            max_delay_ms: policy.max_delay.as_millis() as u64,
+            multiplier: policy.multiplier,
+            jitter: policy.jitter,
+            max_attempts: policy.max_attempts,
+        }
+    }
+}
+
+impl ReconnectConfig {
+    fn apply(&self, o: &ReconnectOverride) -> ReconnectConfig {
+        ReconnectConfig {
+            initial_delay_ms: o.initial_delay_ms.unwrap_or(self.initial_delay_ms),
+            max_delay_ms: o.max_delay_ms.unwrap_or(self.max_delay_ms),
+            multiplier: o.multiplier.unwrap_or(self.multiplier),
+            jitter: o.jitter.unwrap_or(self.jitter),
+            max_attempts: o.max_attempts.unwrap_or(self.max_attempts),
+        }
+    }
+
+    pub fn policy(&self) -> ReconnectPolicy {
+        ReconnectPolicy {
+            initial_delay: Duration::from_millis(self.initial_delay_ms),
+            max_delay: Duration::from_millis(self.max_delay_ms),
+            multiplier: self.multiplier,
+            jitter: self.jitter,
+            max_attempts: self.max_attempts,
+        }
+    }
+
+    fn validate(&self, section: &str, problems: &mut Vec<String>) {
+        if self.initial_delay_ms == 0 {
+            problems.push(Msg::ConfigMustBePositive { key: &format!("{section}.initial_delay_ms") }.to_string());
+        }
+        if self.max_delay_ms < self.initial_delay_ms {
+            problems.push(
+                Msg::ConfigMaxDelay { section: &section, max: &self.max_delay_ms, initial: &self.initial_delay_ms }
+                    .to_string(),
+            );
+        }
+        if !(1.0..).contains(&self.multiplier) {
+            problems.push(Msg::ConfigMultiplier { section: &section, value: &self.multiplier }.to_string());
+        }
+        if !(0.0..=1.0).contains(&self.jitter) {
+            problems.push(Msg::ConfigJitter { section: &section, value: &self.jitter }.to_string());
+        }
+    }
+}
+
+impl Config {
+    /// `%APPDATA%\BT-Audio-Receiver\config.toml`
+    pub fn default_path() -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| dir.join("BT-Audio-Receiver").join("config.toml"))
+    }
+
+    /// Читает конфиг, при необходимости мигрирует и переписывает его.
+    /// Если файла нет — создаёт его со значениями по умолчанию.
+    pub fn load(path: &Path) -> Result<Config> {
+        Self::load_with(path, Config::save)
+    }
+
+    // `load` с подменяемой записью: root пишет и в файлы только для чтения, а отказ
+    // записи проверять надо везде
+    fn load_with(path: &Path, save: impl Fn(&Config, &Path) -> Result<()>) -> Result<Config> {
+        if !path.exists() {
+            let config = Config::default();
+            if let Err(e) = save(&config, path) {
+                log::warn!(target: "config", "{}", Msg::LogConfigCreateFailed { path: &path.display(), error: &e });
+            }
+            return Ok(config);
+        }
+
+        let text = std::fs::read_to_string(path)
+            .with_context(|| Msg::ReadFailed { path: &path.display() }.to_string())?;
+        let (mut config, migrated) = Self::parse(&text)
+            .with_context(|| Msg::ConfigFileError { path: &path.display() }.to_string())?;
+
+        if migrated || config.app_version != APP_VERSION {
+            // Сохраняем копию на случай, если новая версия что-то испортила
+            let _ = std::fs::copy(path, path.with_extension("toml.bak"));
+            config.app_version = APP_VERSION.to_string();
+            // Файл может быть только для чтения (например, раздан администратором): тогда
+            // работаем с обновлённым конфигом в памяти и попробуем записать в следующий раз
+            match save(&config, path) {
+                Ok(()) => log::info!(target: "config", "{}", Msg::LogConfigUpgraded { version: &APP_VERSION }),
+                Err(e) => {
+                    log::warn!(target: "config", "{}", Msg::LogConfigUpgradeSaveFailed { path: &path.display(), error: &e })
+                }
+            }
+        }
+
+        Ok(config)
+    }
+
+    /// Разбирает текст конфига. Второе значение — была ли выполнена миграция схемы.
+    pub fn parse(text: &str) -> Result<(Config, bool)> {
+        let mut table: toml::Table = toml::from_str(text)?;
+        let migrated = migrate(&mut table)?;
+        let config: Config = table.try_into()?;
+        config.validate()?;
+        Ok((config, migrated))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(path, toml::to_string_pretty(self)?)
+            .with_context(|| Msg::WriteFailed { path: &path.display() }.to_string())
+    }
+
+    /// Проверяет значения и перечисляет все найденные проблемы разом.
+    pub fn validate(&self) -> Result<()> {
+        let mut problems = Vec::new();
+
+        let r = &self.receiver;
+        if r.heartbeat_interval_secs == 0 {
+            problems.push(Msg::ConfigMustBePositive { key: &"receiver.heartbeat_interval_secs" }.to_string());
+        }
+        if !(r.anchor_gain > 0.0 && r.anchor_gain <= 1.0) {
+            problems.push(Msg::ConfigAnchorGain { value: &r.anchor_gain }.to_string());
+        }
+        if r.mmcss_task.trim().is_empty() {
+            problems.push(Msg::ConfigMustNotBeEmpty { key: &"receiver.mmcss_task" }.to_string());
+        }
+        if r.max_connections == 0 {
+            problems.push(Msg::ConfigMustBePositive { key: &"receiver.max_connections" }.to_string());
+        }
+        if r.volume > 100 {
+            problems.push(Msg::ConfigPercent { key: &"receiver.volume", value: &r.volume }.to_string());
+        }
+        if self.autostart.key_name.trim().is_empty() {
+            problems.push(Msg::ConfigMustNotBeEmpty { key: &"autostart.key_name" }.to_string());
+        }
+        if self.auto_connect.retry_interval_secs == 0 {
+            problems.push(Msg::ConfigMustBePositive { key: &"auto_connect.retry_interval_secs" }.to_string());
+        }
+        for (i, id) in self.auto_connect.priority.iter().enumerate() {
+            if self.auto_connect.priority[..i].contains(id) {
+                problems.push(Msg::ConfigDuplicatePriority { id }.to_string());
+            }
+        }
+
+        for target in self.log.targets.keys() {
+            if !crate::logging::TARGETS.contains(&target.as_str()) {
+                let known = crate::logging::TARGETS.join(", ");
+                problems.push(Msg::ConfigUnknownLogTarget { target, known: &known }.to_string());
+            }
+        }
+        if self.log.max_file_size_kb == 0 {
+            problems.push(Msg::ConfigMustBePositive { key: &"log.max_file_size_kb" }.to_string());
+        }
+
+        if self.http.token.as_deref().is_some_and(|t| t.trim().is_empty()) {
+            problems.push(Msg::ConfigMustNotBeEmpty { key: &"http.token" }.to_string());
+        }
+        // Без токена управлять приёмником смог бы любой в сети
+        if self.http.enabled && !self.http.address.ip().is_loopback() && self.http.token.is_none() {
+            problems.push(Msg::ConfigHttpTokenRequired { address: &self.http.address }.to_string());
+        }
+
+        let m = &self.mqtt;
+        if m.host.trim().is_empty() {
+            problems.push(Msg::ConfigMustNotBeEmpty { key: &"mqtt.host" }.to_string());
+        }
+        if m.port == 0 {
+            problems.push(Msg::ConfigMustBePositive { key: &"mqtt.port" }.to_string());
+        }
+        if m.client_id.trim().is_empty() {
+            problems.push(Msg::ConfigMustNotBeEmpty { key: &"mqtt.client_id" }.to_string());
+        }
+        for (key, topic) in [("mqtt.base_topic", &m.base_topic), ("mqtt.discovery_prefix", &m.discovery_prefix)] {
+            if topic.contains(['+', '#']) {
+                problems.push(Msg::ConfigMqttWildcard { key: &key }.to_string());
+            }
+        }
+        if m.base_topic.trim().is_empty() {
+            problems.push(Msg::ConfigMustNotBeEmpty { key: &"mqtt.base_topic" }.to_string());
+        }
+
+        if self.recording.split_size_mb == Some(0) {
+            problems.push(Msg::ConfigMustBePositive { key: &"recording.split_size_mb" }.to_string());
+        }
+        if self.recording.split_minutes == Some(0) {
+            problems.push(Msg::ConfigMustBePositive { key: &"recording.split_minutes" }.to_string());
+        }
+        if self.recording.folder.as_ref().is_some_and(|f| f.as_os_str().is_empty()) {
+            problems.push(Msg::ConfigMustNotBeEmpty { key: &"recording.folder" }.to_string());
+        }
+
+        let mut check = |key: &str, value: f64, min: f64, max: f64| {
+            if !(min..=max).contains(&value) {
+                problems.push(Msg::ConfigRange { key: &key, value: &value, min: &min, max: &max }.to_string());
+            }
+        };
+        let l = &self.limiter;
+        check("limiter.ceiling_db", l.ceiling_db, -30.0, 0.0);
+        check("limiter.lookahead_ms", l.lookahead_ms as f64, 1.0, 50.0);
+        if let Some(max) = l.max_loudness_db {
+            check("limiter.max_loudness_db", max, -60.0, 0.0);
+        }
+        let n = &self.night_mode;
+        check("night_mode.threshold_db", n.threshold_db, -60.0, 0.0);
+        check("night_mode.ratio", n.ratio, 1.0, 20.0);
+        check("night_mode.makeup_db", n.makeup_db, 0.0, 24.0);
+        for (key, value) in
+            [("limiter.release_ms", l.release_ms), ("night_mode.attack_ms", n.attack_ms), ("night_mode.release_ms", n.release_ms)]
+        {
+            if value == 0 {
+                problems.push(Msg::ConfigMustBePositive { key: &key }.to_string());
+            }
+        }
+
+        for (name, preset) in &self.equalizer {
+            let section = format!("equalizer.\"{name}\"");
+            match preset.resolve() {
+                Ok(preset) => validate_preset(&section, &preset, &mut problems),
+                Err(error) => problems.push(Msg::ConfigPresetFile { key: &section, error: &error }.to_string()),
+            }
+        }
+        for (id, output) in &self.outputs {
+            if let Some(name) = output.equalizer.as_ref().filter(|name| !self.equalizer.contains_key(*name)) {
+                problems.push(Msg::ConfigUnknownPreset { key: &format!("outputs.\"{id}\".equalizer"), name }.to_string());
+            }
+        }
+
+        self.reconnect.validate("reconnect", &mut problems);
+        for (id, device) in &self.devices {
+            if let Some(volume) = device.volume.filter(|v| *v > 100) {
+                problems.push(Msg::ConfigPercent { key: &format!("devices.\"{id}\".volume"), value: &volume }.to_string());
+            }
+            if device.output.as_deref().is_some_and(|o| o.trim().is_empty()) {
+                problems.push(Msg::ConfigMustNotBeEmpty { key: &format!("devices.\"{id}\".output") }.to_string());
+            }
+            if let Some(name) = device.equalizer.as_ref().filter(|name| !self.equalizer.contains_key(*name)) {
+                problems.push(Msg::ConfigUnknownPreset { key: &format!("devices.\"{id}\".equalizer"), name }.to_string());
+            }
+            self.reconnect
+                .apply(&device.reconnect)
+                .validate(&format!("devices.\"{id}\".reconnect"), &mut problems);
+        }
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            anyhow::bail!("{}", problems.join("\n"))
+        }
+    }
+
+    pub fn worker_options(&self) -> WorkerOptions {
+        WorkerOptions {
+            reconnect: self.reconnect.policy(),
+            device_reconnect: self
+                .devices
+                .iter()
+                .map(|(id, device)| (id.clone(), self.reconnect.apply(&device.reconnect).policy()))
+                .collect(),
+            auto_connect: self
+                .devices
+                .iter()
+                .filter(|(_, device)| device.auto_connect)
+                .map(|(id, _)| id.clone())
+                .collect(),
+            last_device: self.last_device.clone(),
+            auto_connect_retry: Duration::from_secs(self.auto_connect.retry_interval_secs),
+            priority: self.auto_connect.priority.clone(),
+            preempt: self.auto_connect.preempt,
+            max_connections: self.receiver.max_connections,
+            volumes: self
+                .devices
+                .iter()
+                .filter(|(_, device)| device.volume.is_some() || device.muted)
+                .map(|(id, device)| (id.clone(), self.volume_of(device)))
+                .collect(),
+            default_volume: Volume { level: self.receiver.volume, muted: false },
+            outputs: self
+                .devices
+                .iter()
+                .filter_map(|(id, device)| Some((id.clone(), device.output.clone()?)))
+                .collect(),
+            recording: self.recording.settings(),
+            dsp: self
+                .devices
+                .iter()
+                .filter_map(|(id, device)| Some((id.clone(), self.dsp_of(device.equalizer.as_ref()?)?)))
+                .collect(),
+            output_dsp: self
+                .outputs
+                .iter()
+                .filter_map(|(id, output)| Some((id.clone(), self.dsp_of(output.equalizer.as_ref()?)?)))
+                .collect(),
+            limiter: self.limiter.settings(),
+            night_mode: self.night_mode.settings(),
+            night_mode_enabled: self.night_mode.enabled,
+        }
+    }
+
+    // Файл пресета мог пропасть или испортиться уже после проверки конфига
+    fn dsp_of(&self, preset: &str) -> Option<DspSettings> {
+        match self.equalizer.get(preset)?.resolve() {
+            Ok(equalizer) => Some(DspSettings { equalizer: Some(equalizer), ..Default::default() }),
+            Err(e) => {
+                log::warn!(target: "config", "{}", Msg::LogPresetFailed { name: &preset, error: &e });
+                None
+            }
+        }
+    }
+
+    fn volume_of(&self, device: &DeviceConfig) -> Volume {
+        Volume { level: device.volume.unwrap_or(self.receiver.volume), muted: device.muted }
+    }
+
+    pub fn is_auto_connect(&self, id: &str) -> bool {
+        self.devices.get(id).is_some_and(|d| d.auto_connect)
+    }
+}
+
+// Пределы заведомо шире разумного: ловим опечатки, а не вкус
+fn validate_preset(section: &str, preset: &EqPreset, problems: &mut Vec<String>) {
+    let mut check = |key: String, value: f64, min: f64, max: f64| {
+        if !(min..=max).contains(&value) {
+            problems.push(Msg::ConfigRange { key: &key, value: &value, min: &min, max: &max }.to_string());
+        }
+    };
+    check(format!("{section}.preamp"), preset.preamp, -30.0, 30.0);
+    for (i, band) in preset.bands.iter().enumerate() {
+        check(format!("{section}.bands[{i}].freq"), band.freq, 10.0, 24_000.0);
+        check(format!("{section}.bands[{i}].gain"), band.gain, -30.0, 30.0);
+        check(format!("{section}.bands[{i}].q"), band.q, 0.05, 50.0);
+    }
+}
+
+/// Общий для всех частей программы конфиг, который сохраняется на диск при изменении.
+#[derive(Clone, Debug)]
+pub struct ConfigStore {
+    path: Option<PathBuf>,
+    config: Arc<Mutex<Config>>,
+}
+
+impl ConfigStore {
+    /// `path = None` — изменения живут только в памяти.
+    pub fn new(config: Config, path: Option<PathBuf>) -> Self {
+        Self { path, config: Arc::new(Mutex::new(config)) }
+    }
+
+    pub fn get(&self) -> Config {
+        self.config.lock().unwrap().clone()
+    }
+
+    pub fn update(&self, change: impl FnOnce(&mut Config)) -> Result<()> {
+        let mut config = self.config.lock().unwrap();
+        change(&mut config);
+        match &self.path {
+            Some(path) => config.save(path),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Запоминает в конфиге каждое успешно подключённое устройство.
+pub async fn remember_last_device(store: ConfigStore, mut rx_state: watch::Receiver<ReceiverState>) {
+    let mut connected: Vec<String> = Vec::new();
+    while rx_state.changed().await.is_ok() {
+        let now: Vec<String> = rx_state.borrow_and_update().connected().map(|d| d.id.clone()).collect();
+        // Последним считаем то, что подключилось последним, а не первое в списке
+        let fresh = now.iter().rev().find(|id| !connected.contains(id)).cloned();
+        connected = now;
+
+        let Some(id) = fresh else { continue };
+        if store.get().last_device.as_deref() != Some(id.as_str()) {
+            if let Err(e) = store.update(|c| c.last_device = Some(id)) {
+                log::warn!(target: "config", "{}", Msg::LogLastDeviceSaveFailed { error: &e });
+            }
+        }
+    }
+}
+
+/// Запоминает в конфиге громкость, выставленную устройствам.
+pub async fn remember_volumes(store: ConfigStore, mut rx_state: watch::Receiver<ReceiverState>) {
+    while rx_state.changed().await.is_ok() {
+        let volumes = rx_state.borrow_and_update().volumes.clone();
+        let config = store.get();
+        let changed: Vec<(String, Volume)> = volumes
+            .into_iter()
+            .filter(|(id, volume)| match config.devices.get(id) {
+                Some(device) => config.volume_of(device) != *volume,
+                // Громкость по умолчанию у нового устройства запоминать незачем
+                None => *volume != Volume { level: config.receiver.volume, muted: false },
+            })
+            .collect();
+        if changed.is_empty() {
+            continue;
+        }
+        let saved = store.update(|c| {
+            for (id, volume) in changed {
+                let device = c.devices.entry(id).or_default();
+                device.volume = Some(volume.level);
+                device.muted = volume.muted;
+            }
+        });
+        if let Err(e) = saved {
+            log::warn!(target: "config", "{}", Msg::LogSettingsSaveFailed { error: &e });
+        }
+    }
+}
+
+// Миграции схемы: элемент с индексом N переводит файл из версии N в N + 1
+const MIGRATIONS: &[fn(&mut toml::Table)] = &[migrate_v0];
+
+// Версия 0 — файлы без поля `version`. Структура совпадает с первой версией.
+fn migrate_v0(_table: &mut toml::Table) {}
+
+fn migrate(table: &mut toml::Table) -> Result<bool> {
+    let mut version = match table.get("version") {
+        None => 0,
+        Some(value) => value
+            .as_integer()
+            .and_then(|v| u32::try_from(v).ok())
+            .with_context(|| Msg::ConfigVersionInvalid.to_string())?,
+    };
+
+    if version > CONFIG_VERSION {
+        anyhow::bail!("{}", Msg::ConfigVersionTooNew { found: &version, supported: &CONFIG_VERSION });
+    }
+
+    let migrated = version < CONFIG_VERSION;
+    while version < CONFIG_VERSION {
+        MIGRATIONS[version as usize](table);
+        version += 1;
+    }
+    table.insert("version".to_string(), toml::Value::Integer(version as i64));
+
+    Ok(migrated)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Свой каталог на тест: тесты идут параллельно
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("bt-audio-config-{}-{name}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn file_without_version_is_migrated() {
+        let (config, migrated) = Config::parse("[receiver]\nheartbeat_interval_secs = 5\n").unwrap();
+        assert!(migrated, "файл без version — версия 0, её надо мигрировать");
+        assert_eq!(config.version, CONFIG_VERSION);
+        assert_eq!(config.receiver.heartbeat_interval_secs, 5, "значения из файла сохраняются");
+    }
+
+    #[test]
+    fn current_version_is_not_migrated() {
+        let (config, migrated) = Config::parse(&format!("version = {CONFIG_VERSION}\n")).unwrap();
+        assert!(!migrated);
+        assert_eq!(config, Config { app_version: config.app_version.clone(), ..Config::default() });
+    }
+
+    #[test]
+    fn newer_or_broken_version_is_rejected() {
+        let error = Config::parse(&format!("version = {}\n", CONFIG_VERSION + 1)).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            Msg::ConfigVersionTooNew { found: &(CONFIG_VERSION + 1), supported: &CONFIG_VERSION }.to_string()
+        );
+        assert!(Config::parse("version = -1\n").is_err());
+        assert!(Config::parse("version = \"1\"\n").is_err());
+    }
+
+    #[test]
+    fn load_rewrites_migrated_file_and_keeps_backup() {
+        let dir = temp_dir("migrate");
+        let path = dir.join("config.toml");
+        let old = "app_version = \"0.0.1\"\n[receiver]\nheartbeat_interval_secs = 5\n";
+        std::fs::write(&path, old).unwrap();
+
+        let config = Config::load(&path).unwrap();
+        assert_eq!(config.app_version, APP_VERSION);
+        assert_eq!(config.receiver.heartbeat_interval_secs, 5);
+
+        assert_eq!(std::fs::read_to_string(path.with_extension("toml.bak")).unwrap(), old, "копия старого файла");
+        let (saved, migrated) = Config::parse(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        assert!(!migrated, "переписанный файл уже в текущей версии");
+        assert_eq!(saved, config);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn load_keeps_migrated_config_when_file_cannot_be_written() {
+        let dir = temp_dir("readonly");
+        let path = dir.join("config.toml");
+        let old = "[receiver]\nheartbeat_interval_secs = 5\n";
+        std::fs::write(&path, old).unwrap();
+
+        let config = Config::load_with(&path, |_, path| anyhow::bail!("{}", Msg::WriteFailed { path: &path.display() })).unwrap();
+        assert_eq!(config.version, CONFIG_VERSION);
+        assert_eq!(config.app_version, APP_VERSION);
+        assert_eq!(config.receiver.heartbeat_interval_secs, 5);
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), old, "файл остался как был");
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn load_creates_missing_file() {
+        let dir = temp_dir("missing");
+        let path = dir.join("nested").join("config.toml");
+        let config = Config::load(&path).unwrap();
+        assert_eq!(config, Config::default());
+        assert_eq!(Config::parse(&std::fs::read_to_string(&path).unwrap()).unwrap().0, config);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    // Состояние, в котором подключены `ids` (в этом порядке)
+    fn connected(ids: &[&str]) -> ReceiverState {
+        let mut state = ReceiverState::default();
+        for id in ids {
+            let device = crate::backend::BTDevice { id: id.to_string(), name: id.to_string() };
+            state.apply(id, crate::state::StateEvent::ConnectRequested(device));
+            state.apply(id, crate::state::StateEvent::ConnectSucceeded);
+        }
+        state
+    }
+
+    // Отдаёт изменение состояния и ждёт, пока его разберут
+    async fn publish(tx: &watch::Sender<ReceiverState>, state: ReceiverState) {
+        tx.send_replace(state);
+        for _ in 0..10 {
+            tokio::task::yield_now().await;
+        }
+    }
+
+    #[tokio::test]
+    async fn last_connected_device_is_saved() {
+        let dir = temp_dir("last-device");
+        let path = dir.join("config.toml");
+        let store = ConfigStore::new(Config::default(), Some(path.clone()));
+        let (tx, rx) = watch::channel(ReceiverState::default());
+        let task = tokio::spawn(remember_last_device(store.clone(), rx));
+
+        publish(&tx, connected(&["a"])).await;
+        assert_eq!(Config::load(&path).unwrap().last_device.as_deref(), Some("a"));
+        publish(&tx, connected(&["a", "b"])).await;
+        assert_eq!(store.get().last_device.as_deref(), Some("b"), "последним подключилось b");
+        // Отключение b не делает последним оставшееся a
+        publish(&tx, connected(&["a"])).await;
+        assert_eq!(store.get().last_device.as_deref(), Some("b"));
+        publish(&tx, connected(&[])).await;
+        publish(&tx, connected(&["a"])).await;
+
+        drop(tx);
+        task.await.unwrap();
+        assert_eq!(Config::load(&path).unwrap().last_device.as_deref(), Some("a"));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn changed_volumes_are_saved() {
+        let dir = temp_dir("volumes");
+        let path = dir.join("config.toml");
+        let store = ConfigStore::new(Config::default(), Some(path.clone()));
+        let (tx, rx) = watch::channel(ReceiverState::default());
+        let task = tokio::spawn(remember_volumes(store.clone(), rx));
+
+        let default = Volume { level: Config::default().receiver.volume, muted: false };
+        let quiet = Volume { level: 40, muted: false };
+        let mut state = connected(&["a", "b"]);
+        state.volumes.insert("a".into(), default);
+        state.volumes.insert("b".into(), quiet);
+        publish(&tx, state.clone()).await;
+        state.volumes.insert("b".into(), Volume { level: 40, muted: true });
+        publish(&tx, state).await;
+
+        drop(tx);
+        task.await.unwrap();
+        let config = Config::load(&path).unwrap();
+        assert!(!config.devices.contains_key("a"), "громкость по умолчанию не запоминается");
+        assert_eq!(config.devices["b"].volume, Some(40));
+        assert!(config.devices["b"].muted);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn validation_lists_every_problem() {
+        let text = "[reconnect]\ninitial_delay_ms = 0\nmultiplier = 0.5\njitter = 2.0\n";
+        let error = format!("{:#}", Config::parse(text).unwrap_err());
+        for key in ["initial_delay_ms", "0.5", "2"] {
+            assert!(error.contains(key), "нет «{key}» в «{error}»");
+        }
+    }
+}
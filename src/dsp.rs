@@ -0,0 +1,79 @@
+//! Обработка принятого звука. Бэкенд отдаёт цепочке каждый квант графа и играет то,
+//! что она вернула; сама цепочка от Windows не зависит.
+
+use crate::dynamics::{Compressor, CompressorSettings, Limiter, LimiterSettings};
+use crate::equalizer::{EqPreset, Equalizer};
+use crate::recording::AudioFormat;
+
+/// Что включено в обработку звука одного устройства.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct DspSettings {
+    pub equalizer: Option<EqPreset>,
+    /// Ночной режим.
+    pub compressor: Option<CompressorSettings>,
+    /// Лимитер и ограничение громкости — последними, чтобы их не обошла ни одна ступень.
+    pub limiter: Option<LimiterSettings>,
+}
+
+impl DspSettings {
+    /// Обрабатывать нечего — звук можно пустить напрямую, мимо цепочки.
+    pub fn is_empty(&self) -> bool {
+        self.equalizer.as_ref().is_none_or(EqPreset::is_empty) && self.compressor.is_none() && self.limiter.is_none()
+    }
+
+    /// Обработка источника, за которой идёт эквалайзер выхода, на котором он играет.
+    /// Динамика у приёмника общая, поэтому берётся из `self`.
+    pub fn then(&self, output: &DspSettings) -> DspSettings {
+        let equalizer = match (&self.equalizer, &output.equalizer) {
+            (Some(source), Some(output)) => Some(source.then(output)),
+            (source, output) => source.clone().or_else(|| output.clone()),
+        };
+        DspSettings { equalizer, ..self.clone() }
+    }
+}
+
+/// Ступени обработки в порядке прохождения звука.
+#[derive(Default)]
+pub struct DspChain {
+    settings: DspSettings,
+    equalizer: Option<Equalizer>,
+    compressor: Option<Compressor>,
+    loudness_cap: Option<Compressor>,
+    limiter: Option<Limiter>,
+}
+
+impl DspChain {
+    pub fn new(settings: DspSettings) -> Self {
+        Self {
+            equalizer: settings.equalizer.clone().filter(|p| !p.is_empty()).map(Equalizer::new),
+            compressor: settings.compressor.map(Compressor::new),
+            loudness_cap: settings.limiter.and_then(|l| l.loudness_cap()).map(Compressor::new),
+            limiter: settings.limiter.map(Limiter::new),
+            settings,
+        }
+    }
+
+    pub fn settings(&self) -> &DspSettings {
+        &self.settings
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.settings.is_empty()
+    }
+
+    /// Обрабатывает чередующиеся отсчёты на месте.
+    pub fn process(&mut self, samples: &mut [f32], format: AudioFormat) {
+        if let Some(equalizer) = &mut self.equalizer {
+            equalizer.process(samples, format);
+        }
+        if let Some(compressor) = &mut self.compressor {
+            compressor.process(samples, format);
+        }
+        if let Some(cap) = &mut self.loudness_cap {
+            cap.process(samples, format);
+        }
+        if let Some(limiter) = &mut self.limiter {
+            limiter.process(samples, format);
+        }
+    }
+}
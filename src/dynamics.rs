@@ -0,0 +1,385 @@
+//! Обработка динамики: пиковый лимитер с заглядыванием вперёд и компрессор, на котором
+//! построены ограничение громкости и ночной режим.
+
+use crate::recording::AudioFormat;
+use std::collections::VecDeque;
+use std::time::Duration;
+
+/// Лимитер и ограничение громкости.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LimiterSettings {
+    /// Выше этого уровня пики не поднимаются, дБFS.
+    pub ceiling_db: f64,
+    /// Насколько лимитер заглядывает вперёд; на столько же задерживается звук.
+    pub lookahead: Duration,
+    /// За сколько усиление возвращается после пика.
+    pub release: Duration,
+    /// Потолок средней громкости, дБFS (RMS). Пики ловит лимитер, а это — громкость,
+    /// которая держится: уведомление на полной громкости, крик в микрофон.
+    pub max_loudness_db: Option<f64>,
+}
+
+impl Default for LimiterSettings {
+    fn default() -> Self {
+        Self {
+            ceiling_db: -1.0,
+            lookahead: Duration::from_millis(5),
+            release: Duration::from_millis(150),
+            max_loudness_db: None,
+        }
+    }
+}
+
+impl LimiterSettings {
+    /// Ограничение громкости как компрессор: бесконечное сжатие по медленному RMS.
+    pub fn loudness_cap(&self) -> Option<CompressorSettings> {
+        self.max_loudness_db.map(|max| CompressorSettings {
+            threshold_db: max,
+            ratio: f64::INFINITY,
+            attack: Duration::from_millis(20),
+            release: Duration::from_secs(1),
+            makeup_db: 0.0,
+            window: Duration::from_millis(400),
+        })
+    }
+}
+
+/// Компрессор с RMS-детектором.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CompressorSettings {
+    /// Уровень, с которого начинается сжатие, дБFS.
+    pub threshold_db: f64,
+    /// Во сколько раз сжимается превышение порога; бесконечность — не пропускать выше порога.
+    pub ratio: f64,
+    pub attack: Duration,
+    pub release: Duration,
+    /// Усиление после сжатия, дБ: тихое становится громче.
+    pub makeup_db: f64,
+    /// Окно, по которому усредняется уровень.
+    pub window: Duration,
+}
+
+impl CompressorSettings {
+    /// Ночной режим: громкие места тише, тихие громче.
+    pub fn night_mode() -> Self {
+        Self {
+            threshold_db: -30.0,
+            ratio: 4.0,
+            attack: Duration::from_millis(10),
+            release: Duration::from_millis(300),
+            makeup_db: 8.0,
+            window: Duration::from_millis(50),
+        }
+    }
+}
+
+/// Пиковый лимитер. Нужное усиление считается на `lookahead` вперёд и плавно набирается
+/// к моменту, когда пик выходит из задержки, поэтому потолок не превышается никогда,
+/// а на пиках нет щелчков.
+pub struct Limiter {
+    settings: LimiterSettings,
+    ceiling: f64,
+    format: Option<AudioFormat>,
+    // Длина заглядывания в кадрах
+    length: usize,
+    release: f64,
+    // Задержанные отсчёты: length - 1 кадров
+    delay: VecDeque<f32>,
+    // Монотонная очередь для минимума нужного усиления за окно: (номер кадра, усиление)
+    minima: VecDeque<(u64, f64)>,
+    // Последние `length` усилений после восстановления; их среднее сглаживает атаку
+    gains: VecDeque<f64>,
+    sum: f64,
+    released: f64,
+    frame: u64,
+}
+
+impl Limiter {
+    pub fn new(settings: LimiterSettings) -> Self {
+        Self {
+            settings,
+            ceiling: db_to_gain(settings.ceiling_db),
+            format: None,
+            length: 1,
+            release: 1.0,
+            delay: VecDeque::new(),
+            minima: VecDeque::new(),
+            gains: VecDeque::new(),
+            sum: 0.0,
+            released: 1.0,
+            frame: 0,
+        }
+    }
+
+    /// Задержка звука в кадрах.
+    pub fn latency(&self) -> usize {
+        self.length - 1
+    }
+
+    /// Обрабатывает чередующиеся отсчёты на месте.
+    pub fn process(&mut self, samples: &mut [f32], format: AudioFormat) {
+        if format.channels == 0 || format.sample_rate == 0 {
+            return;
+        }
+        if self.format != Some(format) {
+            self.configure(format);
+        }
+        let ceiling = self.ceiling as f32;
+        for frame in samples.chunks_exact_mut(format.channels as usize) {
+            let peak = frame.iter().fold(0f64, |peak, s| peak.max(f64::from(s.abs())));
+            let needed = if peak > self.ceiling { self.ceiling / peak } else { 1.0 };
+
+            while self.minima.back().is_some_and(|(_, gain)| *gain >= needed) {
+                self.minima.pop_back();
+            }
+            self.minima.push_back((self.frame, needed));
+            while self.minima.front().is_some_and(|(n, _)| n + self.length as u64 <= self.frame) {
+                self.minima.pop_front();
+            }
+            let target = self.minima.front().map_or(1.0, |(_, gain)| *gain);
+
+            // Вниз — сразу, вверх — не спеша
+            self.released = match target < self.released {
+                true => target,
+                false => self.released + (target - self.released) * self.release,
+            };
+            self.gains.push_back(self.released);
+            self.sum += self.released;
+            if let Some(old) = self.gains.pop_front() {
+                self.sum -= old;
+            }
+            let gain = (self.sum / self.length as f64) as f32;
+
+            self.delay.extend(frame.iter().copied());
+            for sample in frame.iter_mut() {
+                let delayed = self.delay.pop_front().unwrap_or_default();
+                // Страховка от накопленной ошибки суммы
+                *sample = (delayed * gain).clamp(-ceiling, ceiling);
+            }
+            self.frame += 1;
+        }
+    }
+
+    fn configure(&mut self, format: AudioFormat) {
+        let rate = f64::from(format.sample_rate);
+        self.length = ((self.settings.lookahead.as_secs_f64() * rate).round() as usize).max(1);
+        self.release = smoothing(self.settings.release, rate);
+        self.delay = std::iter::repeat_n(0.0, self.latency() * format.channels as usize).collect();
+        self.minima.clear();
+        self.gains = std::iter::repeat_n(1.0, self.length).collect();
+        self.sum = self.length as f64;
+        self.released = 1.0;
+        self.frame = 0;
+        self.format = Some(format);
+    }
+}
+
+/// Компрессор: усиление считается по среднему уровню всех каналов сразу, чтобы
+/// стереокартина не плыла.
+pub struct Compressor {
+    settings: CompressorSettings,
+    format: Option<AudioFormat>,
+    window: f64,
+    attack: f64,
+    release: f64,
+    // Средний квадрат отсчёта
+    level: f64,
+    // Текущее изменение усиления без компенсации, дБ (≤ 0)
+    reduction_db: f64,
+}
+
+impl Compressor {
+    pub fn new(settings: CompressorSettings) -> Self {
+        Self { settings, format: None, window: 1.0, attack: 1.0, release: 1.0, level: 0.0, reduction_db: 0.0 }
+    }
+
+    /// Насколько сейчас сжат звук, дБ (≤ 0).
+    pub fn reduction_db(&self) -> f64 {
+        self.reduction_db
+    }
+
+    /// Обрабатывает чередующиеся отсчёты на месте.
+    pub fn process(&mut self, samples: &mut [f32], format: AudioFormat) {
+        if format.channels == 0 || format.sample_rate == 0 {
+            return;
+        }
+        if self.format != Some(format) {
+            let rate = f64::from(format.sample_rate);
+            self.window = smoothing(self.settings.window, rate);
+            self.attack = smoothing(self.settings.attack, rate);
+            self.release = smoothing(self.settings.release, rate);
+            self.format = Some(format);
+        }
+        let slope = 1.0 - 1.0 / self.settings.ratio;
+        for frame in samples.chunks_exact_mut(format.channels as usize) {
+            let square = frame.iter().map(|s| f64::from(*s).powi(2)).sum::<f64>() / frame.len() as f64;
+            self.level += (square - self.level) * self.window;
+
+            let over = 10.0 * self.level.max(1e-12).log10() - self.settings.threshold_db;
+            let target = if over > 0.0 { -over * slope } else { 0.0 };
+            let speed = if target < self.reduction_db { self.attack } else { self.release };
+            self.reduction_db += (target - self.reduction_db) * speed;
+
+            let gain = db_to_gain(self.reduction_db + self.settings.makeup_db) as f32;
+            for sample in frame.iter_mut() {
+                *sample *= gain;
+            }
+        }
+    }
+}
+
+pub fn db_to_gain(db: f64) -> f64 {
+    10f64.powf(db / 20.0)
+}
+
+// Коэффициент однополюсного сглаживания с постоянной времени `time`
+fn smoothing(time: Duration, rate: f64) -> f64 {
+    let samples = time.as_secs_f64() * rate;
+    if samples < 1.0 {
+        return 1.0;
+    }
+    1.0 - (-1.0 / samples).exp()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FORMAT: AudioFormat = AudioFormat { sample_rate: 48_000, channels: 2 };
+
+    // Стереосинус длиной `secs` секунд
+    fn sine(freq: f64, amplitude: f64, secs: f64) -> Vec<f32> {
+        let rate = f64::from(FORMAT.sample_rate);
+        (0..(secs * rate) as usize)
+            .flat_map(|i| {
+                let x = (amplitude * (2.0 * std::f64::consts::PI * freq * i as f64 / rate).sin()) as f32;
+                [x, x]
+            })
+            .collect()
+    }
+
+    // Кусками по 10 мс, как их отдаёт граф
+    fn run(process: &mut impl FnMut(&mut [f32], AudioFormat), samples: &mut [f32]) {
+        for chunk in samples.chunks_mut(2 * 480) {
+            process(chunk, FORMAT);
+        }
+    }
+
+    fn peak(samples: &[f32]) -> f64 {
+        samples.iter().fold(0f64, |peak, s| peak.max(f64::from(s.abs())))
+    }
+
+    fn rms_db(samples: &[f32]) -> f64 {
+        let mean = samples.iter().map(|s| f64::from(*s).powi(2)).sum::<f64>() / samples.len() as f64;
+        10.0 * mean.log10()
+    }
+
+    #[test]
+    fn limiter_never_exceeds_ceiling() {
+        let settings = LimiterSettings { ceiling_db: -3.0, ..Default::default() };
+        let ceiling = db_to_gain(-3.0);
+        // Тихий синус, в середине — 100 мс на 20 дБ громче и одиночный выброс
+        let mut samples = sine(440.0, 0.3, 1.0);
+        for s in samples.iter_mut().skip(2 * 24_000).take(2 * 4800) {
+            *s *= 10.0;
+        }
+        samples[2 * 36_000] = -4.0;
+        let mut limiter = Limiter::new(settings);
+        run(&mut |s, f| limiter.process(s, f), &mut samples);
+
+        let top = peak(&samples);
+        assert!(top <= ceiling + 1e-6, "пик {top} выше потолка {ceiling}");
+        assert!(top > ceiling * 0.95, "лимитер не должен давить сильнее нужного: {top}");
+        // Пики снижает усиление, а не срезает страховочный clamp: у потолка только вершины
+        // синуса, а срезанный удар лежал бы на нём большей частью
+        let flat = samples.iter().filter(|s| f64::from(s.abs()) > ceiling - 1e-4).count();
+        assert!(flat < 2 * 4800 / 10, "у потолка {flat} отсчётов");
+    }
+
+    #[test]
+    fn limiter_delays_by_lookahead_and_passes_quiet_audio() {
+        let settings = LimiterSettings { lookahead: Duration::from_millis(5), ..Default::default() };
+        let mut limiter = Limiter::new(settings);
+        let input = sine(1000.0, 0.5, 0.1);
+        let mut output = input.clone();
+        run(&mut |s, f| limiter.process(s, f), &mut output);
+
+        let latency = limiter.latency();
+        assert_eq!(latency, 239, "5 мс при 48 кГц — 240 кадров, из них один текущий");
+        assert!(output[..2 * latency].iter().all(|s| *s == 0.0), "пока задержка не заполнилась — тишина");
+        let shifted = &output[2 * latency..];
+        assert!(shifted.iter().zip(&input).all(|(out, inp)| out == inp), "тихий звук проходит без изменений");
+    }
+
+    #[test]
+    fn limiter_reduces_gain_before_the_peak() {
+        let ceiling = db_to_gain(-1.0);
+        let mut limiter = Limiter::new(LimiterSettings::default());
+        // Постоянный уровень 0.5, затем скачок до 1.0
+        let mut samples = vec![0.5f32; 2 * 4800];
+        samples.extend(vec![1.0f32; 2 * 4800]);
+        run(&mut |s, f| limiter.process(s, f), &mut samples);
+
+        let latency = limiter.latency();
+        let step = 4800 + latency;
+        // Скачок выходит из задержки на кадре `step`; усиление набиралось всё окно до него
+        let before = samples[2 * (step - 1)];
+        let at = samples[2 * step];
+        assert!(before < 0.5 && before > 0.5 * ceiling as f32, "усиление уже снижено, но плавно: {before}");
+        assert!((f64::from(at) - ceiling).abs() < 1e-3, "пик ровно на потолке: {at}");
+        let ramp = &samples[2 * (step - latency)..2 * step];
+        assert!(ramp.windows(2).all(|w| w[1] <= w[0]), "усиление убывает без скачков");
+    }
+
+    #[test]
+    fn limiter_recovers_after_release() {
+        let settings = LimiterSettings { release: Duration::from_millis(50), ..Default::default() };
+        let mut limiter = Limiter::new(settings);
+        let mut samples = vec![1.0f32; 2 * 480];
+        samples.extend(vec![0.5f32; 2 * 48_000]);
+        run(&mut |s, f| limiter.process(s, f), &mut samples);
+
+        // Через десять постоянных времени усиление почти вернулось
+        let late = samples[samples.len() - 2];
+        assert!((late - 0.5).abs() < 1e-3, "усиление не восстановилось: {late}");
+    }
+
+    #[test]
+    fn compressor_reduces_by_ratio_above_threshold() {
+        let settings = CompressorSettings { makeup_db: 0.0, ..CompressorSettings::night_mode() };
+        let mut compressor = Compressor::new(settings);
+        // Синус с RMS -10 дБFS: на 20 дБ выше порога, при 4:1 остаётся 5 дБ превышения
+        let amplitude = db_to_gain(-10.0) * std::f64::consts::SQRT_2;
+        let mut samples = sine(1000.0, amplitude, 2.0);
+        run(&mut |s, f| compressor.process(s, f), &mut samples);
+
+        assert!((compressor.reduction_db() - -15.0).abs() < 0.2, "сжатие {} дБ", compressor.reduction_db());
+        let tail = rms_db(&samples[samples.len() - 2 * 4800..]);
+        assert!((tail - -25.0).abs() < 0.2, "уровень после сжатия {tail} дБFS");
+    }
+
+    #[test]
+    fn night_mode_lifts_quiet_audio() {
+        let mut compressor = Compressor::new(CompressorSettings::night_mode());
+        let amplitude = db_to_gain(-40.0) * std::f64::consts::SQRT_2;
+        let mut samples = sine(1000.0, amplitude, 1.0);
+        run(&mut |s, f| compressor.process(s, f), &mut samples);
+
+        assert_eq!(compressor.reduction_db(), 0.0, "ниже порога не сжимается");
+        let tail = rms_db(&samples[samples.len() - 2 * 4800..]);
+        assert!((tail - -32.0).abs() < 0.1, "тихое поднято на 8 дБ: {tail} дБFS");
+    }
+
+    #[test]
+    fn loudness_cap_holds_sustained_level() {
+        let settings = LimiterSettings { max_loudness_db: Some(-20.0), ..Default::default() };
+        let mut cap = Compressor::new(settings.loudness_cap().unwrap());
+        let amplitude = db_to_gain(-6.0) * std::f64::consts::SQRT_2;
+        let mut samples = sine(1000.0, amplitude, 4.0);
+        run(&mut |s, f| cap.process(s, f), &mut samples);
+
+        let tail = rms_db(&samples[samples.len() - 2 * 48_000..]);
+        assert!((tail - -20.0).abs() < 0.3, "громкость {tail} дБFS вместо потолка -20");
+        assert!(LimiterSettings::default().loudness_cap().is_none());
+    }
+}
@@ -0,0 +1,220 @@
+//! Импорт пресетов в формате Equalizer APO (`config.txt`). В нём же AutoEq выгружает
+//! параметрические кривые (`ParametricEQ.txt`):
+//!
+//! ```text
+//! Preamp: -6.2 dB
+//! Filter 1: ON PK Fc 105 Hz Gain -2.4 dB Q 0.70
+//! Filter 2: ON LSC Fc 105 Hz Gain 5.5 dB Q 0.71
+//! ```
+//!
+//! Понимаем только то, что переводится в полосы эквалайзера без потерь; на всё остальное —
+//! ошибка с номером строки, а не молча другой звук.
+
+use crate::equalizer::{EqBand, EqPreset, FilterKind, DEFAULT_Q};
+use crate::i18n::Msg;
+use anyhow::{Context, Result};
+use std::path::Path;
+
+/// Читает и разбирает файл пресета.
+pub fn load(path: &Path) -> Result<EqPreset> {
+    let text = std::fs::read_to_string(path).with_context(|| Msg::ReadFailed { path: &path.display() }.to_string())?;
+    parse(&text).with_context(|| Msg::EqFileError { path: &path.display() }.to_string())
+}
+
+/// Разбирает текст в формате Equalizer APO.
+pub fn parse(text: &str) -> Result<EqPreset> {
+    let mut preset = EqPreset::default();
+    let mut empty = true;
+    for (number, line) in text.lines().enumerate() {
+        let line = line.trim().trim_start_matches('\u{feff}');
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        parse_line(line, &mut preset)
+            .with_context(|| Msg::EqLine { number: &(number + 1), line: &line }.to_string())?;
+        empty = false;
+    }
+    if empty {
+        anyhow::bail!("{}", Msg::EqNoFilters);
+    }
+    Ok(preset)
+}
+
+fn parse_line(line: &str, preset: &mut EqPreset) -> Result<()> {
+    let (command, rest) = line.split_once(':').unwrap_or((line, ""));
+    let mut tokens = rest.split_whitespace();
+    // «Filter 3:» и «Filter:» — одно и то же, номер ни на что не влияет
+    match command.split_whitespace().next().unwrap_or_default().to_ascii_lowercase().as_str() {
+        "preamp" => {
+            preset.preamp += number(tokens.next())?;
+            unit(tokens.next(), "db")?;
+        }
+        "filter" => {
+            let enabled = match tokens.next().map(str::to_ascii_uppercase).as_deref() {
+                Some("ON") => true,
+                Some("OFF") => false,
+                other => anyhow::bail!("{}", Msg::EqExpected { expected: &"ON/OFF", found: &other.unwrap_or_default() }),
+            };
+            let band = parse_filter(tokens)?;
+            if enabled {
+                preset.bands.push(band);
+            }
+        }
+        _ => anyhow::bail!("{}", Msg::EqUnsupportedCommand { command: &command.trim() }),
+    }
+    Ok(())
+}
+
+fn parse_filter<'a>(mut tokens: impl Iterator<Item = &'a str>) -> Result<EqBand> {
+    let name = tokens.next().unwrap_or_default();
+    let kind = match name.to_ascii_uppercase().as_str() {
+        "PK" | "PEQ" => FilterKind::Peaking,
+        "LS" | "LSC" => FilterKind::LowShelf,
+        "HS" | "HSC" => FilterKind::HighShelf,
+        "LP" | "LPQ" => FilterKind::LowPass,
+        "HP" | "HPQ" => FilterKind::HighPass,
+        _ => anyhow::bail!("{}", Msg::EqUnsupportedFilter { filter: &name }),
+    };
+
+    let (mut freq, mut gain, mut q) = (None, 0.0, DEFAULT_Q);
+    while let Some(key) = tokens.next() {
+        match key.to_ascii_lowercase().as_str() {
+            "fc" => freq = Some(number(tokens.next())?),
+            "gain" => gain = number(tokens.next())?,
+            "q" => q = number(tokens.next())?,
+            // Ширина полосы в октавах: Q = √(2^N) / (2^N − 1)
+            "bw" => {
+                unit(tokens.next(), "oct")?;
+                let octaves = 2f64.powf(number(tokens.next())?);
+                q = octaves.sqrt() / (octaves - 1.0);
+            }
+            "hz" | "db" => {}
+            // «LS 6dB», «HSC 12 dB» — полки с заданной крутизной в дБ на октаву
+            _ => anyhow::bail!("{}", Msg::EqUnsupportedFilter { filter: &format!("{name} {key}") }),
+        }
+    }
+    let freq = freq.ok_or_else(|| anyhow::anyhow!("{}", Msg::EqMissing { key: &"Fc" }))?;
+    Ok(EqBand { kind, freq, gain, q })
+}
+
+fn number(token: Option<&str>) -> Result<f64> {
+    let token = token.unwrap_or_default();
+    token
+        .parse()
+        .ok()
+        .filter(|n: &f64| n.is_finite())
+        .ok_or_else(|| anyhow::anyhow!("{}", Msg::EqExpected { expected: &Msg::EqNumber, found: &token }))
+}
+
+fn unit(token: Option<&str>, expected: &str) -> Result<()> {
+    match token {
+        Some(token) if !token.eq_ignore_ascii_case(expected) => {
+            anyhow::bail!("{}", Msg::EqExpected { expected: &expected, found: &token })
+        }
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn fixture(name: &str) -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/eq").join(name)
+    }
+
+    fn band(kind: FilterKind, freq: f64, gain: f64, q: f64) -> EqBand {
+        EqBand { kind, freq, gain, q }
+    }
+
+    // Полный текст ошибки со всеми контекстами
+    fn error(text: &str) -> String {
+        format!("{:#}", parse(text).unwrap_err())
+    }
+
+    #[test]
+    fn autoeq_export() {
+        let preset = load(&fixture("autoeq_parametric.txt")).unwrap();
+        assert_eq!(preset.file, None);
+        assert_eq!(preset.preamp, -6.2);
+        assert_eq!(preset.bands.len(), 7);
+        assert_eq!(preset.bands[0], band(FilterKind::LowShelf, 105.0, 5.5, 0.70));
+        assert_eq!(preset.bands[3], band(FilterKind::Peaking, 2500.0, -3.8, 2.13));
+        assert_eq!(preset.bands[6], band(FilterKind::HighShelf, 10_000.0, -1.3, 0.70));
+    }
+
+    #[test]
+    fn equalizer_apo_config() {
+        // BOM, CRLF, комментарии, фильтр без номера, выключенный фильтр, строчные буквы, BW Oct
+        let preset = load(&fixture("apo_config.txt")).unwrap();
+        assert_eq!(preset.preamp, -4.5, "предусиления складываются");
+        assert_eq!(preset.bands.len(), 3, "выключенный фильтр пропускается");
+        assert_eq!(preset.bands[0], band(FilterKind::HighPass, 40.0, 0.0, DEFAULT_Q));
+        assert_eq!((preset.bands[1].kind, preset.bands[1].freq, preset.bands[1].gain), (FilterKind::Peaking, 2000.0, -3.0));
+        assert!((preset.bands[1].q - std::f64::consts::SQRT_2).abs() < 1e-12, "ширина в октаву — Q √2");
+        assert_eq!(preset.bands[2], band(FilterKind::LowPass, 16_000.0, 0.0, 0.5));
+    }
+
+    #[test]
+    fn preamp_only_is_a_preset() {
+        let preset = parse("Preamp: -2 dB").unwrap();
+        assert_eq!(preset.preamp, -2.0);
+        assert!(preset.bands.is_empty());
+        assert_eq!(parse("Preamp: 1.5").unwrap().preamp, 1.5, "единицы можно не писать");
+    }
+
+    #[test]
+    fn resolve_adds_config_bands_after_file() {
+        let extra = band(FilterKind::Peaking, 60.0, 2.0, 1.0);
+        let preset = EqPreset { file: Some(fixture("autoeq_parametric.txt")), preamp: -1.0, bands: vec![extra] };
+        let resolved = preset.resolve().unwrap();
+        assert!((resolved.preamp - -7.2).abs() < 1e-12);
+        assert_eq!(resolved.bands.len(), 8);
+        assert_eq!(resolved.bands[7], extra);
+    }
+
+    #[test]
+    fn unsupported_filters_are_named() {
+        let root = |text| parse(text).unwrap_err().root_cause().to_string();
+        assert_eq!(root("Filter 1: ON BP Fc 1000 Hz Q 1"), Msg::EqUnsupportedFilter { filter: &"BP" }.to_string());
+        assert_eq!(root("Filter 1: ON NO Fc 50 Hz"), Msg::EqUnsupportedFilter { filter: &"NO" }.to_string());
+        // Полка с крутизной в дБ на октаву не переводится в Q без потерь
+        assert_eq!(root("Filter 1: ON LS 6dB Fc 100 Hz Gain 3 dB"), Msg::EqUnsupportedFilter { filter: &"LS 6dB" }.to_string());
+        assert_eq!(root("GraphicEQ: 25 -1; 40 0"), Msg::EqUnsupportedCommand { command: &"GraphicEQ" }.to_string());
+        assert_eq!(root("Include: other.txt"), Msg::EqUnsupportedCommand { command: &"Include" }.to_string());
+    }
+
+    #[test]
+    fn bad_lines_report_their_number() {
+        let text = "Preamp: -3 dB\n\nFilter 1: ON PK Fc abc Hz Gain 2 dB Q 1\n";
+        let message = error(text);
+        assert!(message.contains(&Msg::EqLine { number: &3, line: &"Filter 1: ON PK Fc abc Hz Gain 2 dB Q 1" }.to_string()), "{message}");
+        assert!(message.contains(&Msg::EqExpected { expected: &Msg::EqNumber, found: &"abc" }.to_string()), "{message}");
+
+        let cases = [
+            ("Filter 1: MAYBE PK Fc 100 Hz", Msg::EqExpected { expected: &"ON/OFF", found: &"MAYBE" }.to_string()),
+            ("Filter 1: ON PK Gain 3 dB Q 1", Msg::EqMissing { key: &"Fc" }.to_string()),
+            ("Filter 1: ON PK Fc 100 Hz Gain", Msg::EqExpected { expected: &Msg::EqNumber, found: &"" }.to_string()),
+            ("Filter 1: ON PK Fc inf Hz", Msg::EqExpected { expected: &Msg::EqNumber, found: &"inf" }.to_string()),
+            ("Filter 1: ON PK Fc 100 Hz BW 1", Msg::EqExpected { expected: &"oct", found: &"1" }.to_string()),
+            ("Preamp: -3 dBFS", Msg::EqExpected { expected: &"db", found: &"dBFS" }.to_string()),
+        ];
+        for (line, expected) in cases {
+            assert_eq!(parse(line).unwrap_err().root_cause().to_string(), expected, "{line}");
+        }
+    }
+
+    #[test]
+    fn file_without_filters_is_an_error() {
+        assert!(error("").contains(&Msg::EqNoFilters.to_string()));
+        assert!(error("# только комментарий\n\n").contains(&Msg::EqNoFilters.to_string()));
+    }
+
+    #[test]
+    fn missing_file_names_the_path() {
+        let path = fixture("missing.txt");
+        let message = format!("{:#}", load(&path).unwrap_err());
+        assert!(message.contains(&path.display().to_string()), "{message}");
+    }
+}
@@ -0,0 +1,336 @@
+//! Параметрический эквалайзер: полосы из конфига превращаются в биквадратные фильтры
+//! (формулы RBJ Audio EQ Cookbook), которые по очереди обрабатывают каждый канал.
+
+use crate::eq_import;
+use crate::recording::AudioFormat;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::f64::consts::PI;
+use std::path::PathBuf;
+
+/// Добротность по умолчанию — фильтр Баттерворта без подъёма на срезе.
+pub const DEFAULT_Q: f64 = std::f64::consts::FRAC_1_SQRT_2;
+
+/// Выше этой доли частоты дискретизации формулы фильтров вырождаются.
+const MAX_RELATIVE_FREQ: f64 = 0.49;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FilterKind {
+    Peaking,
+    LowShelf,
+    HighShelf,
+    LowPass,
+    HighPass,
+}
+
+/// Полоса эквалайзера.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct EqBand {
+    #[serde(rename = "type")]
+    pub kind: FilterKind,
+    /// Центральная частота или частота среза, Гц.
+    pub freq: f64,
+    /// Подъём или завал, дБ. Фильтрам среза не нужен.
+    #[serde(default)]
+    pub gain: f64,
+    /// Добротность; для полок — крутизна перехода.
+    #[serde(default = "default_q")]
+    pub q: f64,
+}
+
+fn default_q() -> f64 {
+    DEFAULT_Q
+}
+
+/// Набор полос с общим предусилением. Предусиление обычно отрицательное, чтобы
+/// подъём полос не упирался в 0 дБFS.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct EqPreset {
+    /// Пресет Equalizer APO или AutoEq; его полосы идут перед заданными здесь.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub file: Option<PathBuf>,
+    /// дБ
+    pub preamp: f64,
+    pub bands: Vec<EqBand>,
+}
+
+impl EqPreset {
+    pub fn is_empty(&self) -> bool {
+        self.file.is_none() && self.preamp == 0.0 && self.bands.is_empty()
+    }
+
+    /// Пресет с прочитанным `file`.
+    pub fn resolve(&self) -> Result<EqPreset> {
+        let Some(path) = &self.file else { return Ok(self.clone()) };
+        let mut preset = eq_import::load(path)?;
+        preset.preamp += self.preamp;
+        preset.bands.extend_from_slice(&self.bands);
+        Ok(preset)
+    }
+
+    /// Сначала `self`, потом `next` — как два эквалайзера подряд.
+    pub fn then(&self, next: &EqPreset) -> EqPreset {
+        EqPreset {
+            file: None,
+            preamp: self.preamp + next.preamp,
+            bands: self.bands.iter().chain(&next.bands).copied().collect(),
+        }
+    }
+}
+
+/// Коэффициенты биквада, нормированные на a0.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Coefficients {
+    pub b0: f64,
+    pub b1: f64,
+    pub b2: f64,
+    pub a1: f64,
+    pub a2: f64,
+}
+
+impl Coefficients {
+    pub fn new(band: &EqBand, sample_rate: u32) -> Self {
+        let sample_rate = f64::from(sample_rate);
+        let freq = band.freq.clamp(1.0, sample_rate * MAX_RELATIVE_FREQ);
+        let w0 = 2.0 * PI * freq / sample_rate;
+        let (sin, cos) = w0.sin_cos();
+        let alpha = sin / (2.0 * band.q);
+        let a = 10f64.powf(band.gain / 40.0);
+        let shelf = 2.0 * a.sqrt() * alpha;
+
+        let (b0, b1, b2, a0, a1, a2) = match band.kind {
+            FilterKind::Peaking => (1.0 + alpha * a, -2.0 * cos, 1.0 - alpha * a, 1.0 + alpha / a, -2.0 * cos, 1.0 - alpha / a),
+            FilterKind::LowShelf => (
+                a * ((a + 1.0) - (a - 1.0) * cos + shelf),
+                2.0 * a * ((a - 1.0) - (a + 1.0) * cos),
+                a * ((a + 1.0) - (a - 1.0) * cos - shelf),
+                (a + 1.0) + (a - 1.0) * cos + shelf,
+                -2.0 * ((a - 1.0) + (a + 1.0) * cos),
+                (a + 1.0) + (a - 1.0) * cos - shelf,
+            ),
+            FilterKind::HighShelf => (
+                a * ((a + 1.0) + (a - 1.0) * cos + shelf),
+                -2.0 * a * ((a - 1.0) + (a + 1.0) * cos),
+                a * ((a + 1.0) + (a - 1.0) * cos - shelf),
+                (a + 1.0) - (a - 1.0) * cos + shelf,
+                2.0 * ((a - 1.0) - (a + 1.0) * cos),
+                (a + 1.0) - (a - 1.0) * cos - shelf,
+            ),
+            FilterKind::LowPass => ((1.0 - cos) / 2.0, 1.0 - cos, (1.0 - cos) / 2.0, 1.0 + alpha, -2.0 * cos, 1.0 - alpha),
+            FilterKind::HighPass => ((1.0 + cos) / 2.0, -(1.0 + cos), (1.0 + cos) / 2.0, 1.0 + alpha, -2.0 * cos, 1.0 - alpha),
+        };
+        Self { b0: b0 / a0, b1: b1 / a0, b2: b2 / a0, a1: a1 / a0, a2: a2 / a0 }
+    }
+
+    /// АЧХ фильтра на частоте `freq`, дБ.
+    pub fn response_db(&self, freq: f64, sample_rate: u32) -> f64 {
+        let w = 2.0 * PI * freq / f64::from(sample_rate);
+        // H(e^jw) = (b0 + b1·e^-jw + b2·e^-2jw) / (1 + a1·e^-jw + a2·e^-2jw)
+        let (sin1, cos1) = w.sin_cos();
+        let (sin2, cos2) = (2.0 * w).sin_cos();
+        let num = (self.b0 + self.b1 * cos1 + self.b2 * cos2, -(self.b1 * sin1 + self.b2 * sin2));
+        let den = (1.0 + self.a1 * cos1 + self.a2 * cos2, -(self.a1 * sin1 + self.a2 * sin2));
+        let power = (num.0 * num.0 + num.1 * num.1) / (den.0 * den.0 + den.1 * den.1);
+        10.0 * power.log10()
+    }
+}
+
+// Транспонированная прямая форма II: два числа состояния на канал
+#[derive(Clone, Copy, Debug)]
+struct Biquad {
+    c: Coefficients,
+    z1: f64,
+    z2: f64,
+}
+
+impl Biquad {
+    fn new(c: Coefficients) -> Self {
+        Self { c, z1: 0.0, z2: 0.0 }
+    }
+
+    fn process(&mut self, x: f64) -> f64 {
+        let y = self.c.b0 * x + self.z1;
+        self.z1 = self.c.b1 * x - self.c.a1 * y + self.z2;
+        self.z2 = self.c.b2 * x - self.c.a2 * y;
+        y
+    }
+}
+
+/// Эквалайзер для потока одного устройства. Фильтры пересчитываются, когда меняется
+/// формат звука.
+pub struct Equalizer {
+    preset: EqPreset,
+    preamp: f64,
+    format: Option<AudioFormat>,
+    // filters[канал][полоса]
+    filters: Vec<Vec<Biquad>>,
+}
+
+impl Equalizer {
+    /// `preset` должен быть уже прочитан (`EqPreset::resolve`): `file` здесь не читается.
+    pub fn new(preset: EqPreset) -> Self {
+        let preamp = 10f64.powf(preset.preamp / 20.0);
+        Self { preset, preamp, format: None, filters: Vec::new() }
+    }
+
+    pub fn preset(&self) -> &EqPreset {
+        &self.preset
+    }
+
+    /// Суммарная АЧХ всех полос с предусилением, дБ.
+    pub fn response_db(&self, freq: f64, sample_rate: u32) -> f64 {
+        let bands: f64 = self.preset.bands.iter().map(|b| Coefficients::new(b, sample_rate).response_db(freq, sample_rate)).sum();
+        self.preset.preamp + bands
+    }
+
+    /// Обрабатывает чередующиеся отсчёты на месте.
+    pub fn process(&mut self, samples: &mut [f32], format: AudioFormat) {
+        if format.channels == 0 {
+            return;
+        }
+        if self.format != Some(format) {
+            self.design(format);
+        }
+        for frame in samples.chunks_mut(format.channels as usize) {
+            for (sample, filters) in frame.iter_mut().zip(&mut self.filters) {
+                let mut x = f64::from(*sample) * self.preamp;
+                for filter in filters.iter_mut() {
+                    x = filter.process(x);
+                }
+                *sample = x as f32;
+            }
+        }
+    }
+
+    fn design(&mut self, format: AudioFormat) {
+        let channel: Vec<Biquad> =
+            self.preset.bands.iter().map(|b| Biquad::new(Coefficients::new(b, format.sample_rate))).collect();
+        self.filters = vec![channel; format.channels as usize];
+        self.format = Some(format);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RATE: u32 = 48_000;
+
+    fn band(kind: FilterKind, freq: f64, gain: f64, q: f64) -> EqBand {
+        EqBand { kind, freq, gain, q }
+    }
+
+    // Эталон — АЧХ аналогового прототипа из RBJ Cookbook после билинейного преобразования
+    // с подгонкой частоты, посчитанная отдельно от этого модуля. Точки — (Гц, дБ).
+    fn assert_response(band: EqBand, reference: &[(f64, f64)]) {
+        let c = Coefficients::new(&band, RATE);
+        for &(freq, expected) in reference {
+            let actual = c.response_db(freq, RATE);
+            assert!((actual - expected).abs() < 0.01, "{band:?} на {freq} Гц: {actual:.3} дБ вместо {expected} дБ");
+        }
+    }
+
+    #[test]
+    fn peaking_response() {
+        assert_response(
+            band(FilterKind::Peaking, 1000.0, 6.0, 1.41),
+            &[(100.0, 0.033), (500.0, 1.137), (707.0, 3.001), (1000.0, 6.0), (1414.0, 2.998), (2000.0, 1.128), (10_000.0, 0.024)],
+        );
+        assert_response(
+            band(FilterKind::Peaking, 3000.0, -9.0, 4.0),
+            &[(2000.0, -0.818), (2800.0, -6.674), (3000.0, -9.0), (3300.0, -5.483), (5000.0, -0.494)],
+        );
+    }
+
+    #[test]
+    fn shelf_response() {
+        // На частоте полки — половина подъёма, вдали — весь подъём или ничего
+        assert_response(
+            band(FilterKind::LowShelf, 100.0, 6.0, DEFAULT_Q),
+            &[(20.0, 5.99), (50.0, 5.624), (100.0, 3.0), (200.0, 0.376), (1000.0, 0.001)],
+        );
+        assert_response(
+            band(FilterKind::HighShelf, 8000.0, -4.0, DEFAULT_Q),
+            &[(1000.0, -0.001), (4000.0, -0.183), (8000.0, -2.0), (16_000.0, -3.95), (20_000.0, -3.998)],
+        );
+    }
+
+    #[test]
+    fn pass_response() {
+        assert_response(
+            band(FilterKind::LowPass, 1000.0, 0.0, DEFAULT_Q),
+            &[(100.0, 0.0), (500.0, -0.262), (1000.0, -3.01), (2000.0, -12.375), (4000.0, -24.476), (10_000.0, -42.738)],
+        );
+        assert_response(
+            band(FilterKind::HighPass, 80.0, 0.0, DEFAULT_Q),
+            &[(20.0, -24.099), (40.0, -12.305), (80.0, -3.01), (160.0, -0.263), (1000.0, 0.0)],
+        );
+        // Высокая добротность даёт подъём на срезе: |H(f0)| = Q
+        assert_response(band(FilterKind::LowPass, 2000.0, 0.0, 2.0), &[(1000.0, 2.023), (2000.0, 6.021), (3000.0, -3.456)]);
+    }
+
+    #[test]
+    fn pass_filters_null_at_the_far_end() {
+        let low = Coefficients::new(&band(FilterKind::LowPass, 1000.0, 0.0, DEFAULT_Q), RATE);
+        let high = Coefficients::new(&band(FilterKind::HighPass, 1000.0, 0.0, DEFAULT_Q), RATE);
+        // На Найквисте числитель ФНЧ (b0 - b1 + b2) равен нулю, на нуле — числитель ФВЧ
+        assert!((low.b0 - low.b1 + low.b2).abs() < 1e-12);
+        assert!((high.b0 + high.b1 + high.b2).abs() < 1e-12);
+    }
+
+    #[test]
+    fn frequency_is_clamped_below_nyquist() {
+        let c = Coefficients::new(&band(FilterKind::Peaking, 30_000.0, 6.0, 1.0), RATE);
+        let clamped = Coefficients::new(&band(FilterKind::Peaking, f64::from(RATE) * MAX_RELATIVE_FREQ, 6.0, 1.0), RATE);
+        assert_eq!(c, clamped);
+        assert!([c.b0, c.b1, c.b2, c.a1, c.a2].iter().all(|x| x.is_finite()));
+    }
+
+    // Уровень синуса `freq` после эквалайзера относительно входа, дБ
+    fn measured_gain_db(equalizer: &mut Equalizer, freq: f64) -> f64 {
+        let format = AudioFormat { sample_rate: RATE, channels: 2 };
+        let frames = RATE as usize;
+        let mut samples: Vec<f32> = (0..frames)
+            .flat_map(|i| {
+                let x = (0.25 * (2.0 * PI * freq * i as f64 / f64::from(RATE)).sin()) as f32;
+                [x, x]
+            })
+            .collect();
+        let input = samples.clone();
+        equalizer.process(&mut samples, format);
+        // Первая половина — на затухание переходного процесса
+        let rms = |s: &[f32]| (s[frames..].iter().map(|x| f64::from(*x).powi(2)).sum::<f64>() / frames as f64).sqrt();
+        20.0 * (rms(&samples) / rms(&input)).log10()
+    }
+
+    #[test]
+    fn processing_matches_response_and_applies_preamp() {
+        let preset = EqPreset {
+            file: None,
+            preamp: -3.0,
+            bands: vec![band(FilterKind::Peaking, 1000.0, 6.0, 1.41), band(FilterKind::HighPass, 80.0, 0.0, DEFAULT_Q)],
+        };
+        let mut equalizer = Equalizer::new(preset);
+        for freq in [40.0, 1000.0, 5000.0] {
+            let expected = equalizer.response_db(freq, RATE);
+            let actual = measured_gain_db(&mut equalizer, freq);
+            assert!((actual - expected).abs() < 0.05, "{freq} Гц: {actual:.3} дБ вместо {expected:.3} дБ");
+        }
+        assert!((equalizer.response_db(1000.0, RATE) - 3.0).abs() < 0.01, "полоса +6 дБ и предусиление -3 дБ");
+    }
+
+    #[test]
+    fn channels_are_filtered_independently() {
+        let preset = EqPreset { bands: vec![band(FilterKind::LowPass, 500.0, 0.0, DEFAULT_Q)], ..Default::default() };
+        let mut equalizer = Equalizer::new(preset);
+        // Импульс только в левом канале не должен попасть в правый
+        let mut samples = vec![0f32; 256];
+        samples[0] = 1.0;
+        equalizer.process(&mut samples, AudioFormat { sample_rate: RATE, channels: 2 });
+        assert!(samples.iter().skip(1).step_by(2).all(|s| *s == 0.0));
+        assert!(samples.iter().step_by(2).any(|s| *s != 0.0));
+    }
+}
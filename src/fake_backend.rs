@@ -0,0 +1,308 @@
+use crate::backend::{AudioOutput, AudioSinkBackend, BTDevice, BackendEvent};
+use crate::dsp::DspSettings;
+use crate::i18n::Msg;
+use crate::meter::LevelMeter;
+use crate::recording::{AudioChunk, Tap};
+use crate::volume::Volume;
+use anyhow::Result;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::{Arc, Mutex};
+use tokio::sync::broadcast;
+
+/// Бэкенд в памяти. Клоны разделяют состояние, поэтому тест держит у себя
+/// один клон и «управляет миром», пока воркер работает с другим.
+#[derive(Clone)]
+pub struct FakeBackend {
+    state: Arc<Mutex<FakeState>>,
+    events: broadcast::Sender<BackendEvent>,
+}
+
+#[derive(Default)]
+struct FakeState {
+    devices: Vec<BTDevice>,
+    connected: Vec<String>,
+    // Ошибки, которые вернут следующие попытки connect/reconnect (по очереди)
+    connect_failures: VecDeque<String>,
+    calls: Vec<FakeCall>,
+    watching: bool,
+    volumes: HashMap<String, Volume>,
+    // Соединения, у которых громкость не меняется
+    fixed_volume: HashSet<String>,
+    outputs: Vec<AudioOutput>,
+    // Куда идёт звук подключённых устройств; нет записи — на выход по умолчанию
+    routes: HashMap<String, String>,
+    taps: HashMap<String, Tap>,
+    dsp: HashMap<String, DspSettings>,
+    meter: LevelMeter,
+}
+
+/// Журнал вызовов бэкенда — чтобы тест мог проверить, что именно делал воркер.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum FakeCall {
+    ListDevices,
+    Connect(String),
+    Reconnect(String),
+    Disconnect(String),
+    SetVolume(String, Volume),
+    SetOutput(String, Option<String>),
+    SetTap(String, bool),
+    SetDsp(String),
+    WatchDevices,
+}
+
+impl FakeBackend {
+    pub fn new() -> Self {
+        let (events, _) = broadcast::channel(64);
+        Self {
+            state: Arc::new(Mutex::new(FakeState::default())),
+            events,
+        }
+    }
+
+    pub fn with_devices(devices: &[(&str, &str)]) -> Self {
+        let backend = Self::new();
+        for (id, name) in devices {
+            backend.add_device(id, name);
+        }
+        backend
+    }
+
+    /// Телефон появился (спарен или вошёл в зону) или переименован.
+    pub fn add_device(&self, id: &str, name: &str) {
+        let device = BTDevice { id: id.to_string(), name: name.to_string() };
+        let mut state = self.state.lock().unwrap();
+        let event = match state.devices.iter_mut().find(|d| d.id == id) {
+            Some(existing) => {
+                *existing = device.clone();
+                BackendEvent::DeviceUpdated(device)
+            }
+            None => {
+                state.devices.push(device.clone());
+                BackendEvent::DeviceAdded(device)
+            }
+        };
+        if state.watching {
+            let _ = self.events.send(event);
+        }
+    }
+
+    /// Телефон исчез. Если он был подключен — соединение обрывается.
+    pub fn remove_device(&self, id: &str) {
+        let watching = {
+            let mut state = self.state.lock().unwrap();
+            state.devices.retain(|d| d.id != id);
+            state.watching
+        };
+        if watching {
+            let _ = self.events.send(BackendEvent::DeviceRemoved(id.to_string()));
+        }
+        self.drop_connection_of(id);
+    }
+
+    /// Громкость соединения `id` нельзя будет менять, как при звуке мимо графа.
+    pub fn fix_volume(&self, id: &str) {
+        self.state.lock().unwrap().fixed_volume.insert(id.to_string());
+    }
+
+    /// Следующая попытка подключения завершится ошибкой `reason`.
+    pub fn fail_next_connect(&self, reason: &str) {
+        self.state.lock().unwrap().connect_failures.push_back(reason.to_string());
+    }
+
+    /// Имитирует обрыв соединения со стороны телефона.
+    pub fn drop_connection(&self, id: &str) {
+        self.drop_connection_of(id);
+    }
+
+    /// Подключённые устройства в порядке подключения.
+    pub fn connected(&self) -> Vec<String> {
+        self.state.lock().unwrap().connected.clone()
+    }
+
+    /// Громкость, которую воркер последней выставил устройству.
+    pub fn volume(&self, id: &str) -> Option<Volume> {
+        self.state.lock().unwrap().volumes.get(id).copied()
+    }
+
+    /// Подключили устройство вывода.
+    pub fn add_output(&self, id: &str, name: &str) {
+        let output = AudioOutput { id: id.to_string(), name: name.to_string() };
+        self.state.lock().unwrap().outputs.push(output);
+    }
+
+    /// Устройство вывода пропало: звук всех, кто на нём играл, уходит на выход по умолчанию.
+    pub fn remove_output(&self, id: &str) {
+        let lost: Vec<String> = {
+            let mut state = self.state.lock().unwrap();
+            state.outputs.retain(|o| o.id != id);
+            let lost = state.routes.iter().filter(|(_, o)| *o == id).map(|(d, _)| d.clone()).collect();
+            state.routes.retain(|_, o| o != id);
+            lost
+        };
+        for device in lost {
+            let _ = self.events.send(BackendEvent::OutputLost(device));
+        }
+    }
+
+    /// Куда сейчас идёт звук устройства; `None` — на выход по умолчанию.
+    pub fn output(&self, id: &str) -> Option<String> {
+        self.state.lock().unwrap().routes.get(id).cloned()
+    }
+
+    /// Телефон `id` «проиграл» кусок звука: индикатор уровня его видит, если телефон
+    /// подключён. `false` — отвода нет, в запись звук не ушёл.
+    pub fn play(&self, id: &str, chunk: AudioChunk) -> bool {
+        let state = self.state.lock().unwrap();
+        if state.connected.iter().any(|c| c == id) {
+            state.meter.feed(&chunk.samples);
+        }
+        state.taps.get(id).is_some_and(|tap| tap.send(chunk))
+    }
+
+    /// Обработка, которую воркер выставил подключённому устройству.
+    pub fn dsp(&self, id: &str) -> Option<DspSettings> {
+        self.state.lock().unwrap().dsp.get(id).cloned()
+    }
+
+    pub fn calls(&self) -> Vec<FakeCall> {
+        self.state.lock().unwrap().calls.clone()
+    }
+
+    fn drop_connection_of(&self, id: &str) {
+        if self.disconnect_silently(id) {
+            let _ = self.events.send(BackendEvent::ConnectionLost(id.to_string()));
+        }
+    }
+
+    fn try_connect(&self, device: &BTDevice) -> Result<()> {
+        let mut state = self.state.lock().unwrap();
+        if let Some(reason) = state.connect_failures.pop_front() {
+            anyhow::bail!(reason);
+        }
+        if !state.devices.iter().any(|d| d.id == device.id) {
+            anyhow::bail!("Устройство {} недоступно", device.name);
+        }
+        if !state.connected.contains(&device.id) {
+            state.connected.push(device.id.clone());
+        }
+        state.routes.remove(&device.id);
+        state.dsp.remove(&device.id);
+        let _ = self.events.send(BackendEvent::Connected(device.id.clone()));
+        Ok(())
+    }
+
+    fn disconnect_silently(&self, id: &str) -> bool {
+        let mut state = self.state.lock().unwrap();
+        let before = state.connected.len();
+        state.connected.retain(|c| c != id);
+        state.routes.remove(id);
+        state.taps.remove(id);
+        state.dsp.remove(id);
+        state.connected.len() != before
+    }
+
+    fn record(&self, call: FakeCall) {
+        self.state.lock().unwrap().calls.push(call);
+    }
+}
+
+impl Default for FakeBackend {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AudioSinkBackend for FakeBackend {
+    async fn list_devices(&self) -> Result<Vec<BTDevice>> {
+        self.record(FakeCall::ListDevices);
+        Ok(self.state.lock().unwrap().devices.clone())
+    }
+
+    async fn connect(&mut self, device: &BTDevice) -> Result<()> {
+        self.record(FakeCall::Connect(device.id.clone()));
+        self.try_connect(device)
+    }
+
+    async fn reconnect(&mut self, device: &BTDevice) -> Result<()> {
+        self.record(FakeCall::Reconnect(device.id.clone()));
+        self.disconnect_silently(&device.id);
+        self.try_connect(device)
+    }
+
+    async fn disconnect(&mut self, id: &str) {
+        self.record(FakeCall::Disconnect(id.to_string()));
+        if self.disconnect_silently(id) {
+            let _ = self.events.send(BackendEvent::Disconnected(id.to_string()));
+        }
+    }
+
+    fn set_volume(&mut self, id: &str, volume: Volume) -> Result<()> {
+        self.record(FakeCall::SetVolume(id.to_string(), volume));
+        let mut state = self.state.lock().unwrap();
+        if state.connected.iter().any(|c| c == id) && state.fixed_volume.contains(id) {
+            anyhow::bail!("{}", Msg::VolumeUnavailable);
+        }
+        state.volumes.insert(id.to_string(), volume);
+        Ok(())
+    }
+
+    async fn list_outputs(&self) -> Result<Vec<AudioOutput>> {
+        Ok(self.state.lock().unwrap().outputs.clone())
+    }
+
+    async fn set_output(&mut self, id: &str, output: Option<&str>) -> Result<()> {
+        self.record(FakeCall::SetOutput(id.to_string(), output.map(str::to_string)));
+        let mut state = self.state.lock().unwrap();
+        if !state.connected.iter().any(|c| c == id) {
+            return Ok(());
+        }
+        state.routes.remove(id);
+        let Some(output) = output else { return Ok(()) };
+        if !state.outputs.iter().any(|o| o.id == output) {
+            anyhow::bail!("Устройство вывода {} недоступно", output);
+        }
+        state.routes.insert(id.to_string(), output.to_string());
+        Ok(())
+    }
+
+    fn set_tap(&mut self, id: &str, tap: Option<Tap>) -> Result<()> {
+        self.record(FakeCall::SetTap(id.to_string(), tap.is_some()));
+        let mut state = self.state.lock().unwrap();
+        match tap {
+            Some(tap) if state.connected.iter().any(|c| c == id) => state.taps.insert(id.to_string(), tap),
+            Some(_) => None,
+            None => state.taps.remove(id),
+        };
+        Ok(())
+    }
+
+    async fn set_dsp(&mut self, id: &str, settings: DspSettings) -> Result<()> {
+        self.record(FakeCall::SetDsp(id.to_string()));
+        let mut state = self.state.lock().unwrap();
+        match settings.is_empty() {
+            _ if !state.connected.iter().any(|c| c == id) => None,
+            true => state.dsp.remove(id),
+            false => state.dsp.insert(id.to_string(), settings),
+        };
+        Ok(())
+    }
+
+    fn level_meter(&self) -> LevelMeter {
+        self.state.lock().unwrap().meter.clone()
+    }
+
+    fn watch_devices(&mut self) -> Result<()> {
+        self.record(FakeCall::WatchDevices);
+        let mut state = self.state.lock().unwrap();
+        state.watching = true;
+        // Как и DeviceWatcher в Windows, сначала сообщаем об уже известных устройствах
+        for device in &state.devices {
+            let _ = self.events.send(BackendEvent::DeviceAdded(device.clone()));
+        }
+        Ok(())
+    }
+
+    fn subscribe(&self) -> broadcast::Receiver<BackendEvent> {
+        self.events.subscribe()
+    }
+}
@@ -0,0 +1,73 @@
+use crate::backend::BTDevice;
+use crate::media::{MediaAction, MediaControl, MetadataSource, NowPlaying};
+use anyhow::Result;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+/// Управление воспроизведением в памяти. Клоны разделяют журнал, поэтому тест
+/// отдаёт один клон `media_worker` и проверяет, куда ушли команды, по другому.
+#[derive(Clone, Default)]
+pub struct FakeMediaControl {
+    state: Arc<Mutex<FakeMediaState>>,
+}
+
+#[derive(Default)]
+struct FakeMediaState {
+    // Адресат (`BTDevice::id`) и команда — в порядке поступления
+    sent: Vec<(String, MediaAction)>,
+    failures: VecDeque<String>,
+}
+
+impl FakeMediaControl {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Следующая команда завершится ошибкой `reason`.
+    pub fn fail_next(&self, reason: &str) {
+        self.state.lock().unwrap().failures.push_back(reason.to_string());
+    }
+
+    pub fn sent(&self) -> Vec<(String, MediaAction)> {
+        self.state.lock().unwrap().sent.clone()
+    }
+}
+
+impl MediaControl for FakeMediaControl {
+    async fn send(&mut self, target: &BTDevice, action: MediaAction) -> Result<()> {
+        let mut state = self.state.lock().unwrap();
+        if let Some(reason) = state.failures.pop_front() {
+            anyhow::bail!(reason);
+        }
+        state.sent.push((target.id.clone(), action));
+        Ok(())
+    }
+}
+
+/// Источник сведений о треке в памяти: тест сам решает, что «играет» на каждом телефоне.
+#[derive(Clone, Default)]
+pub struct FakeMetadataSource {
+    playing: Arc<Mutex<Vec<(String, NowPlaying)>>>,
+}
+
+impl FakeMetadataSource {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// На телефоне `id` начал играть `now_playing`; `None` — остановился.
+    pub fn set(&self, id: &str, now_playing: Option<NowPlaying>) {
+        let mut playing = self.playing.lock().unwrap();
+        playing.retain(|(d, _)| d != id);
+        if let Some(now_playing) = now_playing {
+            playing.push((id.to_string(), now_playing));
+        }
+    }
+}
+
+impl MetadataSource for FakeMetadataSource {
+    async fn now_playing(&mut self, target: &BTDevice) -> Result<Option<NowPlaying>> {
+        let playing = self.playing.lock().unwrap();
+        Ok(playing.iter().find(|(id, _)| *id == target.id).map(|(_, n)| n.clone()))
+    }
+}
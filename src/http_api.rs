@@ -0,0 +1,313 @@
+//! HTTP API для мониторинга и управления с других машин.
+//!
+//! ```text
+//! GET  /status       → StatusReport
+//! GET  /devices      → [BTDevice]
+//! POST /connect      ← {"device": "Pixel 8"}
+//! POST /disconnect   ← {"device": "Pixel 8"} или {} — отключить все
+//! POST /volume       ← {"device": "Pixel 8", "change": "+10"}; без `device` — последнее подключённое
+//! GET  /events       → text/event-stream, событие `status` при каждом изменении
+//! ```
+//!
+//! Запросы выполняются так же, как запросы через `ipc`.
+
+use crate::config::HttpConfig;
+use crate::i18n::Msg;
+use crate::ipc::{self, ErrorKind, IpcContext, Request, Response as IpcResponse};
+use crate::state::StatusReport;
+use crate::volume::VolumeChange;
+use anyhow::{Context, Result};
+use axum::extract::rejection::JsonRejection;
+use axum::extract::{Request as HttpRequest, State};
+use axum::http::{header, StatusCode};
+use axum::middleware::{self, Next};
+use axum::response::sse::{Event, KeepAlive, Sse};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use serde::Deserialize;
+use std::convert::Infallible;
+use tokio_stream::wrappers::WatchStream;
+use tokio_stream::{Stream, StreamExt};
+
+#[derive(Debug, Deserialize)]
+struct ConnectBody {
+    device: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct DisconnectBody {
+    device: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct VolumeBody {
+    device: Option<String>,
+    change: VolumeChange,
+}
+
+/// Слушает `config.address`, пока жив процесс.
+pub async fn serve(config: HttpConfig, ctx: IpcContext) -> Result<()> {
+    let listener = tokio::net::TcpListener::bind(config.address)
+        .await
+        .with_context(|| Msg::HttpBindFailed { address: &config.address }.to_string())?;
+    log::info!(target: "http", "{}", Msg::LogHttpListening { address: &config.address });
+
+    axum::serve(listener, router(config.token, ctx)).await?;
+    Ok(())
+}
+
+/// Маршруты API; `token` — если задан, без него отвечает 401.
+pub fn router(token: Option<String>, ctx: IpcContext) -> Router {
+    let app = Router::new()
+        .route("/status", get(status))
+        .route("/devices", get(devices))
+        .route("/connect", post(connect))
+        .route("/disconnect", post(disconnect))
+        .route("/volume", post(volume))
+        .route("/events", get(events))
+        .with_state(ctx);
+    match token {
+        Some(token) => app.layer(middleware::from_fn_with_state(token, authorize)),
+        None => app,
+    }
+}
+
+async fn authorize(State(token): State<String>, request: HttpRequest, next: Next) -> Response {
+    let provided = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    if !provided.is_some_and(|provided| same_secret(provided.as_bytes(), token.as_bytes())) {
+        return error(StatusCode::UNAUTHORIZED, Msg::HttpUnauthorized.to_string());
+    }
+    next.run(request).await
+}
+
+// Сравнение за время, не зависящее от того, где токены расходятся: иначе токен можно
+// подобрать по времени ответа. Длина при этом не секрет
+fn same_secret(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+async fn status(State(ctx): State<IpcContext>) -> Response {
+    reply(ipc::handle(Request::Status, &ctx).await)
+}
+
+async fn devices(State(ctx): State<IpcContext>) -> Response {
+    reply(ipc::handle(Request::List, &ctx).await)
+}
+
+async fn connect(State(ctx): State<IpcContext>, body: Result<Json<ConnectBody>, JsonRejection>) -> Response {
+    match body {
+        Ok(Json(body)) => reply(ipc::handle(Request::Connect { device: body.device }, &ctx).await),
+        Err(rejection) => bad_request(rejection),
+    }
+}
+
+// Отключить все можно только явно, телом `{}`: пустой или испорченный запрос — ошибка
+async fn disconnect(State(ctx): State<IpcContext>, body: Result<Json<DisconnectBody>, JsonRejection>) -> Response {
+    match body {
+        Ok(Json(body)) => reply(ipc::handle(Request::Disconnect { device: body.device }, &ctx).await),
+        Err(rejection) => bad_request(rejection),
+    }
+}
+
+async fn volume(State(ctx): State<IpcContext>, body: Result<Json<VolumeBody>, JsonRejection>) -> Response {
+    match body {
+        Ok(Json(body)) => reply(ipc::handle(Request::SetVolume { device: body.device, change: body.change }, &ctx).await),
+        Err(rejection) => bad_request(rejection),
+    }
+}
+
+async fn events(State(ctx): State<IpcContext>) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    let stream = WatchStream::new(ctx.state).map(|state| {
+        let report = StatusReport::new(&state);
+        Ok(Event::default().event("status").json_data(report).unwrap_or_default())
+    });
+    Sse::new(stream).keep_alive(KeepAlive::default())
+}
+
+fn reply(response: IpcResponse) -> Response {
+    match response {
+        IpcResponse::Ok => StatusCode::NO_CONTENT.into_response(),
+        IpcResponse::Status(report) | IpcResponse::Event(report) => Json(report).into_response(),
+        IpcResponse::Devices { devices } => Json(devices).into_response(),
+        IpcResponse::Error { message, kind } => {
+            let status = match kind {
+                ErrorKind::NotFound => StatusCode::NOT_FOUND,
+                ErrorKind::Invalid => StatusCode::BAD_REQUEST,
+                ErrorKind::Failed => StatusCode::CONFLICT,
+            };
+            error(status, message)
+        }
+    }
+}
+
+// axum отвечает 422 на тело, которое не подходит по смыслу (громкость 150); для клиента
+// это такой же неверный запрос, как и неразобранный JSON
+fn bad_request(rejection: JsonRejection) -> Response {
+    error(StatusCode::BAD_REQUEST, rejection.body_text())
+}
+
+fn error(status: StatusCode, message: String) -> Response {
+    (status, Json(serde_json::json!({ "error": message }))).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::BTDevice;
+    use crate::state::ReceiverState;
+    use crate::worker::AppCommand;
+    use axum::body::Body;
+    use http_body_util::BodyExt;
+    use tokio::sync::{mpsc, watch};
+    use tower::ServiceExt;
+
+    fn phone() -> BTDevice {
+        BTDevice { id: "phone-1".to_string(), name: "Pixel".to_string() }
+    }
+
+    // Каналы держим в ответе: если их закрыть, воркер будет считаться завершённым
+    struct Fixture {
+        ctx: IpcContext,
+        cmd_rx: mpsc::Receiver<AppCommand>,
+        _tx_state: watch::Sender<ReceiverState>,
+        tx_devices: watch::Sender<Vec<BTDevice>>,
+    }
+
+    fn fixture() -> Fixture {
+        let (cmd_tx, cmd_rx) = mpsc::channel(10);
+        let (tx_state, state) = watch::channel(ReceiverState::default());
+        let (tx_devices, devices) = watch::channel(vec![phone()]);
+        let (shutdown, _) = mpsc::channel(1);
+        Fixture { ctx: IpcContext { cmd_tx, state, devices, shutdown }, cmd_rx, _tx_state: tx_state, tx_devices }
+    }
+
+    fn post(uri: &str, body: &str) -> HttpRequest {
+        HttpRequest::post(uri).header(header::CONTENT_TYPE, "application/json").body(Body::from(body.to_string())).unwrap()
+    }
+
+    fn get(uri: &str) -> HttpRequest {
+        HttpRequest::get(uri).body(Body::empty()).unwrap()
+    }
+
+    async fn call(app: &Router, request: HttpRequest) -> (StatusCode, serde_json::Value) {
+        let response = app.clone().oneshot(request).await.unwrap();
+        let status = response.status();
+        let bytes = response.into_body().collect().await.unwrap().to_bytes();
+        let json = match bytes.is_empty() {
+            true => serde_json::Value::Null,
+            false => serde_json::from_slice(&bytes).unwrap(),
+        };
+        (status, json)
+    }
+
+    #[tokio::test]
+    async fn status_and_devices() {
+        let fixture = fixture();
+        let app = router(None, fixture.ctx.clone());
+
+        let (status, json) = call(&app, get("/status")).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(json["running"], true);
+
+        let (status, json) = call(&app, get("/devices")).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(json, serde_json::json!([{"id": "phone-1", "name": "Pixel"}]));
+    }
+
+    #[tokio::test]
+    async fn commands_reach_the_worker() {
+        let mut fixture = fixture();
+        let app = router(None, fixture.ctx.clone());
+
+        let (status, _) = call(&app, post("/volume", r#"{"device":"Pixel","change":"+10"}"#)).await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+        assert_eq!(
+            fixture.cmd_rx.recv().await,
+            Some(AppCommand::SetVolume(Some("phone-1".to_string()), VolumeChange::Step(10)))
+        );
+
+        let (status, _) = call(&app, post("/disconnect", "{}")).await;
+        assert_eq!(status, StatusCode::NO_CONTENT, "без устройства отключаются все");
+        assert_eq!(fixture.cmd_rx.recv().await, Some(AppCommand::DisconnectAll));
+    }
+
+    #[tokio::test]
+    async fn unknown_device_is_not_found() {
+        let fixture = fixture();
+        let app = router(None, fixture.ctx.clone());
+
+        for (uri, body) in [
+            ("/connect", r#"{"device":"Nokia"}"#),
+            ("/disconnect", r#"{"device":"Nokia"}"#),
+            ("/volume", r#"{"device":"Nokia","change":"50"}"#),
+        ] {
+            let (status, json) = call(&app, post(uri, body)).await;
+            assert_eq!(status, StatusCode::NOT_FOUND, "{uri}");
+            assert_eq!(json["error"], Msg::DeviceNotFound { query: &"Nokia" }.to_string());
+        }
+    }
+
+    #[tokio::test]
+    async fn bad_requests_are_rejected() {
+        let mut fixture = fixture();
+        let app = router(None, fixture.ctx.clone());
+
+        for body in [r#"{"change":"150"}"#, r#"{"change":"громче"}"#, r#"{"device":"Pixel"}"#, "не json"] {
+            let (status, json) = call(&app, post("/volume", body)).await;
+            assert_eq!(status, StatusCode::BAD_REQUEST, "{body}");
+            assert!(json["error"].is_string());
+        }
+        let (status, _) = call(&app, post("/connect", "{}")).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        // Случайный запрос не должен отключать всё
+        let (status, _) = call(&app, post("/disconnect", "")).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let (status, _) = call(&app, HttpRequest::post("/disconnect").body(Body::empty()).unwrap()).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "без Content-Type");
+        assert!(fixture.cmd_rx.try_recv().is_err(), "воркер ничего не получил");
+    }
+
+    #[tokio::test]
+    async fn ambiguous_name_is_a_bad_request() {
+        let fixture = fixture();
+        let twin = BTDevice { id: "phone-2".to_string(), ..phone() };
+        fixture.tx_devices.send_replace(vec![phone(), twin]);
+        let app = router(None, fixture.ctx.clone());
+
+        let (status, _) = call(&app, post("/connect", r#"{"device":"Pixel"}"#)).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn failed_command_is_a_conflict() {
+        let mut fixture = fixture();
+        fixture.cmd_rx.close();
+        let app = router(None, fixture.ctx.clone());
+
+        let (status, json) = call(&app, post("/volume", r#"{"device":"Pixel","change":"mute"}"#)).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+        assert_eq!(json["error"], Msg::IpcWorkerGone.to_string());
+    }
+
+    #[tokio::test]
+    async fn token_is_required_when_set() {
+        let fixture = fixture();
+        let app = router(Some("s3cret".to_string()), fixture.ctx.clone());
+
+        let (status, json) = call(&app, get("/status")).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        assert_eq!(json["error"], Msg::HttpUnauthorized.to_string());
+
+        let with = |value: &str| HttpRequest::get("/status").header(header::AUTHORIZATION, value).body(Body::empty()).unwrap();
+        assert_eq!(call(&app, with("Bearer other")).await.0, StatusCode::UNAUTHORIZED);
+        assert_eq!(call(&app, with("Bearer s3cre")).await.0, StatusCode::UNAUTHORIZED, "начало токена");
+        assert_eq!(call(&app, with("Bearer s3cret2")).await.0, StatusCode::UNAUTHORIZED);
+        assert_eq!(call(&app, with("s3cret")).await.0, StatusCode::UNAUTHORIZED, "без схемы Bearer");
+        assert_eq!(call(&app, with("Bearer s3cret")).await.0, StatusCode::OK);
+    }
+}
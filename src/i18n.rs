@@ -0,0 +1,448 @@
+use serde::{Deserialize, Serialize};
+use std::fmt::{self, Display};
+use std::sync::atomic::{AtomicU8, Ordering};
+
+/// Язык интерфейса, диалогов и журнала.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Lang {
+    Ru,
+    En,
+}
+
+static CURRENT: AtomicU8 = AtomicU8::new(Lang::Ru as u8);
+
+impl Lang {
+    /// Язык системы: русский для `ru-*`, для остальных — английский.
+    pub fn detect() -> Lang {
+        match sys_locale::get_locale() {
+            Some(locale) if locale.to_lowercase().starts_with("ru") => Lang::Ru,
+            _ => Lang::En,
+        }
+    }
+
+    pub fn current() -> Lang {
+        match CURRENT.load(Ordering::Relaxed) {
+            0 => Lang::Ru,
+            _ => Lang::En,
+        }
+    }
+
+    /// Переключает язык всей программы. Уже показанные строки не меняются.
+    pub fn set_current(lang: Lang) {
+        CURRENT.store(lang as u8, Ordering::Relaxed);
+    }
+}
+
+// Каталог строк. Каждое сообщение обязано иметь перевод на все языки —
+// без него макрос не раскроется и программа не соберётся.
+macro_rules! messages {
+    ($($name:ident $({ $($field:ident),* })? => { ru: $ru:literal, en: $en:literal $(,)? })*) => {
+        /// Все строки, которые видит пользователь. Выводятся на текущем языке через `Display`.
+        pub enum Msg<'a> {
+            $($name $({ $($field: &'a dyn Display),* })?,)*
+        }
+
+        impl Msg<'_> {
+            pub fn text(&self, lang: Lang) -> String {
+                match self {
+                    $(Msg::$name $({ $($field),* })? => match lang {
+                        Lang::Ru => format!($ru),
+                        Lang::En => format!($en),
+                    },)*
+                }
+            }
+        }
+    };
+}
+
+impl Display for Msg<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.text(Lang::current()))
+    }
+}
+
+messages! {
+    // --- Меню трея ---
+    MenuScanning => { ru: "🔍 Поиск устройств...", en: "🔍 Searching for devices..." }
+    MenuConnecting { name } => { ru: "⏳ Подключение к {name}...", en: "⏳ Connecting to {name}..." }
+    MenuReconnectAttempt { name, attempt } => { ru: "🔄 {name} (попытка {attempt})", en: "🔄 {name} (attempt {attempt})" }
+    MenuReconnect => { ru: "🔄 Переподключить", en: "🔄 Reconnect" }
+    MenuDisconnect => { ru: "🔌 Отключить", en: "🔌 Disconnect" }
+    MenuDismiss => { ru: "✖ Убрать", en: "✖ Dismiss" }
+    MenuDisconnectAll => { ru: "🔌 Отключить все", en: "🔌 Disconnect all" }
+    MenuMedia => { ru: "🎵 Воспроизведение", en: "🎵 Playback" }
+    MenuPlayPause => { ru: "⏯ Пауза / продолжить", en: "⏯ Play / pause" }
+    MenuNextTrack => { ru: "⏭ Следующий трек", en: "⏭ Next track" }
+    MenuPreviousTrack => { ru: "⏮ Предыдущий трек", en: "⏮ Previous track" }
+    MenuVolumeUp => { ru: "🔊 Громче", en: "🔊 Volume up" }
+    MenuVolumeDown => { ru: "🔉 Тише", en: "🔉 Volume down" }
+    MenuVolume { volume } => { ru: "Громкость: {volume}", en: "Volume: {volume}" }
+    MenuVolumeUnavailable => { ru: "Громкость не меняется", en: "Volume cannot be changed" }
+    VolumeUnavailable => {
+        ru: "Громкость этого соединения не изменить: звук идёт мимо приёмника",
+        en: "The volume of this connection cannot be changed: audio bypasses the receiver",
+    }
+    MenuRecord => { ru: "⏺ Записывать", en: "⏺ Record" }
+    MenuRecording => { ru: "🔴 Идёт запись", en: "🔴 Recording" }
+    MenuNightMode => { ru: "🌙 Ночной режим", en: "🌙 Night mode" }
+    MenuOutput => { ru: "🔈 Выход", en: "🔈 Output" }
+    MenuOutputDefault => { ru: "Как в системе", en: "System default" }
+    MenuMute => { ru: "🔇 Без звука", en: "🔇 Mute" }
+    MenuNoDevices => { ru: "(Нет устройств)", en: "(No devices)" }
+    MenuAutoConnect => { ru: "⚡ Автоподключение", en: "⚡ Auto-connect" }
+    MenuRefresh => { ru: "🔄 Обновить список", en: "🔄 Refresh list" }
+    MenuCheckUpdate => { ru: "🆙 Проверить обновления", en: "🆙 Check for updates" }
+    MenuOpenLogs => { ru: "📂 Открыть папку журнала", en: "📂 Open log folder" }
+    MenuAutostart => { ru: "Автозагрузка", en: "Start with Windows" }
+    MenuLanguage => { ru: "🌐 Язык", en: "🌐 Language" }
+    MenuLanguageAuto => { ru: "Как в системе", en: "System default" }
+    MenuQuit => { ru: "❌ Выйти", en: "❌ Quit" }
+
+    // --- Причины сбоев, которые показываются в меню ---
+    ConnectionLost { name } => { ru: "Соединение с {name} потеряно", en: "Connection to {name} lost" }
+    ReconnectGaveUp { name, attempts } => {
+        ru: "Не удалось переподключиться к {name} (попыток: {attempts})",
+        en: "Could not reconnect to {name} ({attempts} attempts)",
+    }
+    DeviceUnavailable => { ru: "Устройство больше недоступно", en: "Device is no longer available" }
+    OpenStatus { status } => { ru: "Статус открытия: {status}", en: "Open status: {status}" }
+    ReconnectFailed => { ru: "Ошибка переподключения", en: "Reconnect failed" }
+
+    // --- Диалоги ---
+    LogErrorTitle => { ru: "Ошибка журнала", en: "Logging error" }
+    ConfigErrorTitle => { ru: "Ошибка в настройках", en: "Configuration error" }
+    ConfigErrorText { error } => {
+        ru: "{error:#}\n\nПрограмма запущена с настройками по умолчанию.",
+        en: "{error:#}\n\nStarted with default settings.",
+    }
+    UpdateErrorTitle => { ru: "Ошибка обновления", en: "Update failed" }
+    UpdateErrorText { error } => {
+        ru: "{error}\n\nПроверьте подключение к интернету.",
+        en: "{error}\n\nCheck your internet connection.",
+    }
+    NoReleasesTitle => { ru: "Обновления", en: "Updates" }
+    NoReleasesText => {
+        ru: "На GitHub пока нет доступных выпусков (релизов).",
+        en: "There are no releases on GitHub yet.",
+    }
+    UpdateAvailableTitle => { ru: "🆙 Доступно обновление", en: "🆙 Update available" }
+    UpdateAvailableText { latest, current } => {
+        ru: "Найдена новая версия: v{latest}\nВаша версия: v{current}\n\nЖелаете обновить программу?",
+        en: "New version found: v{latest}\nYour version: v{current}\n\nUpdate now?",
+    }
+    UpToDateTitle => { ru: "✅ Обновлений нет", en: "✅ No updates" }
+    UpToDateText => { ru: "У вас установлена самая последняя версия.", en: "You have the latest version." }
+    OnlyWindows => { ru: "BT Audio Receiver работает только в Windows.", en: "BT Audio Receiver only runs on Windows." }
+
+    // --- Ошибки обновления ---
+    UpdaterConfigError { error } => { ru: "Ошибка конфигурации: {error}", en: "Configuration error: {error}" }
+    UpdaterRequestError { error } => {
+        ru: "Ошибка запроса к GitHub (возможно, лимит запросов): {error}",
+        en: "GitHub request failed (possibly rate limited): {error}",
+    }
+    UpdaterThreadError { error } => { ru: "Ошибка потока: {error}", en: "Thread error: {error}" }
+    UpdaterBuildError { error } => { ru: "Ошибка сборки апдейтера: {error}", en: "Failed to build the updater: {error}" }
+    UpdaterReplaceError { error } => { ru: "Ошибка при замене файла: {error}", en: "Failed to replace the executable: {error}" }
+    UpdaterFatalThreadError { error } => { ru: "Критическая ошибка потока: {error}", en: "Fatal thread error: {error}" }
+
+    // --- Реестр ---
+    RegistryNeedsFix => {
+        ru: "Настройки неоптимальны. Запрашиваю права администратора...",
+        en: "Settings are not optimal. Requesting administrator rights...",
+    }
+    RegistryOk => { ru: "Реестр в порядке (DisableSnoop=1, RemoteWake=1)", en: "Registry is fine (DisableSnoop=1, RemoteWake=1)" }
+    RegistryFixed => {
+        ru: "Настройки успешно применены. Изменения вступят в силу после перезапуска Bluetooth",
+        en: "Settings applied. Changes take effect after Bluetooth restarts",
+    }
+    UacDeclined => { ru: "Пользователь отклонил запрос UAC.", en: "The UAC prompt was declined." }
+
+    // --- Журнал ---
+    LogStarted { version } => { ru: "BT Audio Receiver {version} запущен", en: "BT Audio Receiver {version} started" }
+    LogConnecting { name } => { ru: "Подключение к {name}...", en: "Connecting to {name}..." }
+    LogReconnecting { name } => { ru: "Переподключение к {name}...", en: "Reconnecting to {name}..." }
+    LogConnectionActive { id } => { ru: "Соединение с {id} активно", en: "Connection to {id} is active" }
+    LogClosedByWindows { id } => {
+        ru: "Windows сообщила о закрытии соединения с {id}",
+        en: "Windows reported the connection to {id} closed",
+    }
+    LogConnectionClosed { id } => { ru: "Соединение с {id} закрыто", en: "Connection to {id} closed" }
+    LogHeartbeatStarted { id } => { ru: "Heartbeat для {id} запущен", en: "Heartbeat for {id} started" }
+    LogHeartbeatClosed { id } => {
+        ru: "Соединение с {id} закрыто, останавливаюсь",
+        en: "Connection to {id} closed, stopping",
+    }
+    LogHeartbeatStopped { id } => { ru: "Heartbeat для {id} остановлен", en: "Heartbeat for {id} stopped" }
+    LogWatchStarted => { ru: "Наблюдение за устройствами запущено", en: "Device watcher started" }
+    LogWatchUnavailable { error } => {
+        ru: "Наблюдение за устройствами недоступно: {error:#}",
+        en: "Device watcher unavailable: {error:#}",
+    }
+    LogRetryAttempt { attempt, name } => {
+        ru: "Попытка переподключения #{attempt} к {name}",
+        en: "Reconnect attempt #{attempt} to {name}",
+    }
+    LogRetryFailed { error } => { ru: "Попытка не удалась: {error:#}", en: "Attempt failed: {error:#}" }
+    LogConnectFailed { name, error } => {
+        ru: "Ошибка подключения к {name}: {error:#}",
+        en: "Failed to connect to {name}: {error:#}",
+    }
+    LogNoFreeSlots => {
+        ru: "Мест нет, отключаю самое давнее соединение",
+        en: "No free slots, disconnecting the oldest connection",
+    }
+    LogAutoConnect { name } => { ru: "Автоподключение к {name}", en: "Auto-connecting to {name}" }
+    LogPreempt { target, current } => {
+        ru: "{target} важнее, чем {current} — переключаюсь",
+        en: "{target} has priority over {current}, switching",
+    }
+    LogPreemptFailed { name } => {
+        ru: "Переключиться не удалось, возвращаюсь к {name}",
+        en: "Switch failed, going back to {name}",
+    }
+    LogCommandFailed { error } => { ru: "Ошибка отправки команды: {error}", en: "Failed to send command: {error}" }
+    LogIconFailed { error } => { ru: "Не удалось обновить значок: {error}", en: "Failed to update tray icon: {error}" }
+    LogOpenFailed { path, error } => { ru: "Не удалось открыть {path}: {error}", en: "Failed to open {path}: {error}" }
+    LogSettingsSaveFailed { error } => {
+        ru: "Не удалось сохранить настройки: {error:#}",
+        en: "Failed to save settings: {error:#}",
+    }
+    LogLastDeviceSaveFailed { error } => {
+        ru: "Не удалось сохранить последнее устройство: {error:#}",
+        en: "Failed to save the last device: {error:#}",
+    }
+    LogConfigCreateFailed { path, error } => {
+        ru: "Не удалось создать {path}: {error:#}",
+        en: "Failed to create {path}: {error:#}",
+    }
+    LogConfigUpgradeSaveFailed { path, error } => {
+        ru: "Не удалось записать обновлённые настройки в {path}, они действуют до выхода: {error:#}",
+        en: "Failed to write upgraded settings to {path}, they apply until exit: {error:#}",
+    }
+    LogConfigUpgraded { version } => {
+        ru: "Файл настроек обновлён до версии {version}",
+        en: "Settings file upgraded to version {version}",
+    }
+    LogUpdateCheck { version } => {
+        ru: "Проверка обновлений, текущая версия {version}",
+        en: "Checking for updates, current version {version}",
+    }
+    LogNoReleases => { ru: "Релизов на GitHub нет", en: "No releases on GitHub" }
+    LogUpdateAvailable { version } => { ru: "Доступна версия {version}", en: "Version {version} is available" }
+    LogUpdateInstalled { version } => { ru: "Обновление до {version} установлено", en: "Updated to {version}" }
+    LogPanic { info } => { ru: "Паника: {info}", en: "Panic: {info}" }
+    LogWriteFailed { error } => { ru: "Не удалось записать журнал: {error}", en: "Failed to write the log: {error}" }
+
+    // --- Командная строка ---
+    DeviceNotFound { query } => { ru: "Устройство \"{query}\" не найдено", en: "Device \"{query}\" not found" }
+    DeviceAmbiguous { query, ids } => {
+        ru: "Под именем \"{query}\" несколько устройств, укажите id:\n{ids}",
+        en: "Several devices are named \"{query}\", specify the id:\n{ids}",
+    }
+    CliNotRunning => { ru: "Приёмник не запущен", en: "The receiver is not running" }
+    CliNothingToDisconnect => {
+        ru: "Приёмник не запущен — отключать нечего",
+        en: "The receiver is not running, nothing to disconnect",
+    }
+    CliNoConnections => { ru: "Нет активных соединений", en: "No active connections" }
+    CliHoldingConnection => {
+        ru: "Соединение удерживается, Ctrl+C — отключиться",
+        en: "Holding the connection, press Ctrl+C to disconnect",
+    }
+    CliUpdateAvailable { latest, current } => {
+        ru: "Доступна версия v{latest} (установлена v{current})",
+        en: "Version v{latest} is available (installed v{current})",
+    }
+    CliUpToDate { version } => { ru: "Установлена последняя версия v{version}", en: "v{version} is the latest version" }
+
+    // --- Управление из другого процесса ---
+    IpcWorkerGone => { ru: "Приёмник завершает работу", en: "The receiver is shutting down" }
+    IpcConnectTimeout { name } => {
+        ru: "Не дождались подключения к {name}",
+        en: "Timed out waiting for {name} to connect",
+    }
+    IpcClosed => { ru: "Приёмник закрыл соединение", en: "The receiver closed the connection" }
+    IpcBadMessage { error } => { ru: "Некорректное сообщение: {error}", en: "Malformed message: {error}" }
+    IpcVersionMismatch { found, supported } => {
+        ru: "Версия протокола {found} не поддерживается (нужна {supported})",
+        en: "Protocol version {found} is not supported (expected {supported})",
+    }
+    IpcReplaceTimeout => {
+        ru: "Запущенный экземпляр не завершился",
+        en: "The running instance did not shut down",
+    }
+    LogIpcListening { endpoint } => { ru: "Жду команд на {endpoint}", en: "Listening for commands on {endpoint}" }
+    LogIpcClientGone { error } => { ru: "Клиент отключился: {error:#}", en: "Client disconnected: {error:#}" }
+    HttpBindFailed { address } => { ru: "Не удалось занять адрес {address}", en: "Failed to bind {address}" }
+    HttpUnauthorized => { ru: "Нужен токен доступа", en: "An access token is required" }
+    LogHttpListening { address } => { ru: "HTTP API доступен на http://{address}", en: "HTTP API is available at http://{address}" }
+    LogHttpFailed { error } => { ru: "HTTP API недоступен: {error:#}", en: "HTTP API is unavailable: {error:#}" }
+    LogMqttConnected { host } => { ru: "Подключено к MQTT-брокеру {host}", en: "Connected to MQTT broker {host}" }
+    LogMqttError { error } => { ru: "Ошибка MQTT: {error}", en: "MQTT error: {error}" }
+    LogMqttCommandFailed { command, error } => {
+        ru: "Команда MQTT {command} не выполнена: {error}",
+        en: "MQTT command {command} failed: {error}",
+    }
+    MqttEntityState => { ru: "Состояние", en: "State" }
+    MqttEntityConnected => { ru: "Подключено", en: "Connected" }
+    MqttEntityDevice => { ru: "Устройство", en: "Device" }
+    MqttEntityAvailable => { ru: "Доступные устройства", en: "Available devices" }
+    MqttEntityConnect => { ru: "Подключить", en: "Connect" }
+    MqttEntityDisconnect => { ru: "Отключить все", en: "Disconnect all" }
+    MediaNoSession => { ru: "Сейчас ничего не воспроизводится", en: "Nothing is playing right now" }
+    MediaNoPhone => { ru: "Телефон не подключён", en: "No phone is connected" }
+    MediaRejected => { ru: "Источник не принял команду", en: "The source rejected the command" }
+    LogMediaFailed { action, error } => { ru: "Команда «{action}» не выполнена: {error:#}", en: "Command \"{action}\" failed: {error:#}" }
+    LogMetadataFailed { error } => {
+        ru: "Не удалось узнать, что играет: {error:#}",
+        en: "Failed to get what is playing: {error:#}",
+    }
+    LogHotkeysFailed { error } => {
+        ru: "Медиаклавиши заняты другой программой: {error}",
+        en: "Media keys are taken by another program: {error}",
+    }
+    VolumeInvalid { value } => {
+        ru: "Непонятная громкость «{value}»: нужно число 0–100, +N, -N, mute, unmute или toggle",
+        en: "Invalid volume \"{value}\": expected 0-100, +N, -N, mute, unmute or toggle",
+    }
+    LogVolumeFailed { error } => { ru: "Не удалось изменить громкость: {error:#}", en: "Failed to change volume: {error:#}" }
+    LogOutputUnavailable { error } => {
+        ru: "Выбранный выход недоступен, звук идёт на выход по умолчанию: {error:#}",
+        en: "The selected output is unavailable, audio goes to the default output: {error:#}",
+    }
+    LogOutputLost { name } => {
+        ru: "Выход для {name} пропал, звук переключён на выход по умолчанию",
+        en: "The output for {name} disappeared, audio switched to the default output",
+    }
+    LogOutputsUnavailable { error } => {
+        ru: "Не удалось получить список устройств вывода: {error:#}",
+        en: "Failed to list output devices: {error:#}",
+    }
+    LogGraphOutputLost { id } => { ru: "Граф {id} потерял устройство вывода", en: "The graph of {id} lost its output device" }
+    OutputUnavailable { id } => { ru: "нет устройства вывода {id}", en: "no output device {id}" }
+    RecordingFolderFailed { path } => { ru: "Не удалось создать папку для записей {path}", en: "Failed to create the recordings folder {path}" }
+    RecordingFileFailed { path } => { ru: "Не удалось создать файл {path}", en: "Failed to create file {path}" }
+    LogRecordingStarted { path } => { ru: "Запись в {path}", en: "Recording to {path}" }
+    LogRecordingSaved { path } => { ru: "Запись сохранена: {path}", en: "Recording saved: {path}" }
+    LogRecordingDropped { name, chunks } => {
+        ru: "Запись {name} не успевала за звуком, пропущено кусков: {chunks}",
+        en: "Recording of {name} fell behind, chunks skipped: {chunks}",
+    }
+    LogRecordingFailed { name, error } => {
+        ru: "Запись {name} прервана: {error:#}",
+        en: "Recording of {name} stopped: {error:#}",
+    }
+    LogTapFailed { name, error } => {
+        ru: "Не удалось начать запись {name}: {error:#}",
+        en: "Failed to start recording {name}: {error:#}",
+    }
+    LogStreamPathUnavailable { id, error } => {
+        ru: "Звук {id} идёт мимо приёмника, громкость регулироваться не будет: {error:#}",
+        en: "Audio from {id} bypasses the receiver, volume control is unavailable: {error:#}",
+    }
+    LogPresetFailed { name, error } => {
+        ru: "Пресет эквалайзера {name} не загружен: {error:#}",
+        en: "Equalizer preset {name} was not loaded: {error:#}",
+    }
+    LogDspFailed { name, error } => {
+        ru: "Не удалось включить эквалайзер для {name}: {error:#}",
+        en: "Failed to enable the equalizer for {name}: {error:#}",
+    }
+    NoCaptureEndpoint => { ru: "у устройства нет звукового входа", en: "the device has no audio capture endpoint" }
+    LogIpcActivated => {
+        ru: "Повторный запуск: обновляю список устройств",
+        en: "Launched again: refreshing the device list",
+    }
+    LogIpcShutdown => { ru: "Завершение по просьбе другого экземпляра", en: "Shutting down at another instance's request" }
+    LogAlreadyRunning => {
+        ru: "Приёмник уже запущен, передаю запуск ему",
+        en: "The receiver is already running, handing the launch over to it",
+    }
+    LogIpcFailed { error } => {
+        ru: "Управление из других процессов недоступно: {error:#}",
+        en: "Control from other processes is unavailable: {error:#}",
+    }
+
+    // --- Файлы и настройки ---
+    CreateFailed { path } => { ru: "Не удалось создать {path}", en: "Failed to create {path}" }
+    OpenFailed { path } => { ru: "Не удалось открыть {path}", en: "Failed to open {path}" }
+    ReadFailed { path } => { ru: "Не удалось прочитать {path}", en: "Failed to read {path}" }
+    WriteFailed { path } => { ru: "Не удалось записать {path}", en: "Failed to write {path}" }
+    ConfigFileError { path } => { ru: "Ошибка в файле настроек {path}", en: "Error in settings file {path}" }
+    ConfigMustBePositive { key } => { ru: "{key} должно быть больше 0", en: "{key} must be greater than 0" }
+    ConfigMustNotBeEmpty { key } => { ru: "{key} не может быть пустым", en: "{key} must not be empty" }
+    ConfigMaxDelay { section, max, initial } => {
+        ru: "{section}.max_delay_ms ({max}) меньше initial_delay_ms ({initial})",
+        en: "{section}.max_delay_ms ({max}) is less than initial_delay_ms ({initial})",
+    }
+    ConfigMultiplier { section, value } => {
+        ru: "{section}.multiplier = {value}: должно быть не меньше 1.0",
+        en: "{section}.multiplier = {value}: must be at least 1.0",
+    }
+    ConfigJitter { section, value } => {
+        ru: "{section}.jitter = {value}: должно быть от 0.0 до 1.0",
+        en: "{section}.jitter = {value}: must be between 0.0 and 1.0",
+    }
+    ConfigAnchorGain { value } => {
+        ru: "receiver.anchor_gain = {value}: должно быть больше 0 и не больше 1",
+        en: "receiver.anchor_gain = {value}: must be greater than 0 and at most 1",
+    }
+    ConfigDuplicatePriority { id } => {
+        ru: "auto_connect.priority: устройство \"{id}\" указано дважды",
+        en: "auto_connect.priority: device \"{id}\" is listed twice",
+    }
+    ConfigUnknownLogTarget { target, known } => {
+        ru: "log.targets: неизвестная цель \"{target}\", доступны: {known}",
+        en: "log.targets: unknown target \"{target}\", available: {known}",
+    }
+    ConfigHttpTokenRequired { address } => {
+        ru: "http.address = {address} доступен не только с этого компьютера — задайте http.token",
+        en: "http.address = {address} is reachable from other machines — set http.token",
+    }
+    ConfigMqttWildcard { key } => {
+        ru: "{key} не может содержать «+» и «#»",
+        en: "{key} must not contain '+' or '#'",
+    }
+    ConfigPercent { key, value } => {
+        ru: "{key} должно быть от 0 до 100, а не {value}",
+        en: "{key} must be between 0 and 100, not {value}",
+    }
+    ConfigRange { key, value, min, max } => {
+        ru: "{key} должно быть от {min} до {max}, а не {value}",
+        en: "{key} must be between {min} and {max}, not {value}",
+    }
+    ConfigUnknownPreset { key, name } => {
+        ru: "{key}: нет пресета эквалайзера \"{name}\" в [equalizer]",
+        en: "{key}: there is no equalizer preset \"{name}\" in [equalizer]",
+    }
+    ConfigPresetFile { key, error } => {
+        ru: "{key}: не удалось загрузить пресет: {error:#}",
+        en: "{key}: failed to load the preset: {error:#}",
+    }
+    EqFileError { path } => { ru: "Ошибка в файле пресета {path}", en: "Error in preset file {path}" }
+    EqLine { number, line } => { ru: "строка {number} «{line}»", en: "line {number} \"{line}\"" }
+    EqNoFilters => { ru: "в файле нет ни одного фильтра", en: "the file contains no filters" }
+    EqNumber => { ru: "число", en: "a number" }
+    EqExpected { expected, found } => {
+        ru: "ожидалось {expected}, а не «{found}»",
+        en: "expected {expected}, found \"{found}\"",
+    }
+    EqMissing { key } => { ru: "не задан {key}", en: "{key} is missing" }
+    EqUnsupportedCommand { command } => {
+        ru: "команда {command} не поддерживается, можно только Preamp и Filter",
+        en: "command {command} is not supported, only Preamp and Filter are",
+    }
+    EqUnsupportedFilter { filter } => {
+        ru: "фильтр {filter} не поддерживается; поддерживаются PK, LS/LSC, HS/HSC, LP/LPQ и HP/HPQ",
+        en: "filter {filter} is not supported; supported are PK, LS/LSC, HS/HSC, LP/LPQ and HP/HPQ",
+    }
+    ConfigVersionInvalid => {
+        ru: "version должно быть целым неотрицательным числом",
+        en: "version must be a non-negative integer",
+    }
+    ConfigVersionTooNew { found, supported } => {
+        ru: "Файл создан более новой версией программы (схема {found}, поддерживается до {supported})",
+        en: "The file was written by a newer version (schema {found}, supported up to {supported})",
+    }
+}
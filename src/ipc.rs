@@ -0,0 +1,596 @@
+//! Управление запущенным экземпляром из другого процесса.
+//!
+//! Протокол — строки JSON, по одной на запрос и на ответ. В каждом сообщении
+//! есть поле `v` с версией протокола; сервер отвечает ошибкой на чужую версию,
+//! не пытаясь разобрать запрос.
+//!
+//! ```text
+//! → {"v":1,"cmd":"connect","device":"Pixel 8"}
+//! ← {"v":1,"type":"ok"}
+//! → {"v":1,"cmd":"subscribe"}
+//! ← {"v":1,"type":"event","running":true,"scanning":false,"sessions":[...]}
+//! ```
+
+use crate::backend::BTDevice;
+use crate::cli::{resolve_device, DeviceLookupError};
+use crate::i18n::Msg;
+use crate::state::{ConnectionState, ReceiverState, StatusReport};
+use crate::volume::VolumeChange;
+use crate::worker::AppCommand;
+use anyhow::{Context, Result};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::path::PathBuf;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader, Lines, ReadHalf, WriteHalf};
+use tokio::sync::{mpsc, watch};
+
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// Сколько ждать исхода `connect`, прежде чем ответить ошибкой.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Сколько ждать, пока заменяемый экземпляр освободит точку подключения.
+const REPLACE_TIMEOUT: Duration = Duration::from_secs(15);
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "cmd", rename_all = "snake_case")]
+pub enum Request {
+    Status,
+    List,
+    /// `device` — id или имя, как в `cli::resolve_device`.
+    Connect { device: String },
+    /// Без `device` отключаются все.
+    Disconnect {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        device: Option<String>,
+    },
+    /// Без `device` меняется громкость последнего подключённого.
+    SetVolume {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        device: Option<String>,
+        change: VolumeChange,
+    },
+    /// После подтверждения сервер присылает `Event` при каждом изменении состояния.
+    Subscribe,
+    /// Повторный запуск без команды: запущенный экземпляр обновляет список устройств.
+    Activate,
+    /// Завершить работу, отпустив подключённые устройства.
+    Shutdown,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Response {
+    Ok,
+    Error {
+        message: String,
+        /// Поле появилось позже первой версии протокола, поэтому необязательное.
+        #[serde(default, skip_serializing_if = "ErrorKind::is_failed")]
+        kind: ErrorKind,
+    },
+    Status(StatusReport),
+    Devices { devices: Vec<BTDevice> },
+    Event(StatusReport),
+}
+
+/// Чем плох запрос, если он не выполнен. HTTP API переводит это в код ответа.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorKind {
+    /// Запрос верный, но выполнить его не удалось.
+    #[default]
+    Failed,
+    /// Нет такого устройства.
+    NotFound,
+    /// Запрос не разобран или неоднозначен.
+    Invalid,
+}
+
+impl ErrorKind {
+    fn is_failed(&self) -> bool {
+        *self == ErrorKind::Failed
+    }
+}
+
+impl Response {
+    pub fn error(error: &anyhow::Error) -> Self {
+        let kind = match error.downcast_ref::<DeviceLookupError>() {
+            Some(DeviceLookupError::NotFound { .. }) => ErrorKind::NotFound,
+            Some(DeviceLookupError::Ambiguous { .. }) => ErrorKind::Invalid,
+            None => ErrorKind::Failed,
+        };
+        Response::Error { message: format!("{error:#}"), kind }
+    }
+}
+
+/// Имя канала для текущего пользователя: у каждого пользователя свой экземпляр.
+#[cfg(windows)]
+pub fn endpoint() -> PathBuf {
+    let user = std::env::var("USERNAME").unwrap_or_default();
+    PathBuf::from(format!(r"\\.\pipe\BT-Audio-Receiver-{user}"))
+}
+
+/// Сокет в `$XDG_RUNTIME_DIR`, а без него — во временной папке.
+#[cfg(unix)]
+pub fn endpoint() -> PathBuf {
+    let user = std::env::var("USER").unwrap_or_default();
+    dirs::runtime_dir()
+        .unwrap_or_else(std::env::temp_dir)
+        .join(format!("bt-audio-receiver-{user}.sock"))
+}
+
+/// То, с чем работает сервер: те же каналы, что у трея.
+#[derive(Clone)]
+pub struct IpcContext {
+    pub cmd_tx: mpsc::Sender<AppCommand>,
+    pub state: watch::Receiver<ReceiverState>,
+    pub devices: watch::Receiver<Vec<BTDevice>>,
+    /// Просьба трею завершиться (`Request::Shutdown`).
+    pub shutdown: mpsc::Sender<()>,
+}
+
+/// Занятая точка подключения. Создаётся заранее, чтобы ошибка была видна до запуска трея.
+pub struct IpcServer {
+    #[cfg(windows)]
+    pipe: tokio::net::windows::named_pipe::NamedPipeServer,
+    #[cfg(unix)]
+    listener: tokio::net::UnixListener,
+    path: PathBuf,
+}
+
+impl IpcServer {
+    #[cfg(windows)]
+    pub fn bind() -> Result<Self> {
+        use tokio::net::windows::named_pipe::ServerOptions;
+
+        let path = endpoint();
+        // first_pipe_instance: если канал уже создан другим процессом, получим ошибку, а не вторую копию
+        let pipe = ServerOptions::new()
+            .first_pipe_instance(true)
+            .create(&path)
+            .with_context(|| Msg::CreateFailed { path: &path.display() }.to_string())?;
+        Ok(Self { pipe, path })
+    }
+
+    #[cfg(unix)]
+    pub fn bind() -> Result<Self> {
+        use std::os::unix::fs::PermissionsExt;
+
+        let path = endpoint();
+        // Файл сокета остаётся после аварийного завершения. Если на нём никто не слушает — он ничей
+        if path.exists() {
+            if std::os::unix::net::UnixStream::connect(&path).is_ok() {
+                anyhow::bail!("{}", Msg::CreateFailed { path: &path.display() });
+            }
+            let _ = std::fs::remove_file(&path);
+        }
+        let listener = tokio::net::UnixListener::bind(&path)
+            .with_context(|| Msg::CreateFailed { path: &path.display() }.to_string())?;
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600))?;
+        Ok(Self { listener, path })
+    }
+
+    /// Просит запущенный экземпляр завершиться и занимает его место.
+    pub async fn take_over(mut client: IpcClient) -> Result<Self> {
+        match client.request(&Request::Shutdown).await? {
+            Response::Error { message, .. } => anyhow::bail!(message),
+            _ => drop(client),
+        }
+
+        let deadline = tokio::time::Instant::now() + REPLACE_TIMEOUT;
+        loop {
+            match Self::bind() {
+                Ok(server) => return Ok(server),
+                Err(e) if tokio::time::Instant::now() >= deadline => {
+                    return Err(e.context(Msg::IpcReplaceTimeout.to_string()))
+                }
+                Err(_) => tokio::time::sleep(Duration::from_millis(100)).await,
+            }
+        }
+    }
+
+    /// Принимает клиентов, пока жив процесс. Каждый клиент обслуживается отдельной задачей.
+    pub async fn run(self, ctx: IpcContext) -> Result<()> {
+        log::info!(target: "ipc", "{}", Msg::LogIpcListening { endpoint: &self.path.display() });
+
+        #[cfg(windows)]
+        {
+            use tokio::net::windows::named_pipe::ServerOptions;
+
+            let mut pipe = self.pipe;
+            loop {
+                pipe.connect().await?;
+                // Следующий экземпляр канала создаём до того, как отдать текущий клиенту
+                let next = ServerOptions::new().create(&self.path)?;
+                tokio::spawn(serve_client(std::mem::replace(&mut pipe, next), ctx.clone()));
+            }
+        }
+
+        #[cfg(unix)]
+        loop {
+            let (stream, _) = self.listener.accept().await?;
+            tokio::spawn(serve_client(stream, ctx.clone()));
+        }
+    }
+}
+
+async fn serve_client<S: AsyncRead + AsyncWrite>(stream: S, ctx: IpcContext) {
+    let (reader, mut writer) = tokio::io::split(stream);
+    let mut lines = BufReader::new(reader).lines();
+
+    while let Ok(Some(line)) = lines.next_line().await {
+        let result = match decode::<Request>(&line) {
+            Ok(Request::Subscribe) => subscribe(&mut writer, ctx.state.clone()).await,
+            Ok(request) => {
+                let response = handle(request, &ctx).await;
+                write(&mut writer, &response).await
+            }
+            Err(e) => {
+                let response = Response::Error { message: format!("{e:#}"), kind: ErrorKind::Invalid };
+                write(&mut writer, &response).await
+            }
+        };
+        if let Err(e) = result {
+            log::debug!(target: "ipc", "{}", Msg::LogIpcClientGone { error: &e });
+            return;
+        }
+    }
+}
+
+/// Выполняет запрос. Кроме канала, им пользуется HTTP API (`http_api`).
+pub(crate) async fn handle(request: Request, ctx: &IpcContext) -> Response {
+    let result = match request {
+        Request::Status => Ok(Response::Status(StatusReport::new(&ctx.state.borrow()))),
+        Request::List => Ok(Response::Devices { devices: ctx.devices.borrow().clone() }),
+        Request::Connect { device } => connect(&device, ctx).await.map(|()| Response::Ok),
+        Request::Disconnect { device } => disconnect(device.as_deref(), ctx).await.map(|()| Response::Ok),
+        Request::SetVolume { device, change } => set_volume(device.as_deref(), change, ctx).await.map(|()| Response::Ok),
+        Request::Activate => {
+            log::info!(target: "ipc", "{}", Msg::LogIpcActivated);
+            send(ctx, AppCommand::Scan).await.map(|()| Response::Ok)
+        }
+        Request::Shutdown => {
+            log::info!(target: "ipc", "{}", Msg::LogIpcShutdown);
+            // Отвечаем сразу: клиент ждёт не ответа, а освобождения точки подключения
+            let _ = ctx.shutdown.try_send(());
+            Ok(Response::Ok)
+        }
+        Request::Subscribe => unreachable!("подписка обслуживается отдельно"),
+    };
+    result.unwrap_or_else(|e| Response::error(&e))
+}
+
+// Отвечаем только когда стал известен исход — скрипту так проще, чем следить за событиями
+async fn connect(query: &str, ctx: &IpcContext) -> Result<()> {
+    let device = resolve_device(&ctx.devices.borrow(), query)?;
+    let mut state = ctx.state.clone();
+    let before = state.borrow_and_update().get(&device.id).clone();
+    if before.is_connected() {
+        return Ok(());
+    }
+    send(ctx, AppCommand::Connect(device.id.clone())).await?;
+
+    let outcome = tokio::time::timeout(CONNECT_TIMEOUT, async {
+        // Ошибка, оставшаяся от прошлой попытки, — не ответ на эту
+        let mut started = false;
+        while state.changed().await.is_ok() {
+            let state = state.borrow_and_update();
+            let now = state.get(&device.id);
+            started |= *now != before;
+            match now {
+                ConnectionState::Connected { .. } => return Ok(()),
+                ConnectionState::Failed { reason, .. } if started => anyhow::bail!("{reason}"),
+                _ => {}
+            }
+        }
+        anyhow::bail!("{}", Msg::IpcWorkerGone)
+    });
+    outcome.await.unwrap_or_else(|_| anyhow::bail!("{}", Msg::IpcConnectTimeout { name: &device.name }))
+}
+
+async fn disconnect(query: Option<&str>, ctx: &IpcContext) -> Result<()> {
+    let Some(query) = query else { return send(ctx, AppCommand::DisconnectAll).await };
+    let device = resolve_known(query, ctx)?;
+    send(ctx, AppCommand::Disconnect(device.id)).await
+}
+
+// Воркер меняет громкость молча, поэтому то, что не получится, отвергаем здесь
+async fn set_volume(query: Option<&str>, change: VolumeChange, ctx: &IpcContext) -> Result<()> {
+    let id = match query {
+        Some(query) => resolve_known(query, ctx)?.id,
+        None => match ctx.state.borrow().connected().last() {
+            Some(device) => device.id.clone(),
+            None => anyhow::bail!("{}", Msg::MediaNoPhone),
+        },
+    };
+    {
+        let state = ctx.state.borrow();
+        if state.get(&id).is_connected() && !state.volumes.contains_key(&id) {
+            anyhow::bail!("{}", Msg::VolumeUnavailable);
+        }
+    }
+    send(ctx, AppCommand::SetVolume(Some(id), change)).await
+}
+
+// Подключённое устройство может уже пропасть из списка доступных
+fn resolve_known(query: &str, ctx: &IpcContext) -> Result<BTDevice> {
+    let known: Vec<BTDevice> = {
+        let state = ctx.state.borrow();
+        let active = state.sessions.iter().filter_map(|(_, s)| s.device().cloned());
+        active.chain(ctx.devices.borrow().iter().cloned()).collect()
+    };
+    Ok(resolve_device(&known, query)?)
+}
+
+async fn subscribe<W: AsyncWrite>(writer: &mut WriteHalf<W>, mut state: watch::Receiver<ReceiverState>) -> Result<()> {
+    write(writer, &Response::Ok).await?;
+    loop {
+        let report = StatusReport::new(&state.borrow_and_update());
+        write(writer, &Response::Event(report)).await?;
+        if state.changed().await.is_err() {
+            return Ok(());
+        }
+    }
+}
+
+async fn send(ctx: &IpcContext, command: AppCommand) -> Result<()> {
+    ctx.cmd_tx.send(command).await.map_err(|_| anyhow::anyhow!("{}", Msg::IpcWorkerGone))
+}
+
+/// Подключение к запущенному экземпляру.
+pub struct IpcClient {
+    #[cfg(windows)]
+    lines: Lines<BufReader<ReadHalf<tokio::net::windows::named_pipe::NamedPipeClient>>>,
+    #[cfg(windows)]
+    writer: WriteHalf<tokio::net::windows::named_pipe::NamedPipeClient>,
+    #[cfg(unix)]
+    lines: Lines<BufReader<ReadHalf<tokio::net::UnixStream>>>,
+    #[cfg(unix)]
+    writer: WriteHalf<tokio::net::UnixStream>,
+}
+
+impl IpcClient {
+    /// `None` — экземпляр не запущен.
+    pub async fn connect() -> Result<Option<Self>> {
+        let path = endpoint();
+
+        #[cfg(windows)]
+        let stream = {
+            use tokio::net::windows::named_pipe::ClientOptions;
+            use windows::Win32::Foundation::{ERROR_FILE_NOT_FOUND, ERROR_PIPE_BUSY};
+
+            loop {
+                match ClientOptions::new().open(&path) {
+                    Ok(client) => break client,
+                    Err(e) if e.raw_os_error() == Some(ERROR_FILE_NOT_FOUND.0 as i32) => return Ok(None),
+                    // Все экземпляры канала заняты — сервер вот-вот создаст следующий
+                    Err(e) if e.raw_os_error() == Some(ERROR_PIPE_BUSY.0 as i32) => {
+                        tokio::time::sleep(Duration::from_millis(50)).await;
+                    }
+                    Err(e) => return Err(e).with_context(|| Msg::OpenFailed { path: &path.display() }.to_string()),
+                }
+            }
+        };
+
+        #[cfg(unix)]
+        let stream = match tokio::net::UnixStream::connect(&path).await {
+            Ok(stream) => stream,
+            Err(e) if matches!(e.kind(), std::io::ErrorKind::NotFound | std::io::ErrorKind::ConnectionRefused) => {
+                return Ok(None)
+            }
+            Err(e) => return Err(e).with_context(|| Msg::OpenFailed { path: &path.display() }.to_string()),
+        };
+
+        let (reader, writer) = tokio::io::split(stream);
+        Ok(Some(Self { lines: BufReader::new(reader).lines(), writer }))
+    }
+
+    pub async fn request(&mut self, request: &Request) -> Result<Response> {
+        write(&mut self.writer, request).await?;
+        self.next().await?.ok_or_else(|| anyhow::anyhow!("{}", Msg::IpcClosed))
+    }
+
+    /// Следующее сообщение от сервера (после `Subscribe` — очередное событие).
+    /// `None` — сервер закрыл соединение.
+    pub async fn next(&mut self) -> Result<Option<Response>> {
+        match self.lines.next_line().await? {
+            Some(line) => decode(&line).map(Some),
+            None => Ok(None),
+        }
+    }
+}
+
+async fn write<W: AsyncWrite, T: Serialize>(writer: &mut WriteHalf<W>, message: &T) -> Result<()> {
+    let mut line = encode(message)?;
+    line.push('\n');
+    writer.write_all(line.as_bytes()).await?;
+    writer.flush().await?;
+    Ok(())
+}
+
+fn encode<T: Serialize>(message: &T) -> Result<String> {
+    let mut value = serde_json::to_value(message)?;
+    if let Some(object) = value.as_object_mut() {
+        object.insert("v".to_string(), PROTOCOL_VERSION.into());
+    }
+    Ok(value.to_string())
+}
+
+fn decode<T: DeserializeOwned>(line: &str) -> Result<T> {
+    let mut value: serde_json::Value =
+        serde_json::from_str(line).map_err(|e| anyhow::anyhow!("{}", Msg::IpcBadMessage { error: &e }))?;
+    let version = value.get("v").and_then(|v| v.as_u64());
+    if version != Some(PROTOCOL_VERSION as u64) {
+        let found = version.map_or_else(|| "—".to_string(), |v| v.to_string());
+        anyhow::bail!("{}", Msg::IpcVersionMismatch { found: &found, supported: &PROTOCOL_VERSION });
+    }
+    if let Some(object) = value.as_object_mut() {
+        object.remove("v");
+    }
+    serde_json::from_value(value).map_err(|e| anyhow::anyhow!("{}", Msg::IpcBadMessage { error: &e }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::StateEvent;
+    use crate::volume::Volume;
+
+    fn phone() -> BTDevice {
+        BTDevice { id: "phone-1".to_string(), name: "Pixel".to_string() }
+    }
+
+    fn round_trip<T: Serialize + DeserializeOwned>(message: &T) -> T {
+        decode(&encode(message).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn requests_round_trip() {
+        let requests = [
+            Request::Status,
+            Request::List,
+            Request::Connect { device: "Pixel 8".to_string() },
+            Request::Disconnect { device: None },
+            Request::Disconnect { device: Some("phone-1".to_string()) },
+            Request::SetVolume { device: None, change: VolumeChange::Step(-10) },
+            Request::SetVolume { device: Some("phone-1".to_string()), change: VolumeChange::ToggleMute },
+            Request::Subscribe,
+            Request::Activate,
+            Request::Shutdown,
+        ];
+        for request in requests {
+            assert_eq!(round_trip(&request), request);
+        }
+    }
+
+    #[test]
+    fn responses_round_trip() {
+        let mut state = ReceiverState::default();
+        state.apply("phone-1", StateEvent::ConnectRequested(phone()));
+        state.apply("phone-1", StateEvent::ConnectSucceeded);
+        state.volumes.insert("phone-1".to_string(), Volume { level: 40, muted: true });
+        let responses = [
+            Response::Ok,
+            Response::Error { message: "нет".to_string(), kind: ErrorKind::NotFound },
+            Response::Status(StatusReport::new(&state)),
+            Response::Devices { devices: vec![phone()] },
+            Response::Event(StatusReport::new(&ReceiverState::default())),
+        ];
+        for response in responses {
+            assert_eq!(round_trip(&response), response);
+        }
+    }
+
+    #[test]
+    fn wire_format_is_tagged_and_versioned() {
+        let line = encode(&Request::Connect { device: "Pixel".to_string() }).unwrap();
+        let value: serde_json::Value = serde_json::from_str(&line).unwrap();
+        assert_eq!(value, serde_json::json!({"v": 1, "cmd": "connect", "device": "Pixel"}));
+        assert_eq!(decode::<Request>(r#"{"v":1,"cmd":"status"}"#).unwrap(), Request::Status);
+        // Ответ без `kind` от экземпляра постарше
+        let old = decode::<Response>(r#"{"v":1,"type":"error","message":"занят"}"#).unwrap();
+        assert_eq!(old, Response::Error { message: "занят".to_string(), kind: ErrorKind::Failed });
+    }
+
+    #[test]
+    fn other_versions_are_rejected() {
+        assert!(decode::<Request>(r#"{"v":2,"cmd":"status"}"#).is_err());
+        assert!(decode::<Request>(r#"{"v":"1","cmd":"status"}"#).is_err());
+        assert!(decode::<Request>(r#"{"cmd":"status"}"#).is_err());
+        // Запрос чужой версии не разбирается: неизвестная команда не важна
+        assert!(decode::<Request>(r#"{"v":2,"cmd":"teleport"}"#).is_err());
+        assert!(decode::<Request>(r#"{"v":1,"cmd":"teleport"}"#).is_err());
+        assert!(decode::<Request>("не json").is_err());
+    }
+
+    fn context() -> (IpcContext, mpsc::Receiver<AppCommand>, watch::Sender<ReceiverState>) {
+        let (cmd_tx, cmd_rx) = mpsc::channel(10);
+        let (tx_state, state) = watch::channel(ReceiverState::default());
+        let (_, devices) = watch::channel(vec![phone()]);
+        let (shutdown, _) = mpsc::channel(1);
+        (IpcContext { cmd_tx, state, devices, shutdown }, cmd_rx, tx_state)
+    }
+
+    #[tokio::test]
+    async fn server_answers_over_a_stream() {
+        let (ctx, mut cmd_rx, _tx_state) = context();
+        let (client, server) = tokio::io::duplex(4096);
+        tokio::spawn(serve_client(server, ctx));
+        let (reader, mut writer) = tokio::io::split(client);
+        let mut lines = BufReader::new(reader).lines();
+        let mut ask = async |line: &str| -> Response {
+            writer.write_all(format!("{line}\n").as_bytes()).await.unwrap();
+            decode(&lines.next_line().await.unwrap().unwrap()).unwrap()
+        };
+
+        assert_eq!(ask(r#"{"v":1,"cmd":"list"}"#).await, Response::Devices { devices: vec![phone()] });
+        assert!(matches!(ask(r#"{"v":99,"cmd":"list"}"#).await, Response::Error { .. }));
+        // После ошибки соединение живо
+        assert_eq!(ask(r#"{"v":1,"cmd":"disconnect","device":"Pixel"}"#).await, Response::Ok);
+        assert_eq!(cmd_rx.recv().await, Some(AppCommand::Disconnect("phone-1".to_string())));
+        assert!(matches!(ask(r#"{"v":1,"cmd":"disconnect","device":"Nokia"}"#).await, Response::Error { .. }));
+    }
+
+    #[tokio::test]
+    async fn connect_waits_for_the_outcome() {
+        let (ctx, mut cmd_rx, tx_state) = context();
+        let worker = tokio::spawn(async move {
+            let Some(AppCommand::Connect(id)) = cmd_rx.recv().await else { panic!("ждали Connect") };
+            tx_state.send_modify(|state| {
+                state.apply(&id, StateEvent::ConnectRequested(phone()));
+            });
+            tx_state.send_modify(|state| {
+                state.apply(&id, StateEvent::ConnectFailed("занят".to_string()));
+            });
+            tx_state
+        });
+        let response = handle(Request::Connect { device: "pixel".to_string() }, &ctx).await;
+        assert_eq!(response, Response::Error { message: "занят".to_string(), kind: ErrorKind::Failed });
+        drop(worker.await);
+    }
+
+    #[tokio::test]
+    async fn connect_ignores_an_earlier_failure() {
+        let (ctx, mut cmd_rx, tx_state) = context();
+        tx_state.send_modify(|state| {
+            state.apply("phone-1", StateEvent::ConnectRequested(phone()));
+            state.apply("phone-1", StateEvent::ConnectFailed("занят".to_string()));
+        });
+        let worker = tokio::spawn(async move {
+            let Some(AppCommand::Connect(id)) = cmd_rx.recv().await else { panic!("ждали Connect") };
+            // Постороннее изменение, пока старая ошибка ещё на месте; даём его увидеть
+            tx_state.send_modify(|state| state.scanning = true);
+            tokio::task::yield_now().await;
+            tx_state.send_modify(|state| {
+                state.apply(&id, StateEvent::ConnectRequested(phone()));
+            });
+            tx_state.send_modify(|state| {
+                state.apply(&id, StateEvent::ConnectSucceeded);
+            });
+            tx_state
+        });
+        assert_eq!(handle(Request::Connect { device: "pixel".to_string() }, &ctx).await, Response::Ok);
+        drop(worker.await);
+    }
+
+    #[tokio::test]
+    async fn volume_is_refused_when_it_cannot_change() {
+        let (ctx, mut cmd_rx, tx_state) = context();
+        let change = VolumeChange::Step(10);
+        let request = || Request::SetVolume { device: None, change };
+        assert_eq!(handle(request(), &ctx).await, Response::error(&anyhow::anyhow!("{}", Msg::MediaNoPhone)));
+
+        tx_state.send_modify(|state| {
+            state.apply("phone-1", StateEvent::ConnectRequested(phone()));
+            state.apply("phone-1", StateEvent::ConnectSucceeded);
+        });
+        let unavailable = Response::error(&anyhow::anyhow!("{}", Msg::VolumeUnavailable));
+        assert_eq!(handle(request(), &ctx).await, unavailable, "воркер не применил громкость");
+
+        tx_state.send_modify(|state| {
+            state.volumes.insert("phone-1".to_string(), Default::default());
+        });
+        assert_eq!(handle(request(), &ctx).await, Response::Ok);
+        assert_eq!(cmd_rx.recv().await, Some(AppCommand::SetVolume(Some("phone-1".to_string()), change)));
+    }
+}
@@ -0,0 +1,29 @@
+pub mod backend;
+pub mod cli;
+pub mod config;
+pub mod dsp;
+pub mod dynamics;
+pub mod eq_import;
+pub mod equalizer;
+pub mod fake_backend;
+pub mod fake_media;
+pub mod http_api;
+pub mod i18n;
+pub mod ipc;
+pub mod logging;
+pub mod media;
+pub mod menu;
+pub mod meter;
+pub mod mqtt;
+pub mod priority;
+pub mod reconnect;
+pub mod recording;
+pub mod state;
+pub mod volume;
+pub mod watcher;
+pub mod worker;
+
+#[cfg(windows)]
+pub mod bluetooth_receiver;
+#[cfg(windows)]
+pub mod media_session;
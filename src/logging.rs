@@ -0,0 +1,196 @@
+use crate::config::{LogConfig, LogLevel};
+use crate::i18n::Msg;
+use anyhow::{Context, Result};
+use log::{LevelFilter, Log, Metadata, Record};
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+
+/// Цели, по которым разделён журнал программы.
+pub const TARGETS: &[&str] = &["receiver", "monitor", "updater", "registry", "ui", "config", "ipc", "http", "mqtt", "media", "recording"];
+
+const FILE_NAME: &str = "bt-audio-receiver.log";
+
+static LOGGER: OnceLock<Logger> = OnceLock::new();
+
+/// `%LOCALAPPDATA%\BT-Audio-Receiver\logs`
+pub fn log_dir() -> Option<PathBuf> {
+    dirs::data_local_dir().map(|dir| dir.join("BT-Audio-Receiver").join("logs"))
+}
+
+/// Включает журнал: в файл в `dir` (если задан) и в консоль в отладочной сборке.
+/// Вызывается один раз в начале `main`, настройки потом меняются через `configure`.
+pub fn init(settings: &LogConfig, dir: Option<&Path>) -> Result<()> {
+    let file = match dir {
+        Some(dir) => Some(RotatingFile::open(dir.join(FILE_NAME), settings)?),
+        None => None,
+    };
+    let logger = LOGGER.get_or_init(|| Logger {
+        inner: Mutex::new(Inner { settings: settings.clone(), file }),
+        console: cfg!(debug_assertions),
+    });
+    log::set_logger(logger).map_err(|e| anyhow::anyhow!("{e}"))?;
+    log::set_max_level(max_level(settings));
+
+    // Паника в фоновой задаче иначе пропадает бесследно: консоли в релизе нет
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        log::error!(target: "ui", "{}", Msg::LogPanic { info });
+        default_hook(info);
+    }));
+    Ok(())
+}
+
+/// Применяет новые уровни и размеры файлов без перезапуска.
+pub fn configure(settings: &LogConfig) {
+    let Some(logger) = LOGGER.get() else { return };
+    let mut inner = logger.inner.lock().unwrap();
+    if let Some(file) = &mut inner.file {
+        file.max_size = settings.max_file_size_kb * 1024;
+        file.max_files = settings.max_files;
+    }
+    inner.settings = settings.clone();
+    log::set_max_level(max_level(settings));
+}
+
+struct Logger {
+    inner: Mutex<Inner>,
+    console: bool,
+}
+
+struct Inner {
+    settings: LogConfig,
+    file: Option<RotatingFile>,
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        let settings = &self.inner.lock().unwrap().settings;
+        metadata.level() <= target_level(settings, metadata.target())
+    }
+
+    fn log(&self, record: &Record) {
+        let mut inner = self.inner.lock().unwrap();
+        if record.level() > target_level(&inner.settings, record.target()) {
+            return;
+        }
+
+        let line = format!(
+            "{} {:<5} [{}] {}\n",
+            chrono::Local::now().format("%Y-%m-%d %H:%M:%S%.3f"),
+            record.level(),
+            record.target(),
+            record.args()
+        );
+        if self.console {
+            eprint!("{line}");
+        }
+        if let Some(file) = &mut inner.file {
+            // Писать об ошибке журнала некуда — только в консоль, если она есть
+            if let Err(e) = file.write_line(&line) {
+                if self.console {
+                    eprintln!("{}", Msg::LogWriteFailed { error: &e });
+                }
+            }
+        }
+    }
+
+    fn flush(&self) {
+        if let Some(file) = &mut self.inner.lock().unwrap().file {
+            let _ = file.file.flush();
+        }
+    }
+}
+
+/// Уровень для записи с целью `target`. Сторонние библиотеки пишут в журнал
+/// под путями своих модулей — от них нужны только предупреждения и ошибки.
+fn target_level(settings: &LogConfig, target: &str) -> LevelFilter {
+    if let Some(level) = settings.targets.get(target) {
+        return level_filter(*level);
+    }
+    if TARGETS.contains(&target) {
+        level_filter(settings.level)
+    } else {
+        level_filter(settings.level).min(LevelFilter::Warn)
+    }
+}
+
+fn max_level(settings: &LogConfig) -> LevelFilter {
+    settings.targets.values().copied().chain([settings.level]).map(level_filter).max().unwrap_or(LevelFilter::Off)
+}
+
+fn level_filter(level: LogLevel) -> LevelFilter {
+    match level {
+        LogLevel::Off => LevelFilter::Off,
+        LogLevel::Error => LevelFilter::Error,
+        LogLevel::Warn => LevelFilter::Warn,
+        LogLevel::Info => LevelFilter::Info,
+        LogLevel::Debug => LevelFilter::Debug,
+        LogLevel::Trace => LevelFilter::Trace,
+    }
+}
+
+/// Файл журнала, который по достижении `max_size` переименовывается в `.1`,
+/// прежний `.1` — в `.2` и так далее; всё старше `max_files` удаляется.
+pub struct RotatingFile {
+    path: PathBuf,
+    file: File,
+    size: u64,
+    max_size: u64,
+    max_files: u32,
+}
+
+impl RotatingFile {
+    pub fn open(path: PathBuf, settings: &LogConfig) -> Result<Self> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).with_context(|| Msg::CreateFailed { path: &dir.display() }.to_string())?;
+        }
+        let file = Self::open_append(&path)?;
+        let size = file.metadata().map(|m| m.len()).unwrap_or(0);
+        Ok(Self {
+            path,
+            file,
+            size,
+            max_size: settings.max_file_size_kb * 1024,
+            max_files: settings.max_files,
+        })
+    }
+
+    pub fn write_line(&mut self, line: &str) -> std::io::Result<()> {
+        if self.size > 0 && self.size + line.len() as u64 > self.max_size {
+            self.rotate()?;
+        }
+        self.file.write_all(line.as_bytes())?;
+        self.size += line.len() as u64;
+        Ok(())
+    }
+
+    fn rotate(&mut self) -> std::io::Result<()> {
+        let _ = std::fs::remove_file(self.archived(self.max_files));
+        for i in (1..self.max_files).rev() {
+            let _ = std::fs::rename(self.archived(i), self.archived(i + 1));
+        }
+        if self.max_files > 0 {
+            std::fs::rename(&self.path, self.archived(1))?;
+        } else {
+            std::fs::remove_file(&self.path)?;
+        }
+        self.file = Self::open_append(&self.path).map_err(std::io::Error::other)?;
+        self.size = 0;
+        Ok(())
+    }
+
+    // bt-audio-receiver.log -> bt-audio-receiver.1.log
+    fn archived(&self, index: u32) -> PathBuf {
+        self.path.with_extension(format!("{index}.log"))
+    }
+
+    fn open_append(path: &Path) -> Result<File> {
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .with_context(|| Msg::OpenFailed { path: &path.display() }.to_string())
+    }
+}
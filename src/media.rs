@@ -0,0 +1,462 @@
+use crate::backend::BTDevice;
+use crate::i18n::Msg;
+use crate::state::ReceiverState;
+use crate::volume::VolumeChange;
+use crate::worker::AppCommand;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::future::Future;
+use std::time::Duration;
+use tokio::sync::{mpsc, watch};
+
+/// Предел подсказки значка в трее: `szTip` — 128 символов UTF-16 вместе с завершающим нулём.
+pub const TOOLTIP_MAX: usize = 127;
+
+/// Предел заголовка «что играет» в меню, чтобы меню не растягивалось на пол-экрана.
+pub const MENU_HEADER_MAX: usize = 64;
+
+/// На сколько процентов меняют громкость «Громче» и «Тише».
+pub const VOLUME_STEP: i16 = 10;
+
+/// Как часто спрашивать, что играет: событий о смене трека у сеансов Bluetooth нет.
+const METADATA_POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Команды управления воспроизведением на телефоне.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum MediaAction {
+    PlayPause,
+    Next,
+    Previous,
+    VolumeUp,
+    VolumeDown,
+}
+
+impl MediaAction {
+    pub const ALL: [MediaAction; 5] = [
+        MediaAction::PlayPause,
+        MediaAction::Previous,
+        MediaAction::Next,
+        MediaAction::VolumeDown,
+        MediaAction::VolumeUp,
+    ];
+
+    /// Часть id пункта меню после `media:`.
+    pub fn id(self) -> &'static str {
+        match self {
+            MediaAction::PlayPause => "play_pause",
+            MediaAction::Next => "next",
+            MediaAction::Previous => "previous",
+            MediaAction::VolumeUp => "volume_up",
+            MediaAction::VolumeDown => "volume_down",
+        }
+    }
+
+    pub fn from_id(id: &str) -> Option<MediaAction> {
+        Self::ALL.into_iter().find(|action| action.id() == id)
+    }
+
+    /// Громкость меняет сам приёмник, а не телефон: так она не зависит от того, поддерживает
+    /// ли телефон абсолютную громкость AVRCP, и не трогает общую громкость компьютера.
+    pub fn volume_change(self) -> Option<VolumeChange> {
+        match self {
+            MediaAction::VolumeUp => Some(VolumeChange::Step(VOLUME_STEP)),
+            MediaAction::VolumeDown => Some(VolumeChange::Step(-VOLUME_STEP)),
+            _ => None,
+        }
+    }
+
+    pub fn title(self) -> Msg<'static> {
+        match self {
+            MediaAction::PlayPause => Msg::MenuPlayPause,
+            MediaAction::Next => Msg::MenuNextTrack,
+            MediaAction::Previous => Msg::MenuPreviousTrack,
+            MediaAction::VolumeUp => Msg::MenuVolumeUp,
+            MediaAction::VolumeDown => Msg::MenuVolumeDown,
+        }
+    }
+}
+
+/// Что сейчас играет на телефоне.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NowPlaying {
+    pub title: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub artist: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub album: String,
+}
+
+impl NowPlaying {
+    /// «Исполнитель — Название»; если чего-то нет — то, что есть.
+    pub fn summary(&self) -> String {
+        match (self.artist.is_empty(), self.title.is_empty()) {
+            (false, false) => format!("{} — {}", self.artist, self.title),
+            (true, _) => self.title.clone(),
+            (false, true) => self.artist.clone(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.title.is_empty() && self.artist.is_empty()
+    }
+}
+
+/// Подсказка значка: название программы и, если что-то играет, трек и альбом отдельными строками.
+pub fn tooltip(now_playing: Option<&NowPlaying>) -> String {
+    let mut text = "BT Audio Receiver".to_string();
+    if let Some(now_playing) = now_playing.filter(|n| !n.is_empty()) {
+        text.push('\n');
+        text.push_str(&now_playing.summary());
+        if !now_playing.album.is_empty() {
+            text.push('\n');
+            text.push_str(&now_playing.album);
+        }
+    }
+    truncate(&text, TOOLTIP_MAX)
+}
+
+/// Неактивный пункт в начале меню.
+pub fn menu_header(now_playing: &NowPlaying) -> String {
+    let text = match now_playing.album.is_empty() {
+        true => format!("🎵 {}", now_playing.summary()),
+        false => format!("🎵 {} ({})", now_playing.summary(), now_playing.album),
+    };
+    truncate(&text, MENU_HEADER_MAX)
+}
+
+/// Обрезает `text` до `max` единиц UTF-16 (так считает длину Windows), ставя «…» в конце.
+/// Суррогатные пары не разрываются.
+pub fn truncate(text: &str, max: usize) -> String {
+    if text.encode_utf16().count() <= max {
+        return text.to_string();
+    }
+    let mut result = String::new();
+    let mut length = 0;
+    for c in text.chars() {
+        // Одна единица оставлена под «…»
+        if length + c.len_utf16() > max.saturating_sub(1) {
+            break;
+        }
+        length += c.len_utf16();
+        result.push(c);
+    }
+    let mut result = result.trim_end().to_string();
+    if max > 0 {
+        result.push('…');
+    }
+    result
+}
+
+/// Принадлежит ли сеанс воспроизведения с источником `source` (`SourceAppUserModelId`)
+/// телефону `device`. Сеанс Bluetooth называется по имени телефона, а у программ
+/// на компьютере источник — AUMID с `!` или имя exe: их не берём, даже если имя
+/// телефона встречается в нём («Music» и `Microsoft.ZuneMusic_…!Microsoft.ZuneMusic`).
+/// Имя должно совпасть целиком: сеанс «Pixel 8» не принадлежит телефону «Pixel».
+pub fn is_phone_session(source: &str, device: &BTDevice) -> bool {
+    let source = source.trim().to_lowercase();
+    let name = device.name.trim().to_lowercase();
+    if name.is_empty() || source.contains('!') || source.ends_with(".exe") {
+        return false;
+    }
+    match source.strip_suffix(&name) {
+        Some("") => true,
+        // Перед именем может стоять служебный префикс, но отделённый знаком, а не словом:
+        // «Google Pixel 8» — другой телефон
+        Some(prefix) => prefix.ends_with(|c: char| !c.is_alphanumeric() && !c.is_whitespace()),
+        None => false,
+    }
+}
+
+/// Доступ к управлению воспроизведением. Реализуется `SystemMediaControl`
+/// (Global System Media Transport Controls) и `FakeMediaControl` (для тестов).
+pub trait MediaControl: Send {
+    /// Отдаёт команду телефону `target`. Громкость сюда не приходит (см. `MediaAction::volume_change`).
+    fn send(&mut self, target: &BTDevice, action: MediaAction) -> impl Future<Output = Result<()>> + Send;
+}
+
+/// Откуда брать сведения о текущем треке. Реализуется `SystemMediaControl`
+/// и `FakeMetadataSource` (для тестов).
+pub trait MetadataSource: Send {
+    /// `None` — телефон ничего не сообщает о том, что играет.
+    fn now_playing(&mut self, target: &BTDevice) -> impl Future<Output = Result<Option<NowPlaying>>> + Send;
+}
+
+/// Следит за тем, что играет на последнем подключённом телефоне, и кладёт это
+/// в `ReceiverState::now_playing`. Пока ничего не подключено, там `None`.
+pub async fn metadata_worker(mut source: impl MetadataSource, tx_state: watch::Sender<ReceiverState>) {
+    let mut rx_state = tx_state.subscribe();
+    loop {
+        let target = rx_state.borrow_and_update().connected().last().cloned();
+        let now_playing = match &target {
+            Some(device) => source.now_playing(device).await.unwrap_or_else(|e| {
+                log::debug!(target: "media", "{}", Msg::LogMetadataFailed { error: &e });
+                None
+            }),
+            None => None,
+        };
+        tx_state.send_if_modified(|state| {
+            let changed = state.now_playing != now_playing;
+            state.now_playing = now_playing;
+            changed
+        });
+        // Своё же изменение не повод спрашивать снова
+        rx_state.borrow_and_update();
+
+        tokio::select! {
+            _ = tokio::time::sleep(METADATA_POLL_INTERVAL) => {}
+            changed = rx_state.changed() => {
+                if changed.is_err() {
+                    return;
+                }
+            }
+        }
+    }
+}
+
+/// Принимает команды из трея и горячих клавиш и отдаёт их телефону, подключившемуся
+/// последним. Громкость уходит воркеру (`cmd_tx`): её меняет приёмник.
+pub async fn media_worker(
+    mut control: impl MediaControl,
+    mut actions: mpsc::Receiver<MediaAction>,
+    state: watch::Receiver<ReceiverState>,
+    cmd_tx: mpsc::Sender<AppCommand>,
+) {
+    while let Some(action) = actions.recv().await {
+        let target = state.borrow().connected().last().cloned();
+        let result = match (target, action.volume_change()) {
+            (None, _) => Err(anyhow::anyhow!("{}", Msg::MediaNoPhone)),
+            (Some(device), Some(change)) => cmd_tx
+                .send(AppCommand::SetVolume(Some(device.id), change))
+                .await
+                .map_err(|_| anyhow::anyhow!("{}", Msg::IpcWorkerGone)),
+            (Some(device), None) => control.send(&device, action).await,
+        };
+        if let Err(e) = result {
+            log::warn!(target: "media", "{}", Msg::LogMediaFailed { action: &action.title(), error: &e });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fake_media::{FakeMediaControl, FakeMetadataSource};
+    use crate::state::StateEvent;
+
+    fn device(id: &str, name: &str) -> BTDevice {
+        BTDevice { id: id.to_string(), name: name.to_string() }
+    }
+
+    fn connect(state: &watch::Sender<ReceiverState>, device: BTDevice) {
+        state.send_modify(|state| {
+            let id = device.id.clone();
+            state.apply(&id, StateEvent::ConnectRequested(device));
+            state.apply(&id, StateEvent::ConnectSucceeded);
+        });
+    }
+
+    struct Routing {
+        control: FakeMediaControl,
+        actions: mpsc::Sender<MediaAction>,
+        commands: mpsc::Receiver<AppCommand>,
+        state: watch::Sender<ReceiverState>,
+    }
+
+    fn routing() -> Routing {
+        let control = FakeMediaControl::new();
+        let (actions, rx_actions) = mpsc::channel(10);
+        let (cmd_tx, commands) = mpsc::channel(10);
+        let (state, rx_state) = watch::channel(ReceiverState::default());
+        tokio::spawn(media_worker(control.clone(), rx_actions, rx_state, cmd_tx));
+        Routing { control, actions, commands, state }
+    }
+
+    // Пока воркер не взял следующую команду, предыдущая точно обработана
+    async fn settle(routing: &Routing) {
+        while routing.actions.capacity() < routing.actions.max_capacity() {
+            tokio::task::yield_now().await;
+        }
+        tokio::task::yield_now().await;
+    }
+
+    #[tokio::test]
+    async fn transport_goes_to_the_last_connected_phone() {
+        let mut routing = routing();
+        connect(&routing.state, device("phone-1", "Pixel"));
+        connect(&routing.state, device("phone-2", "iPhone"));
+
+        for action in [MediaAction::PlayPause, MediaAction::Next, MediaAction::Previous] {
+            routing.actions.send(action).await.unwrap();
+        }
+        settle(&routing).await;
+        assert_eq!(
+            routing.control.sent(),
+            [
+                ("phone-2".to_string(), MediaAction::PlayPause),
+                ("phone-2".to_string(), MediaAction::Next),
+                ("phone-2".to_string(), MediaAction::Previous),
+            ]
+        );
+        assert!(routing.commands.try_recv().is_err(), "воркеру приёмника тут нечего делать");
+    }
+
+    #[tokio::test]
+    async fn volume_changes_the_receiver_not_the_phone() {
+        let mut routing = routing();
+        connect(&routing.state, device("phone-1", "Pixel"));
+
+        routing.actions.send(MediaAction::VolumeUp).await.unwrap();
+        routing.actions.send(MediaAction::VolumeDown).await.unwrap();
+        let expected = [VolumeChange::Step(VOLUME_STEP), VolumeChange::Step(-VOLUME_STEP)];
+        for change in expected {
+            assert_eq!(routing.commands.recv().await, Some(AppCommand::SetVolume(Some("phone-1".to_string()), change)));
+        }
+        settle(&routing).await;
+        assert!(routing.control.sent().is_empty());
+    }
+
+    #[tokio::test]
+    async fn nothing_is_sent_without_a_phone() {
+        let mut routing = routing();
+        for action in MediaAction::ALL {
+            routing.actions.send(action).await.unwrap();
+        }
+        settle(&routing).await;
+        assert!(routing.control.sent().is_empty(), "без телефона команды не уходят никому");
+        assert!(routing.commands.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn failed_command_does_not_stop_the_worker() {
+        let routing = routing();
+        connect(&routing.state, device("phone-1", "Pixel"));
+        routing.control.fail_next("нет сеанса");
+
+        routing.actions.send(MediaAction::Next).await.unwrap();
+        routing.actions.send(MediaAction::PlayPause).await.unwrap();
+        settle(&routing).await;
+        assert_eq!(routing.control.sent(), [("phone-1".to_string(), MediaAction::PlayPause)]);
+    }
+
+    #[test]
+    fn actions_round_trip_through_menu_ids() {
+        for action in MediaAction::ALL {
+            assert_eq!(MediaAction::from_id(action.id()), Some(action));
+        }
+        assert_eq!(MediaAction::from_id("stop"), None);
+    }
+
+    fn track(title: &str, artist: &str, album: &str) -> NowPlaying {
+        NowPlaying { title: title.to_string(), artist: artist.to_string(), album: album.to_string() }
+    }
+
+    #[test]
+    fn summary_uses_what_is_known() {
+        assert_eq!(track("Song", "Band", "").summary(), "Band — Song");
+        assert_eq!(track("Song", "", "").summary(), "Song");
+        assert_eq!(track("", "Band", "").summary(), "Band");
+        assert!(track("", "", "Album").is_empty(), "один альбом ничего не говорит");
+    }
+
+    #[test]
+    fn tooltip_and_header() {
+        assert_eq!(tooltip(None), "BT Audio Receiver");
+        assert_eq!(tooltip(Some(&track("", "", "Album"))), "BT Audio Receiver");
+        assert_eq!(tooltip(Some(&track("Song", "Band", "Album"))), "BT Audio Receiver\nBand — Song\nAlbum");
+        assert_eq!(menu_header(&track("Song", "Band", "")), "🎵 Band — Song");
+        assert_eq!(menu_header(&track("Song", "Band", "Album")), "🎵 Band — Song (Album)");
+
+        let long = track(&"а".repeat(300), "Band", "Album");
+        assert_eq!(tooltip(Some(&long)).encode_utf16().count(), TOOLTIP_MAX);
+        assert_eq!(menu_header(&long).encode_utf16().count(), MENU_HEADER_MAX);
+        assert!(menu_header(&long).ends_with('…'));
+    }
+
+    #[test]
+    fn truncate_counts_utf16_and_keeps_pairs() {
+        assert_eq!(truncate("короткий", 8), "короткий");
+        assert_eq!(truncate("длинный текст", 8), "длинный…", "пробел перед «…» убирается");
+        assert_eq!(truncate("abcdef", 1), "…");
+        assert_eq!(truncate("abcdef", 0), "");
+        // 🎵 — две единицы UTF-16: не влезает целиком — не попадает вовсе
+        assert_eq!(truncate("ab🎵cd", 4), "ab…");
+        assert_eq!(truncate("ab🎵cd", 5), "ab🎵…");
+        assert_eq!(truncate("ab🎵", 4), "ab🎵");
+    }
+
+    #[test]
+    fn only_the_phone_session_matches() {
+        let phone = device("phone-1", "Pixel 8");
+        assert!(is_phone_session("Pixel 8", &phone));
+        assert!(is_phone_session("pixel 8", &phone));
+        assert!(!is_phone_session("Spotify.exe", &phone));
+        assert!(!is_phone_session("Microsoft.ZuneMusic_8wekyb3d8bbwe!Microsoft.ZuneMusic", &device("phone-2", "Music")));
+        assert!(!is_phone_session("Pixel 8 Pro", &device("phone-3", "")), "без имени сеанс не найти");
+        assert!(!is_phone_session("iPhone", &phone));
+        assert!(is_phone_session("Bluetooth\\Pixel 8", &phone));
+        // Похожие имена — разные телефоны
+        assert!(!is_phone_session("Pixel 8", &device("phone-4", "Pixel")));
+        assert!(!is_phone_session("Pixel 8 Pro", &phone));
+        assert!(!is_phone_session("Google Pixel 8", &phone));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn metadata_follows_the_last_connected_phone() {
+        let source = FakeMetadataSource::new();
+        source.set("phone-1", Some(track("Song", "Band", "")));
+        source.set("phone-2", Some(track("Other", "", "")));
+        let (state, mut rx_state) = watch::channel(ReceiverState::default());
+        tokio::spawn(metadata_worker(source.clone(), state.clone()));
+
+        let mut now_playing = async |expected: Option<NowPlaying>| {
+            let waited = tokio::time::timeout(Duration::from_secs(10), rx_state.wait_for(|s| s.now_playing == expected)).await;
+            assert!(waited.is_ok(), "не дождались {expected:?}");
+        };
+
+        connect(&state, device("phone-1", "Pixel"));
+        now_playing(Some(track("Song", "Band", ""))).await;
+
+        // Смену трека замечает опрос
+        source.set("phone-1", Some(track("Next", "Band", "")));
+        now_playing(Some(track("Next", "Band", ""))).await;
+
+        connect(&state, device("phone-2", "iPhone"));
+        now_playing(Some(track("Other", "", ""))).await;
+
+        // Телефон замолчал — заголовка нет, а не остаётся прежний
+        source.set("phone-2", None);
+        now_playing(None).await;
+
+        state.send_modify(|state| {
+            state.apply("phone-2", StateEvent::Disconnected);
+        });
+        source.set("phone-2", Some(track("Other", "", "")));
+        now_playing(Some(track("Next", "Band", ""))).await;
+
+        state.send_modify(|state| {
+            state.apply("phone-1", StateEvent::Disconnected);
+        });
+        now_playing(None).await;
+    }
+
+    // Сеансы по имени источника, как их видит `SystemMediaControl`
+    struct Sessions(Vec<(&'static str, NowPlaying)>);
+
+    impl MetadataSource for Sessions {
+        async fn now_playing(&mut self, target: &BTDevice) -> Result<Option<NowPlaying>> {
+            Ok(self.0.iter().find(|(source, _)| is_phone_session(source, target)).map(|(_, track)| track.clone()))
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn metadata_is_not_taken_from_a_similar_name() {
+        let sessions = Sessions(vec![("Pixel 8", track("Song", "", "")), ("Pixel", track("Other", "", ""))]);
+        let (state, mut rx_state) = watch::channel(ReceiverState::default());
+        tokio::spawn(metadata_worker(sessions, state.clone()));
+
+        connect(&state, device("phone-1", "Pixel"));
+        let expected = Some(track("Other", "", ""));
+        let waited = tokio::time::timeout(Duration::from_secs(10), rx_state.wait_for(|s| s.now_playing == expected)).await.is_ok();
+        assert!(waited, "трек взят не у того телефона: {:?}", rx_state.borrow().now_playing);
+    }
+}
@@ -0,0 +1,66 @@
+use crate::backend::BTDevice;
+use crate::i18n::Msg;
+use crate::media::{is_phone_session, MediaAction, MediaControl, MetadataSource, NowPlaying};
+use anyhow::Result;
+use windows::Media::Control::{
+    GlobalSystemMediaTransportControlsSession, GlobalSystemMediaTransportControlsSessionManager,
+};
+
+/// Управление через Global System Media Transport Controls: телефон, подключённый
+/// по A2DP, появляется там отдельным сеансом с пультом AVRCP.
+#[derive(Default)]
+pub struct SystemMediaControl {
+    manager: Option<GlobalSystemMediaTransportControlsSessionManager>,
+}
+
+impl SystemMediaControl {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Только сеанс самого телефона: чужой (плеер на компьютере) управлять не должен
+    async fn session(&mut self, target: &BTDevice) -> Result<GlobalSystemMediaTransportControlsSession> {
+        if self.manager.is_none() {
+            self.manager = Some(GlobalSystemMediaTransportControlsSessionManager::RequestAsync()?.await?);
+        }
+        let manager = self.manager.as_ref().unwrap();
+
+        // У сеанса Bluetooth нет ссылки на устройство, кроме имени источника
+        for session in manager.GetSessions()? {
+            if is_phone_session(&session.SourceAppUserModelId()?.to_string_lossy(), target) {
+                return Ok(session);
+            }
+        }
+        anyhow::bail!("{}", Msg::MediaNoSession)
+    }
+}
+
+impl MediaControl for SystemMediaControl {
+    async fn send(&mut self, target: &BTDevice, action: MediaAction) -> Result<()> {
+        let accepted = match action {
+            // Громкость меняет приёмник, см. `MediaAction::volume_change`
+            MediaAction::VolumeUp | MediaAction::VolumeDown => false,
+            MediaAction::PlayPause => self.session(target).await?.TryTogglePlayPauseAsync()?.await?,
+            MediaAction::Next => self.session(target).await?.TrySkipNextAsync()?.await?,
+            MediaAction::Previous => self.session(target).await?.TrySkipPreviousAsync()?.await?,
+        };
+        if !accepted {
+            anyhow::bail!("{}", Msg::MediaRejected);
+        }
+        Ok(())
+    }
+}
+
+impl MetadataSource for SystemMediaControl {
+    async fn now_playing(&mut self, target: &BTDevice) -> Result<Option<NowPlaying>> {
+        // Нет сеанса — значит, ничего не играет, а не ошибка
+        let Ok(session) = self.session(target).await else { return Ok(None) };
+        let properties = session.TryGetMediaPropertiesAsync()?.await?;
+        let now_playing = NowPlaying {
+            title: properties.Title()?.to_string_lossy(),
+            artist: properties.Artist()?.to_string_lossy(),
+            album: properties.AlbumTitle()?.to_string_lossy(),
+        };
+        Ok(Some(now_playing).filter(|n| !n.is_empty()))
+    }
+}
@@ -0,0 +1,259 @@
+//! Меню трея без трея: что показать и что значит нажатие. Трей только переводит эти
+//! пункты в `tray_icon` и выполняет нажатия, поэтому вся логика проверяется и на Linux.
+
+use crate::backend::{display_names, menu_key, BTDevice};
+use crate::config::{Config, Language};
+use crate::i18n::Msg;
+use crate::media::{menu_header, MediaAction};
+use crate::state::ReceiverState;
+use crate::volume::VolumeChange;
+use crate::worker::AppCommand;
+
+/// Ступени громкости в подменю соединения; точнее — через `volume` в командной строке.
+pub const VOLUME_LEVELS: [u8; 5] = [100, 75, 50, 25, 10];
+
+/// Пункт меню. `id` — то, что вернёт трей при нажатии (см. `MenuClick::parse`).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum MenuEntry {
+    Item { id: String, title: String, enabled: bool },
+    Check { id: String, title: String, checked: bool },
+    Separator,
+    Submenu { title: String, entries: Vec<MenuEntry> },
+}
+
+impl MenuEntry {
+    fn item(id: impl Into<String>, title: impl Into<String>) -> Self {
+        MenuEntry::Item { id: id.into(), title: title.into(), enabled: true }
+    }
+
+    // Надпись, которую нельзя нажать
+    fn label(title: impl Into<String>) -> Self {
+        MenuEntry::Item { id: "status".to_string(), title: title.into(), enabled: false }
+    }
+
+    fn check(id: impl Into<String>, title: impl Into<String>, checked: bool) -> Self {
+        MenuEntry::Check { id: id.into(), title: title.into(), checked }
+    }
+}
+
+/// Меню для текущего состояния. `autostart` — включён ли автозапуск (его знает только
+/// система, поэтому спрашивает трей).
+pub fn build(devices: &[BTDevice], state: &ReceiverState, config: &Config, autostart: bool) -> Vec<MenuEntry> {
+    let mut menu = Vec::new();
+    let names = display_names(devices);
+    let name_of = |device: &BTDevice| {
+        devices
+            .iter()
+            .position(|d| d.id == device.id)
+            .map_or_else(|| device.name.clone(), |i| names[i].clone())
+    };
+
+    if let Some(now_playing) = &state.now_playing {
+        menu.push(MenuEntry::Item { id: "now_playing".to_string(), title: menu_header(now_playing), enabled: false });
+        menu.push(MenuEntry::Separator);
+    }
+
+    if state.scanning {
+        menu.push(MenuEntry::label(Msg::MenuScanning.to_string()));
+        menu.push(MenuEntry::Separator);
+    }
+
+    // По подменю на каждое соединение: у каждого свои «Переподключить» и «Отключить»
+    for (id, session) in &state.sessions {
+        let Some(device) = session.device() else {
+            // Неудачное соединение: причина и способ убрать её из меню
+            if let Some(title) = session.label("") {
+                let entries = vec![MenuEntry::item(format!("dismiss:{}", menu_key(id)), Msg::MenuDismiss.to_string())];
+                menu.push(MenuEntry::Submenu { title, entries });
+            }
+            continue;
+        };
+        let Some(title) = session.label(&name_of(device)) else { continue };
+        let key = device.menu_key();
+
+        let mut entries = vec![
+            MenuEntry::item(format!("reconnect:{key}"), Msg::MenuReconnect.to_string()),
+            MenuEntry::item(format!("disconnect:{key}"), Msg::MenuDisconnect.to_string()),
+        ];
+        if session.is_connected() && !state.volumes.contains_key(&device.id) {
+            entries.push(MenuEntry::Separator);
+            entries.push(MenuEntry::label(Msg::MenuVolumeUnavailable.to_string()));
+        }
+        if let Some(volume) = state.volumes.get(&device.id) {
+            entries.push(MenuEntry::Separator);
+            entries.push(MenuEntry::label(Msg::MenuVolume { volume }.to_string()));
+            for level in VOLUME_LEVELS {
+                entries.push(MenuEntry::check(format!("volume:{key}:{level}"), format!("{level}%"), volume.level == level));
+            }
+            entries.push(MenuEntry::check(format!("mute:{key}"), Msg::MenuMute.to_string(), volume.muted));
+        }
+        if session.is_connected() && !state.outputs.is_empty() {
+            let route = state.route(&device.id);
+            let mut outputs = vec![MenuEntry::check(format!("output:{key}:"), Msg::MenuOutputDefault.to_string(), route.is_none())];
+            for output in &state.outputs {
+                let checked = route.is_some_and(|r| r.id == output.id);
+                outputs.push(MenuEntry::check(format!("output:{key}:{}", output.menu_key()), &output.name, checked));
+            }
+            entries.push(MenuEntry::Separator);
+            entries.push(MenuEntry::Submenu { title: Msg::MenuOutput.to_string(), entries: outputs });
+        }
+        menu.push(MenuEntry::Submenu { title, entries });
+    }
+    if state.active_count() > 1 {
+        menu.push(MenuEntry::item("disconnect_all", Msg::MenuDisconnectAll.to_string()));
+    }
+    let connected = state.connected().next().is_some();
+    if connected {
+        let entries = MediaAction::ALL
+            .iter()
+            .map(|action| MenuEntry::item(format!("media:{}", action.id()), action.title().to_string()))
+            .collect();
+        menu.push(MenuEntry::Submenu { title: Msg::MenuMedia.to_string(), entries });
+    }
+    // Пока идёт запись, пункт остаётся, даже если телефоны отключились: запись продолжится с новыми
+    if state.recording || connected {
+        let title = match state.recording {
+            true => Msg::MenuRecording,
+            false => Msg::MenuRecord,
+        };
+        menu.push(MenuEntry::check("record", title.to_string(), state.recording));
+    }
+    if state.night_mode || connected {
+        menu.push(MenuEntry::check("night_mode", Msg::MenuNightMode.to_string(), state.night_mode));
+    }
+    if !state.sessions.is_empty() {
+        menu.push(MenuEntry::Separator);
+    }
+
+    if devices.is_empty() {
+        menu.push(MenuEntry::Item { id: "none".to_string(), title: Msg::MenuNoDevices.to_string(), enabled: false });
+    } else {
+        for (device, name) in devices.iter().zip(&names) {
+            if !state.get(&device.id).is_active() {
+                menu.push(MenuEntry::item(format!("dev:{}", device.menu_key()), format!("📱 {}", name)));
+            }
+        }
+    }
+
+    if !devices.is_empty() {
+        let entries = devices
+            .iter()
+            .zip(&names)
+            .map(|(device, name)| MenuEntry::check(format!("auto:{}", device.menu_key()), name, config.is_auto_connect(&device.id)))
+            .collect();
+        menu.push(MenuEntry::Separator);
+        menu.push(MenuEntry::Submenu { title: Msg::MenuAutoConnect.to_string(), entries });
+    }
+
+    menu.push(MenuEntry::Separator);
+    menu.push(MenuEntry::item("refresh", Msg::MenuRefresh.to_string()));
+    menu.push(MenuEntry::item("check_update", Msg::MenuCheckUpdate.to_string()));
+    menu.push(MenuEntry::item("open_logs", Msg::MenuOpenLogs.to_string()));
+    menu.push(MenuEntry::check("toggle_autostart", Msg::MenuAutostart.to_string(), autostart));
+
+    // Названия языков не переводятся: их должен узнать тот, кто не читает текущий
+    let languages = [
+        ("lang:auto", Msg::MenuLanguageAuto.to_string(), Language::Auto),
+        ("lang:ru", "Русский".to_string(), Language::Ru),
+        ("lang:en", "English".to_string(), Language::En),
+    ];
+    let entries = languages.into_iter().map(|(id, title, language)| MenuEntry::check(id, title, config.ui.language == language)).collect();
+    menu.push(MenuEntry::Submenu { title: Msg::MenuLanguage.to_string(), entries });
+
+    menu.push(MenuEntry::Separator);
+    menu.push(MenuEntry::item("quit_app", Msg::MenuQuit.to_string()));
+    menu
+}
+
+/// Что сделать по нажатию пункта меню. Устройства здесь уже по полному `BTDevice::id`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum MenuClick {
+    Quit,
+    CheckUpdate,
+    OpenLogs,
+    ToggleAutostart,
+    Language(Language),
+    Media(MediaAction),
+    /// Достаточно передать воркеру.
+    Command(AppCommand),
+    /// Сохранить в настройках и передать воркеру.
+    NightMode(bool),
+    AutoConnect(String, bool),
+    Output(String, Option<String>),
+}
+
+impl MenuClick {
+    /// Разбирает `id` нажатого пункта меню, построенного по тем же `devices`, `state`
+    /// и `config`. `None` — пункт ничего не делает или его устройство уже пропало.
+    pub fn parse(id: &str, devices: &[BTDevice], state: &ReceiverState, config: &Config) -> Option<MenuClick> {
+        // В id пунктов меню лежит `BTDevice::menu_key`, а воркеру нужен полный id
+        let device = |key: &str| {
+            devices
+                .iter()
+                .chain(state.sessions.iter().filter_map(|(_, state)| state.device()))
+                .find(|d| d.menu_key() == key)
+                .map(|d| d.id.clone())
+        };
+
+        let (kind, rest) = id.split_once(':').unwrap_or((id, ""));
+        let click = match kind {
+            "quit_app" => MenuClick::Quit,
+            "check_update" => MenuClick::CheckUpdate,
+            "open_logs" => MenuClick::OpenLogs,
+            "toggle_autostart" => MenuClick::ToggleAutostart,
+            "refresh" => MenuClick::Command(AppCommand::Scan),
+            "disconnect_all" => MenuClick::Command(AppCommand::DisconnectAll),
+            "record" => MenuClick::Command(AppCommand::SetRecording(!state.recording)),
+            "night_mode" => MenuClick::NightMode(!state.night_mode),
+            "lang" => MenuClick::Language(match rest {
+                "ru" => Language::Ru,
+                "en" => Language::En,
+                _ => Language::Auto,
+            }),
+            "media" => MenuClick::Media(MediaAction::from_id(rest)?),
+            "dev" => MenuClick::Command(AppCommand::Connect(device(rest)?)),
+            "reconnect" => MenuClick::Command(AppCommand::Reconnect(device(rest)?)),
+            // У неудачного соединения может не быть устройства, ищем по id соединения
+            "dismiss" => {
+                let (id, _) = state.sessions.iter().find(|(id, _)| menu_key(id) == rest)?;
+                MenuClick::Command(AppCommand::Dismiss(id.clone()))
+            }
+            "disconnect" => MenuClick::Command(AppCommand::Disconnect(device(rest)?)),
+            "mute" => MenuClick::Command(AppCommand::SetVolume(Some(device(rest)?), VolumeChange::ToggleMute)),
+            "volume" => {
+                let (key, level) = rest.split_once(':')?;
+                MenuClick::Command(AppCommand::SetVolume(Some(device(key)?), VolumeChange::Set(level.parse().ok()?)))
+            }
+            "auto" => {
+                let id = device(rest)?;
+                let enabled = !config.is_auto_connect(&id);
+                MenuClick::AutoConnect(id, enabled)
+            }
+            "output" => {
+                let (key, output_key) = rest.split_once(':')?;
+                // Пустой ключ — выход по умолчанию
+                let output = match output_key {
+                    "" => None,
+                    _ => Some(state.outputs.iter().find(|o| o.menu_key() == output_key)?.id.clone()),
+                };
+                MenuClick::Output(device(key)?, output)
+            }
+            _ => return None,
+        };
+        Some(click)
+    }
+
+    /// Меню надо перестроить сразу: отметки в нём ставит трей, а не состояние, и если
+    /// состояние не изменится, они так и останутся неверными.
+    pub fn changes_menu(&self) -> bool {
+        matches!(
+            self,
+            MenuClick::ToggleAutostart
+                | MenuClick::Language(_)
+                | MenuClick::NightMode(_)
+                | MenuClick::AutoConnect(..)
+                | MenuClick::Output(..)
+                | MenuClick::Command(AppCommand::SetRecording(_) | AppCommand::SetVolume(_, VolumeChange::Set(_)))
+        )
+    }
+}
@@ -0,0 +1,393 @@
+//! Индикатор уровня в значке трея: уровни принятого звука, сглаживание для глаза и
+//! отрисовка значка. От Windows не зависит — значок рисуется в RGBA-буфер.
+
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::Notify;
+
+/// Ниже этого уровня считаем, что звука нет, дБFS. Заодно нижний край шкалы.
+const FLOOR_DB: f32 = -60.0;
+
+/// Тишина должна продлиться, прежде чем значок покажет её: паузы между словами не в счёт.
+const SILENCE_AFTER: Duration = Duration::from_secs(2);
+
+/// Столько шкалы в секунду уровень теряет при спаде (20 дБ/с).
+const FALL_PER_SEC: f32 = 20.0 / -FLOOR_DB;
+
+/// Сколько держится отметка пика.
+const PEAK_HOLD: Duration = Duration::from_secs(1);
+
+/// Ступеней у столбика: значок перерисовывается, только когда уровень перешёл на другую.
+pub const BAR_STEPS: u8 = 8;
+
+/// Уровни за промежуток времени: 0.0..=1.0, где 1.0 — 0 дБFS.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Levels {
+    pub peak: f32,
+    pub rms: f32,
+}
+
+/// Копит уровни принятого звука между опросами. Клоны общие: бэкенд пишет из потока
+/// графа, трей забирает по таймеру, а в тишине таймер не заводит и ждёт `sound`.
+#[derive(Clone, Debug, Default)]
+pub struct LevelMeter {
+    window: Arc<Mutex<Window>>,
+    sound: Arc<Notify>,
+}
+
+#[derive(Debug, Default)]
+struct Window {
+    peak: f32,
+    squares: f64,
+    count: u64,
+    // Ждут звука: первый не тихий кусок разбудит `sound`
+    armed: bool,
+}
+
+impl LevelMeter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn feed(&self, samples: &[f32]) {
+        let peak = samples.iter().fold(0f32, |peak, s| peak.max(s.abs()));
+        let mut window = self.window.lock().unwrap();
+        if window.armed {
+            // Тишину (всё тише `FLOOR_DB`), пока ждём звука, не копим: иначе она разбавит
+            // первый замер
+            if scale(peak) == 0.0 {
+                return;
+            }
+            window.armed = false;
+            self.sound.notify_one();
+        }
+        let squares: f64 = samples.iter().map(|s| f64::from(*s).powi(2)).sum();
+        window.peak = window.peak.max(peak);
+        window.squares += squares;
+        window.count += samples.len() as u64;
+    }
+
+    /// Уровни с прошлого вызова.
+    pub fn take(&self) -> Levels {
+        let window = std::mem::take(&mut *self.window.lock().unwrap());
+        let rms = match window.count {
+            0 => 0.0,
+            count => (window.squares / count as f64).sqrt() as f32,
+        };
+        Levels { peak: window.peak.min(1.0), rms: rms.min(1.0) }
+    }
+
+    /// Просит разбудить `sound`, когда придёт звук громче `FLOOR_DB`. Пока его нет, опрашивать
+    /// индикатор незачем.
+    pub fn wake_on_sound(&self) {
+        let mut window = self.window.lock().unwrap();
+        if !window.armed {
+            *window = Window { armed: true, ..Window::default() };
+        }
+    }
+
+    /// Ждёт звука после `wake_on_sound`.
+    pub async fn sound(&self) {
+        self.sound.notified().await;
+    }
+}
+
+/// Что показывает значок.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IconState {
+    /// Ничего не подключено — обычный значок.
+    Idle,
+    /// Телефон подключён, но звука нет.
+    Silent,
+    /// Уровень и пик в ступенях, 0..=`BAR_STEPS`.
+    Level { level: u8, peak: u8 },
+}
+
+/// Превращает уровни в то, что видно на значке: столбик поднимается сразу, а опускается
+/// плавно, пик держится секунду, тишина показывается, только если длится.
+#[derive(Clone, Debug, Default)]
+pub struct MeterDisplay {
+    level: f32,
+    peak: f32,
+    peak_age: Duration,
+    quiet: Duration,
+}
+
+impl MeterDisplay {
+    /// `elapsed` — сколько прошло с прошлого вызова.
+    pub fn update(&mut self, levels: Levels, elapsed: Duration, connected: bool) -> IconState {
+        if !connected {
+            *self = Self::default();
+            return IconState::Idle;
+        }
+
+        let level = scale(levels.rms);
+        let fallen = (self.level - FALL_PER_SEC * elapsed.as_secs_f32()).max(0.0);
+        self.level = level.max(fallen);
+
+        let peak = scale(levels.peak);
+        self.peak_age += elapsed;
+        if peak >= self.peak || self.peak_age > PEAK_HOLD {
+            self.peak = peak.max(self.level);
+            self.peak_age = Duration::ZERO;
+        }
+
+        self.quiet = match peak > 0.0 {
+            true => Duration::ZERO,
+            false => self.quiet + elapsed,
+        };
+        if self.quiet >= SILENCE_AFTER {
+            return IconState::Silent;
+        }
+        IconState::Level { level: steps(self.level), peak: steps(self.peak) }
+    }
+}
+
+// Линейный уровень в долю шкалы от `FLOOR_DB` до 0 дБFS
+fn scale(linear: f32) -> f32 {
+    if linear <= 0.0 {
+        return 0.0;
+    }
+    ((20.0 * linear.log10() - FLOOR_DB) / -FLOOR_DB).clamp(0.0, 1.0)
+}
+
+fn steps(fraction: f32) -> u8 {
+    (fraction * f32::from(BAR_STEPS)).round() as u8
+}
+
+const GREEN: [u8; 4] = [0x3c, 0xc8, 0x50, 0xff];
+const YELLOW: [u8; 4] = [0xf0, 0xc8, 0x28, 0xff];
+const RED: [u8; 4] = [0xe6, 0x3c, 0x32, 0xff];
+const PEAK: [u8; 4] = [0xff, 0xff, 0xff, 0xff];
+const TRACK: [u8; 4] = [0x20, 0x20, 0x20, 0xb4];
+const SILENT: [u8; 4] = [0x80, 0x80, 0x80, 0xff];
+
+/// Значок для состояния `state` поверх `base` (RGBA, `width`×`height`). Столбик стоит у
+/// правого края на всю высоту; в тишине значок становится серым, а столбик — пустым.
+pub fn render(base: &[u8], width: u32, height: u32, state: IconState) -> Vec<u8> {
+    let (width, height) = (width as usize, height as usize);
+    let mut image = base.to_vec();
+    let mut canvas = Canvas { pixels: &mut image, width, height };
+    let left = width - (width / 4).max(2).min(width);
+    let segment = height as f32 / f32::from(BAR_STEPS);
+    // Ступень `step` (с 1) снизу занимает строки от `top(step)` до `top(step - 1)`
+    let top = |step: u8| height - (segment * f32::from(step)).round() as usize;
+
+    match state {
+        IconState::Idle => {}
+        IconState::Silent => {
+            canvas.grayscale();
+            canvas.fill(left, 0, width, height, TRACK);
+            let line = (segment / 2.0).ceil() as usize;
+            canvas.fill(left, height.saturating_sub(line), width, height, SILENT);
+        }
+        IconState::Level { level, peak } => {
+            canvas.fill(left, 0, width, height, TRACK);
+            for step in 1..=level.min(BAR_STEPS) {
+                let color = match step {
+                    s if s * 4 <= BAR_STEPS * 3 => GREEN,
+                    s if s < BAR_STEPS => YELLOW,
+                    _ => RED,
+                };
+                canvas.fill(left, top(step), width, top(step - 1), color);
+            }
+            if peak > level && peak <= BAR_STEPS {
+                let y = top(peak);
+                let thickness = (segment / 4.0).ceil() as usize;
+                canvas.fill(left, y, width, y + thickness, PEAK);
+            }
+        }
+    }
+    image
+}
+
+struct Canvas<'a> {
+    pixels: &'a mut [u8],
+    width: usize,
+    height: usize,
+}
+
+impl Canvas<'_> {
+    // Прямоугольник [x0, x1) × [y0, y1) цветом `color` с учётом его прозрачности
+    fn fill(&mut self, x0: usize, y0: usize, x1: usize, y1: usize, color: [u8; 4]) {
+        let alpha = u32::from(color[3]);
+        for y in y0..y1.min(self.height) {
+            for x in x0..x1.min(self.width) {
+                let i = (y * self.width + x) * 4;
+                let Some(pixel) = self.pixels.get_mut(i..i + 4) else { return };
+                for c in 0..3 {
+                    pixel[c] = ((u32::from(color[c]) * alpha + u32::from(pixel[c]) * (255 - alpha)) / 255) as u8;
+                }
+                pixel[3] = pixel[3].max(color[3]);
+            }
+        }
+    }
+
+    fn grayscale(&mut self) {
+        for pixel in self.pixels.chunks_exact_mut(4) {
+            let luma = (u32::from(pixel[0]) * 299 + u32::from(pixel[1]) * 587 + u32::from(pixel[2]) * 114) / 1000;
+            pixel[..3].fill(luma as u8);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SIZE: u32 = 16;
+    const BLUE: [u8; 4] = [0, 0, 0xff, 0xff];
+    // Столбик занимает правую четверть, по две строки на ступень
+    const BAR: usize = 12;
+
+    fn base(color: [u8; 4]) -> Vec<u8> {
+        color.repeat((SIZE * SIZE) as usize)
+    }
+
+    fn pixel(image: &[u8], x: usize, y: usize) -> [u8; 4] {
+        let i = (y * SIZE as usize + x) * 4;
+        image[i..i + 4].try_into().unwrap()
+    }
+
+    // Цвет столбика в строке `y` (во всех его столбцах одинаковый)
+    fn bar_row(image: &[u8], y: usize) -> [u8; 4] {
+        let color = pixel(image, BAR, y);
+        assert!((BAR..SIZE as usize).all(|x| pixel(image, x, y) == color), "строка {y} столбика неоднородна");
+        color
+    }
+
+    fn untouched_left(image: &[u8], color: [u8; 4]) -> bool {
+        (0..SIZE as usize).all(|y| (0..BAR).all(|x| pixel(image, x, y) == color))
+    }
+
+    #[test]
+    fn idle_keeps_base_icon() {
+        let image = render(&base(BLUE), SIZE, SIZE, IconState::Idle);
+        assert_eq!(image, base(BLUE));
+    }
+
+    #[test]
+    fn full_level_is_green_yellow_red() {
+        let image = render(&base(BLUE), SIZE, SIZE, IconState::Level { level: BAR_STEPS, peak: BAR_STEPS });
+        assert_eq!(image.len(), (SIZE * SIZE * 4) as usize);
+        assert!(untouched_left(&image, BLUE), "значок слева от столбика не меняется");
+        for y in 0..2 {
+            assert_eq!(bar_row(&image, y), RED, "верхняя ступень красная");
+        }
+        for y in 2..4 {
+            assert_eq!(bar_row(&image, y), YELLOW);
+        }
+        for y in 4..16 {
+            assert_eq!(bar_row(&image, y), GREEN);
+        }
+    }
+
+    #[test]
+    fn empty_level_shows_translucent_track() {
+        let image = render(&base(BLUE), SIZE, SIZE, IconState::Level { level: 0, peak: 0 });
+        // 0x20 с непрозрачностью 0xb4 поверх синего
+        let track = [0x20 * 0xb4 / 0xff, 0x20 * 0xb4 / 0xff, (0x20 * 0xb4 + 0xff * (0xff - 0xb4)) / 0xff, 0xff].map(|c| c as u8);
+        assert_eq!(track, [22, 22, 97, 255]);
+        assert!((0..16).all(|y| bar_row(&image, y) == track));
+
+        // Поверх прозрачного значка дорожка остаётся полупрозрачной
+        let image = render(&base([0; 4]), SIZE, SIZE, IconState::Level { level: 0, peak: 0 });
+        assert_eq!(bar_row(&image, 0), [22, 22, 22, 0xb4]);
+        assert!(untouched_left(&image, [0; 4]));
+    }
+
+    #[test]
+    fn peak_mark_is_drawn_above_level() {
+        let image = render(&base(BLUE), SIZE, SIZE, IconState::Level { level: 2, peak: 6 });
+        // Уровень — две нижние ступени, пик — строка на границе шестой
+        for y in 12..16 {
+            assert_eq!(bar_row(&image, y), GREEN);
+        }
+        assert_eq!(bar_row(&image, 4), PEAK);
+        assert_eq!(bar_row(&image, 5), bar_row(&image, 0), "под пиком дорожка");
+        assert_ne!(bar_row(&image, 5), PEAK);
+
+        // Пик не выше уровня не рисуется
+        let image = render(&base(BLUE), SIZE, SIZE, IconState::Level { level: 4, peak: 4 });
+        assert!((0..16).all(|y| bar_row(&image, y) != PEAK));
+    }
+
+    #[test]
+    fn silent_grays_out_icon() {
+        let image = render(&base(BLUE), SIZE, SIZE, IconState::Silent);
+        // Яркость синего: 255 · 0.114
+        assert!(untouched_left(&image, [29, 29, 29, 0xff]));
+        assert_eq!(bar_row(&image, 15), SILENT, "внизу — серая черта");
+        assert_ne!(bar_row(&image, 14), SILENT);
+    }
+
+    #[test]
+    fn odd_sizes_do_not_panic() {
+        for (width, height) in [(1, 1), (3, 5), (5, 3), (32, 32)] {
+            let base = vec![0xff; (width * height * 4) as usize];
+            for state in [IconState::Silent, IconState::Level { level: BAR_STEPS + 3, peak: BAR_STEPS + 5 }] {
+                assert_eq!(render(&base, width, height, state).len(), base.len());
+            }
+        }
+    }
+
+    #[test]
+    fn meter_reports_peak_and_rms() {
+        let meter = LevelMeter::new();
+        meter.feed(&[0.5, -0.5, 0.5, -0.5]);
+        meter.feed(&[0.0; 4]);
+        let levels = meter.take();
+        assert_eq!(levels.peak, 0.5);
+        assert!((levels.rms - 0.5 / std::f32::consts::SQRT_2).abs() < 1e-6);
+        assert_eq!(meter.take(), Levels::default(), "окно обнуляется");
+    }
+
+    #[test]
+    fn display_rises_at_once_and_falls_slowly() {
+        let mut display = MeterDisplay::default();
+        let tick = Duration::from_millis(100);
+        let full = Levels { peak: 1.0, rms: 1.0 };
+        assert_eq!(display.update(full, tick, true), IconState::Level { level: 8, peak: 8 });
+        // Спад 20 дБ/с из 60 — за 0,1 с треть ступени, за 0,3 с — одна
+        let quiet = Levels { peak: 0.01, rms: 0.01 };
+        assert_eq!(display.update(quiet, tick, true), IconState::Level { level: 8, peak: 8 });
+        assert_eq!(display.update(quiet, 2 * tick, true), IconState::Level { level: 7, peak: 8 }, "пик держится");
+        assert_eq!(display.update(quiet, 8 * tick, true), IconState::Level { level: 5, peak: 5 }, "через секунду пик опускается");
+        assert_eq!(display.update(full, tick, false), IconState::Idle);
+    }
+
+    #[test]
+    fn display_goes_silent_after_a_pause() {
+        let mut display = MeterDisplay::default();
+        let tick = Duration::from_millis(500);
+        display.update(Levels { peak: 0.5, rms: 0.3 }, tick, true);
+        for _ in 0..3 {
+            assert!(matches!(display.update(Levels::default(), tick, true), IconState::Level { .. }), "паузы между словами не в счёт");
+        }
+        assert_eq!(display.update(Levels::default(), tick, true), IconState::Silent);
+        assert!(matches!(display.update(Levels { peak: 0.5, rms: 0.3 }, tick, true), IconState::Level { .. }));
+    }
+
+    #[tokio::test]
+    async fn armed_meter_wakes_on_sound_only() {
+        let meter = LevelMeter::new();
+        meter.feed(&[0.5; 4]);
+        meter.wake_on_sound();
+        assert_eq!(meter.take(), Levels::default(), "накопленное до ожидания выброшено");
+
+        meter.wake_on_sound();
+        // Тише -60 дБFS — всё ещё тишина: не будит и не копится
+        meter.feed(&[0.0005; 480]);
+        let woken = tokio::time::timeout(Duration::from_millis(20), meter.sound()).await;
+        assert!(woken.is_err(), "тишина не будит");
+
+        meter.feed(&[0.25; 4]);
+        tokio::time::timeout(Duration::from_secs(1), meter.sound()).await.expect("звук будит");
+        assert_eq!(meter.take(), Levels { peak: 0.25, rms: 0.25 }, "тишина до звука не разбавила замер");
+
+        // Разбудив, больше не будит, пока не попросят снова
+        meter.feed(&[0.25; 4]);
+        let woken = tokio::time::timeout(Duration::from_millis(20), meter.sound()).await;
+        assert!(woken.is_err());
+    }
+}
@@ -0,0 +1,311 @@
+//! Публикация состояния в MQTT и управление через него, с обнаружением в Home Assistant.
+//!
+//! Топики (все относительно `mqtt.base_topic`):
+//!
+//! ```text
+//! availability   online / offline (завещание клиента)
+//! state          idle, connecting, connected, reconnecting или failed
+//! device         имена подключённых устройств через запятую
+//! devices        JSON-список доступных устройств
+//! status         StatusReport целиком
+//! connect        ← id или имя устройства (одинаковые имена — как в меню)
+//! disconnect     ← id или имя устройства; пустое сообщение — отключить все
+//! ```
+
+use crate::backend::{display_names, BTDevice};
+use crate::config::MqttConfig;
+use crate::i18n::Msg;
+use crate::ipc::{self, IpcContext, Request, Response};
+use crate::state::{ConnectionState, ReceiverState, StatusReport};
+use anyhow::Result;
+use rumqttc::{AsyncClient, Event, LastWill, MqttOptions, Packet, QoS};
+use serde_json::json;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Notify;
+
+/// Пауза перед повторным подключением к брокеру.
+const RETRY_DELAY: Duration = Duration::from_secs(5);
+
+#[derive(Clone)]
+struct Topics {
+    base: String,
+    discovery_prefix: String,
+    /// Идентификатор устройства в Home Assistant: `client_id` без недопустимых символов.
+    node_id: String,
+}
+
+impl Topics {
+    fn new(config: &MqttConfig) -> Self {
+        let node_id = config
+            .client_id
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() || c == '_' || c == '-' { c } else { '_' })
+            .collect();
+        Self {
+            base: config.base_topic.trim_end_matches('/').to_string(),
+            discovery_prefix: config.discovery_prefix.trim_end_matches('/').to_string(),
+            node_id,
+        }
+    }
+
+    fn topic(&self, name: &str) -> String {
+        format!("{}/{}", self.base, name)
+    }
+
+    fn discovery(&self, component: &str, object_id: &str) -> String {
+        format!("{}/{}/{}/{}/config", self.discovery_prefix, component, self.node_id, object_id)
+    }
+}
+
+/// Держит соединение с брокером, пока жив процесс; после обрыва переподключается.
+pub async fn run(config: MqttConfig, ctx: IpcContext) -> Result<()> {
+    let topics = Topics::new(&config);
+
+    let mut options = MqttOptions::new(&config.client_id, &config.host, config.port);
+    options.set_keep_alive(Duration::from_secs(30));
+    options.set_last_will(LastWill::new(topics.topic("availability"), "offline", QoS::AtLeastOnce, true));
+    if let Some(username) = &config.username {
+        options.set_credentials(username, config.password.clone().unwrap_or_default());
+    }
+    let (client, mut eventloop) = AsyncClient::new(options, 32);
+
+    // Публикуем из отдельной задачи: `publish` ждёт, пока цикл событий разберёт очередь,
+    // и внутри этого цикла он бы заблокировался
+    let connected = Arc::new(Notify::new());
+    tokio::spawn(publish(client, topics.clone(), ctx.clone(), connected.clone()));
+
+    loop {
+        match eventloop.poll().await {
+            Ok(Event::Incoming(Packet::ConnAck(_))) => {
+                log::info!(target: "mqtt", "{}", Msg::LogMqttConnected { host: &config.host });
+                connected.notify_one();
+            }
+            Ok(Event::Incoming(Packet::Publish(message))) => {
+                let Some(command) = message.topic.strip_prefix(&topics.base).map(|t| t.trim_start_matches('/')) else {
+                    continue;
+                };
+                let payload = String::from_utf8_lossy(&message.payload).trim().to_string();
+                let request = match command {
+                    "connect" if !payload.is_empty() => Request::Connect { device: device_query(&ctx.devices.borrow(), payload) },
+                    "disconnect" => Request::Disconnect { device: Some(payload).filter(|p| !p.is_empty()) },
+                    _ => continue,
+                };
+                let (ctx, command) = (ctx.clone(), command.to_string());
+                // `connect` отвечает только после подключения — не держим из-за него цикл событий
+                tokio::spawn(async move {
+                    if let Response::Error { message, .. } = ipc::handle(request, &ctx).await {
+                        log::warn!(target: "mqtt", "{}", Msg::LogMqttCommandFailed { command: &command, error: &message });
+                    }
+                });
+            }
+            Ok(_) => {}
+            Err(e) => {
+                log::warn!(target: "mqtt", "{}", Msg::LogMqttError { error: &e });
+                tokio::time::sleep(RETRY_DELAY).await;
+            }
+        }
+    }
+}
+
+// Ошибка публикации не повод замолкать: следующее изменение состояния опубликует всё заново
+async fn publish(client: AsyncClient, topics: Topics, mut ctx: IpcContext, connected: Arc<Notify>) {
+    loop {
+        let result: Result<()> = tokio::select! {
+            // После каждого (пере)подключения: брокер мог потерять подписки и сохранённые сообщения
+            _ = connected.notified() => publish_all(&client, &topics, &mut ctx).await,
+            changed = ctx.state.changed() => {
+                // Воркер завершился — публиковать больше нечего
+                if changed.is_err() {
+                    return;
+                }
+                let state = ctx.state.borrow_and_update().clone();
+                publish_state(&client, &topics, &state).await
+            }
+            changed = ctx.devices.changed() => {
+                if changed.is_err() {
+                    return;
+                }
+                let devices = ctx.devices.borrow_and_update().clone();
+                publish_devices(&client, &topics, &devices).await
+            }
+        };
+        if let Err(e) = result {
+            log::warn!(target: "mqtt", "{}", Msg::LogMqttError { error: &e });
+        }
+    }
+}
+
+async fn publish_all(client: &AsyncClient, topics: &Topics, ctx: &mut IpcContext) -> Result<()> {
+    client.subscribe(topics.topic("connect"), QoS::AtLeastOnce).await?;
+    client.subscribe(topics.topic("disconnect"), QoS::AtLeastOnce).await?;
+    retain(client, topics.topic("availability"), "online".to_string()).await?;
+    if !topics.discovery_prefix.is_empty() {
+        for (topic, payload) in discovery(topics) {
+            retain(client, topic, payload).await?;
+        }
+    }
+    // Выбор устройства публикуется вместе со списком
+    let devices = ctx.devices.borrow_and_update().clone();
+    publish_devices(client, topics, &devices).await?;
+    let state = ctx.state.borrow_and_update().clone();
+    publish_state(client, topics, &state).await
+}
+
+async fn publish_state(client: &AsyncClient, topics: &Topics, state: &ReceiverState) -> Result<()> {
+    let names: Vec<&str> = state.connected().map(|d| d.name.as_str()).collect();
+    retain(client, topics.topic("state"), summary(state).to_string()).await?;
+    retain(client, topics.topic("device"), names.join(", ")).await?;
+    retain(client, topics.topic("status"), serde_json::to_string(&StatusReport::new(state))?).await
+}
+
+async fn publish_devices(client: &AsyncClient, topics: &Topics, devices: &[BTDevice]) -> Result<()> {
+    retain(client, topics.topic("devices"), serde_json::to_string(devices)?).await?;
+    // Варианты выбора в Home Assistant — это список устройств
+    if !topics.discovery_prefix.is_empty() {
+        let (topic, payload) = select_discovery(topics, devices);
+        retain(client, topic, payload).await?;
+    }
+    Ok(())
+}
+
+async fn retain(client: &AsyncClient, topic: String, payload: String) -> Result<()> {
+    client.publish(topic, QoS::AtLeastOnce, true, payload).await?;
+    Ok(())
+}
+
+/// Одно состояние на весь приёмник: самое «успешное» из состояний устройств.
+fn summary(state: &ReceiverState) -> &'static str {
+    let rank = |s: &ConnectionState| match s {
+        ConnectionState::Connected { .. } => 4,
+        ConnectionState::Connecting { .. } => 3,
+        ConnectionState::Reconnecting { .. } => 2,
+        ConnectionState::Failed { .. } => 1,
+        ConnectionState::Idle => 0,
+    };
+    state.sessions.iter().map(|(_, s)| s).max_by_key(|s| rank(s)).map_or("idle", |s| s.kind())
+}
+
+fn discovery(topics: &Topics) -> Vec<(String, String)> {
+    vec![
+        (
+            topics.discovery("sensor", "state"),
+            entity(topics, Msg::MqttEntityState, "state", json!({
+                "state_topic": topics.topic("state"),
+                "json_attributes_topic": topics.topic("status"),
+                "icon": "mdi:bluetooth-audio",
+            })),
+        ),
+        (
+            topics.discovery("binary_sensor", "connected"),
+            entity(topics, Msg::MqttEntityConnected, "connected", json!({
+                "state_topic": topics.topic("state"),
+                "value_template": "{{ 'ON' if value == 'connected' else 'OFF' }}",
+                "device_class": "connectivity",
+            })),
+        ),
+        (
+            topics.discovery("sensor", "device"),
+            entity(topics, Msg::MqttEntityDevice, "device", json!({
+                "state_topic": topics.topic("device"),
+                "icon": "mdi:cellphone-sound",
+            })),
+        ),
+        (
+            topics.discovery("sensor", "devices"),
+            entity(topics, Msg::MqttEntityAvailable, "devices", json!({
+                "state_topic": topics.topic("devices"),
+                "value_template": "{{ value_json | count }}",
+                "json_attributes_topic": topics.topic("devices"),
+                "json_attributes_template": "{{ {'devices': value_json | map(attribute='name') | list} | tojson }}",
+                "icon": "mdi:cellphone-link",
+            })),
+        ),
+        (
+            topics.discovery("button", "disconnect"),
+            entity(topics, Msg::MqttEntityDisconnect, "disconnect", json!({
+                "command_topic": topics.topic("disconnect"),
+                "payload_press": "",
+                "icon": "mdi:link-off",
+            })),
+        ),
+    ]
+}
+
+// Home Assistant не принимает выбор без вариантов, поэтому без устройств сущность убираем
+fn select_discovery(topics: &Topics, devices: &[BTDevice]) -> (String, String) {
+    let topic = topics.discovery("select", "connect");
+    if devices.is_empty() {
+        return (topic, String::new());
+    }
+    // Имена как в меню: у одинаковых телефонов варианты должны различаться
+    let options = display_names(devices);
+    let payload = entity(topics, Msg::MqttEntityConnect, "connect", json!({
+        "command_topic": topics.topic("connect"),
+        "options": options,
+        "optimistic": true,
+        "icon": "mdi:bluetooth-connect",
+    }));
+    (topic, payload)
+}
+
+// Выбор в Home Assistant присылает вариант из `display_names`, а не id
+fn device_query(devices: &[BTDevice], payload: String) -> String {
+    match display_names(devices).iter().position(|name| *name == payload) {
+        Some(i) => devices[i].id.clone(),
+        None => payload,
+    }
+}
+
+/// Общая часть описания сущности: имя, id и принадлежность устройству «приёмник».
+fn entity(topics: &Topics, name: Msg, object_id: &str, fields: serde_json::Value) -> String {
+    let mut payload = json!({
+        "name": name.to_string(),
+        "unique_id": format!("{}_{}", topics.node_id, object_id),
+        "availability_topic": topics.topic("availability"),
+        "device": {
+            "identifiers": [topics.node_id],
+            "name": "BT Audio Receiver",
+            "sw_version": env!("CARGO_PKG_VERSION"),
+        },
+    });
+    if let (Some(payload), serde_json::Value::Object(fields)) = (payload.as_object_mut(), fields) {
+        payload.extend(fields);
+    }
+    payload.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn device(id: &str, name: &str) -> BTDevice {
+        BTDevice { id: id.to_string(), name: name.to_string() }
+    }
+
+    fn topics() -> Topics {
+        Topics::new(&MqttConfig { client_id: "bt audio".to_string(), ..MqttConfig::default() })
+    }
+
+    #[test]
+    fn select_options_tell_same_names_apart() {
+        let devices = [device("a", "iPhone"), device("b", "iPhone"), device("c", "Pixel")];
+        let (_, payload) = select_discovery(&topics(), &devices);
+        let payload: serde_json::Value = serde_json::from_str(&payload).unwrap();
+        let options: Vec<String> = serde_json::from_value(payload["options"].clone()).unwrap();
+        assert_eq!(options, display_names(&devices));
+
+        // Выбранный вариант ведёт к своему телефону
+        for (option, device) in options.iter().zip(&devices) {
+            assert_eq!(device_query(&devices, option.clone()), device.id);
+        }
+        assert_eq!(device_query(&devices, "iphone".to_string()), "iphone", "остальное разбирает resolve_device");
+    }
+
+    #[test]
+    fn select_is_removed_without_devices() {
+        let (topic, payload) = select_discovery(&topics(), &[]);
+        assert_eq!(topic, "homeassistant/select/bt_audio/connect/config");
+        assert!(payload.is_empty());
+    }
+}
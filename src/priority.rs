@@ -0,0 +1,112 @@
+use crate::backend::BTDevice;
+use std::collections::HashSet;
+
+/// Порядок, в котором приёмник сам выбирает устройство.
+///
+/// Сначала идут устройства из списка приоритетов (в его порядке), затем
+/// остальные устройства с автоподключением — последнее подключённое первым.
+/// Устройства без приоритета и без автоподключения сами не выбираются.
+#[derive(Clone, Copy, Debug)]
+pub struct Priority<'a> {
+    pub order: &'a [String],
+    pub auto_connect: &'a HashSet<String>,
+    pub last_device: Option<&'a str>,
+}
+
+impl Priority<'_> {
+    /// Ранг устройства: меньше — важнее. `None` — устройство не выбирается автоматически.
+    pub fn rank(&self, id: &str) -> Option<usize> {
+        if let Some(i) = self.order.iter().position(|p| p == id) {
+            return Some(i);
+        }
+        if self.auto_connect.contains(id) {
+            let bonus = usize::from(self.last_device != Some(id));
+            return Some(self.order.len() + bonus);
+        }
+        None
+    }
+
+    /// Доступные устройства, к которым можно подключаться, от самого важного.
+    pub fn candidates(&self, available: &[BTDevice]) -> Vec<BTDevice> {
+        let mut ranked: Vec<(usize, &BTDevice)> = available
+            .iter()
+            .filter_map(|d| self.rank(&d.id).map(|r| (r, d)))
+            .collect();
+        // sort_by_key стабильна: при равном ранге сохраняется порядок списка устройств
+        ranked.sort_by_key(|(rank, _)| *rank);
+        ranked.into_iter().map(|(_, d)| d.clone()).collect()
+    }
+
+    /// Устройство, ради которого стоит разорвать текущее соединение с `current`.
+    pub fn preempt_target(&self, available: &[BTDevice], current: &str) -> Option<BTDevice> {
+        let current_rank = self.rank(current).unwrap_or(usize::MAX);
+        self.candidates(available)
+            .into_iter()
+            .find(|d| d.id != current && self.rank(&d.id).is_some_and(|r| r < current_rank))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn devices(ids: &[&str]) -> Vec<BTDevice> {
+        ids.iter().map(|id| BTDevice { id: id.to_string(), name: id.to_uppercase() }).collect()
+    }
+
+    fn ids(devices: &[BTDevice]) -> Vec<&str> {
+        devices.iter().map(|d| d.id.as_str()).collect()
+    }
+
+    fn strings(ids: &[&str]) -> Vec<String> {
+        ids.iter().map(|id| id.to_string()).collect()
+    }
+
+    #[test]
+    fn priority_list_comes_first_then_auto_connect() {
+        let order = strings(&["b", "a"]);
+        let auto_connect = strings(&["c", "d", "a"]).into_iter().collect();
+        let priority = Priority { order: &order, auto_connect: &auto_connect, last_device: Some("d") };
+
+        assert_eq!(priority.rank("b"), Some(0));
+        assert_eq!(priority.rank("a"), Some(1));
+        // Последнее подключённое — первым среди автоподключаемых
+        assert_eq!(priority.rank("d"), Some(2));
+        assert_eq!(priority.rank("c"), Some(3));
+        assert_eq!(priority.rank("e"), None);
+
+        let available = devices(&["e", "c", "d", "a", "b"]);
+        assert_eq!(ids(&priority.candidates(&available)), ["b", "a", "d", "c"]);
+    }
+
+    #[test]
+    fn equal_rank_keeps_device_order() {
+        let auto_connect = strings(&["x", "y", "z"]).into_iter().collect();
+        let priority = Priority { order: &[], auto_connect: &auto_connect, last_device: None };
+        assert_eq!(ids(&priority.candidates(&devices(&["z", "x", "y"]))), ["z", "x", "y"]);
+    }
+
+    #[test]
+    fn unavailable_devices_are_skipped() {
+        let order = strings(&["a", "b"]);
+        let auto_connect = HashSet::new();
+        let priority = Priority { order: &order, auto_connect: &auto_connect, last_device: None };
+        assert_eq!(ids(&priority.candidates(&devices(&["b", "c"]))), ["b"]);
+        assert!(priority.candidates(&[]).is_empty());
+    }
+
+    #[test]
+    fn preempts_only_for_more_important_device() {
+        let order = strings(&["a", "b"]);
+        let auto_connect = strings(&["c"]).into_iter().collect();
+        let priority = Priority { order: &order, auto_connect: &auto_connect, last_device: None };
+        let available = devices(&["a", "b", "c"]);
+
+        assert_eq!(priority.preempt_target(&available, "b").map(|d| d.id), Some("a".to_string()));
+        assert_eq!(priority.preempt_target(&available, "c").map(|d| d.id), Some("a".to_string()));
+        assert_eq!(priority.preempt_target(&available, "a"), None);
+        // Подключённое вручную устройство без приоритета уступает любому из списка
+        assert_eq!(priority.preempt_target(&devices(&["c"]), "manual").map(|d| d.id), Some("c".to_string()));
+        assert_eq!(priority.preempt_target(&devices(&["b"]), "a"), None);
+    }
+}
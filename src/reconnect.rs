@@ -0,0 +1,157 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Правила автоматического переподключения после обрыва.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ReconnectPolicy {
+    pub initial_delay: Duration,
+    pub max_delay: Duration,
+    pub multiplier: f64,
+    /// Случайный разброс задержки: 0.2 = ±20%. Нужен, чтобы несколько
+    /// приёмников не долбили телефон строго одновременно.
+    pub jitter: f64,
+    /// Сколько попыток сделать, прежде чем сдаться. 0 — не переподключаться вовсе.
+    pub max_attempts: u32,
+}
+
+impl Default for ReconnectPolicy {
+    fn default() -> Self {
+        Self {
+            initial_delay: Duration::from_secs(1),
+            max_delay: Duration::from_secs(60),
+            multiplier: 2.0,
+            jitter: 0.2,
+            max_attempts: 8,
+        }
+    }
+}
+
+/// Экспоненциальная задержка между попытками. Не знает ничего о таймерах:
+/// только считает, сколько ждать перед очередной попыткой.
+#[derive(Clone, Debug)]
+pub struct Backoff {
+    policy: ReconnectPolicy,
+    attempt: u32,
+    rng: u64,
+}
+
+impl Backoff {
+    pub fn new(policy: ReconnectPolicy) -> Self {
+        let seed = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos() as u64)
+            .unwrap_or(0);
+        Self::with_seed(policy, seed)
+    }
+
+    pub fn with_seed(policy: ReconnectPolicy, seed: u64) -> Self {
+        // Соседние зёрна (наносекунды двух приёмников) должны давать разные задержки уже
+        // с первой попытки, поэтому зерно перемешиваем (splitmix64). xorshift не работает
+        // с нулевым состоянием.
+        let mut z = seed.wrapping_add(0x9e3779b97f4a7c15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        Self { policy, attempt: 0, rng: (z ^ (z >> 31)) | 1 }
+    }
+
+    /// Номер последней выданной попытки (с единицы).
+    pub fn attempt(&self) -> u32 {
+        self.attempt
+    }
+
+    pub fn max_attempts(&self) -> u32 {
+        self.policy.max_attempts
+    }
+
+    /// Задержка перед следующей попыткой или `None`, если попытки кончились.
+    pub fn next_delay(&mut self) -> Option<Duration> {
+        if self.attempt >= self.policy.max_attempts {
+            return None;
+        }
+        self.attempt += 1;
+
+        let base = self.policy.initial_delay.as_secs_f64()
+            * self.policy.multiplier.powi(self.attempt as i32 - 1);
+        let base = base.min(self.policy.max_delay.as_secs_f64());
+
+        // Равномерно в [-1, 1]
+        let spread = self.next_random() * 2.0 - 1.0;
+        let delay = base * (1.0 + self.policy.jitter * spread);
+
+        Some(Duration::from_secs_f64(delay.max(0.0)))
+    }
+
+    fn next_random(&mut self) -> f64 {
+        self.rng ^= self.rng << 13;
+        self.rng ^= self.rng >> 7;
+        self.rng ^= self.rng << 17;
+        (self.rng >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy(jitter: f64, max_attempts: u32) -> ReconnectPolicy {
+        ReconnectPolicy {
+            initial_delay: Duration::from_secs(1),
+            max_delay: Duration::from_secs(10),
+            multiplier: 2.0,
+            jitter,
+            max_attempts,
+        }
+    }
+
+    fn delays(backoff: &mut Backoff) -> Vec<Duration> {
+        std::iter::from_fn(|| backoff.next_delay()).collect()
+    }
+
+    #[test]
+    fn delay_grows_up_to_max() {
+        let mut backoff = Backoff::with_seed(policy(0.0, 6), 1);
+        let secs: Vec<u64> = delays(&mut backoff).iter().map(Duration::as_secs).collect();
+        assert_eq!(secs, [1, 2, 4, 8, 10, 10]);
+    }
+
+    #[test]
+    fn jitter_stays_in_bounds() {
+        let mut seen = Vec::new();
+        for seed in 0..200 {
+            let mut backoff = Backoff::with_seed(policy(0.2, 4), seed);
+            for (delay, base) in delays(&mut backoff).into_iter().zip([1.0, 2.0, 4.0, 8.0]) {
+                let delay = delay.as_secs_f64();
+                assert!(delay >= base * 0.8 - 1e-9 && delay <= base * 1.2 + 1e-9, "{delay} вне ±20% от {base}");
+                if base == 1.0 {
+                    seen.push(delay);
+                }
+            }
+        }
+        // Разброс действительно есть и занимает большую часть допустимого
+        let min = seen.iter().copied().fold(f64::MAX, f64::min);
+        let max = seen.iter().copied().fold(0.0, f64::max);
+        assert!(min < 0.85 && max > 1.15, "разброс {min}..{max}");
+    }
+
+    #[test]
+    fn attempts_are_capped() {
+        let mut backoff = Backoff::with_seed(policy(0.2, 3), 7);
+        assert_eq!(delays(&mut backoff).len(), 3);
+        assert_eq!(backoff.attempt(), 3);
+        assert_eq!(backoff.next_delay(), None);
+        assert_eq!(backoff.attempt(), 3);
+
+        let mut never = Backoff::with_seed(policy(0.2, 0), 7);
+        assert_eq!(never.next_delay(), None);
+        assert_eq!(never.attempt(), 0);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn schedule_takes_the_sum_of_delays() {
+        let start = tokio::time::Instant::now();
+        let mut backoff = Backoff::with_seed(policy(0.0, 5), 1);
+        while let Some(delay) = backoff.next_delay() {
+            tokio::time::sleep(delay).await;
+        }
+        assert_eq!(start.elapsed(), Duration::from_secs(1 + 2 + 4 + 8 + 10));
+    }
+}
@@ -0,0 +1,648 @@
+//! Запись принятого звука в файлы WAV и FLAC. От Windows не зависит: бэкенд только
+//! отдаёт куски звука (`AudioChunk`), а файлы, их имена и нарезка — здесь.
+
+use crate::i18n::Msg;
+use anyhow::{Context, Result};
+use flacenc::component::{BitRepr, StreamInfo};
+use flacenc::config::Encoder;
+use flacenc::error::{Verified, Verify};
+use flacenc::source::{Fill, FrameBuf};
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{self, BufWriter, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+/// Файлы пишутся в 16-битном PCM: этого с запасом хватает для звука, пришедшего по Bluetooth.
+const BITS_PER_SAMPLE: u16 = 16;
+
+/// Размер блока FLAC в отсчётах на канал — как у `flac` по умолчанию.
+const FLAC_BLOCK_SIZE: usize = 4096;
+
+/// Блок короче этого flacenc с предсказанием закодировать не может: разбиение остатка для
+/// кода Райса требует хотя бы 64 отсчёта. Такой последний блок пишется без предсказания.
+const FLAC_MIN_CODED_BLOCK: usize = 64;
+
+/// «fLaC», заголовок блока метаданных и сам STREAMINFO.
+const FLAC_HEADER_BYTES: u64 = 4 + 4 + 34;
+
+/// Сколько кусков звука ждут записи, пока диск занят: при квантах графа по 10 мс — около
+/// пяти секунд. Дальше куски выбрасываются, а не копятся в памяти.
+const TAP_QUEUE: usize = 512;
+
+/// Размеры в заголовке WAV 32-битные, поэтому файл больше 4 ГБ режется в любом случае.
+const WAV_MAX_DATA: u64 = u32::MAX as u64 - 36;
+
+// Потоки записи: перед выходом их надо дождаться, иначе последний файл останется без заголовка
+static WRITERS: Mutex<Vec<JoinHandle<()>>> = Mutex::new(Vec::new());
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RecordingFormat {
+    #[default]
+    Wav,
+    Flac,
+}
+
+impl RecordingFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            RecordingFormat::Wav => "wav",
+            RecordingFormat::Flac => "flac",
+        }
+    }
+}
+
+/// Формат принятого звука.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AudioFormat {
+    pub sample_rate: u32,
+    pub channels: u16,
+}
+
+/// Кусок принятого звука: отсчёты в диапазоне -1.0..=1.0, каналы чередуются.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AudioChunk {
+    pub format: AudioFormat,
+    pub samples: Vec<f32>,
+}
+
+/// Отвод звука в запись. Отдаёт куски, не дожидаясь диска: если запись не успевает,
+/// лишние куски выбрасываются и считаются. Клоны пишут в ту же запись.
+#[derive(Clone, Debug)]
+pub struct Tap {
+    tx: mpsc::Sender<AudioChunk>,
+    dropped: Arc<AtomicU64>,
+}
+
+impl Tap {
+    /// Отвод на `capacity` кусков и его приёмник.
+    pub fn new(capacity: usize) -> (Tap, mpsc::Receiver<AudioChunk>) {
+        let (tx, rx) = mpsc::channel(capacity);
+        (Tap { tx, dropped: Arc::default() }, rx)
+    }
+
+    /// `false` — запись закончилась и кусок никуда не ушёл.
+    pub fn send(&self, chunk: AudioChunk) -> bool {
+        match self.tx.try_send(chunk) {
+            Ok(()) => true,
+            Err(mpsc::error::TrySendError::Full(_)) => {
+                self.dropped.fetch_add(1, Ordering::Relaxed);
+                true
+            }
+            Err(mpsc::error::TrySendError::Closed(_)) => false,
+        }
+    }
+
+    /// Сколько кусков выброшено из-за переполнения.
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+}
+
+/// Куда и как записывать.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RecordingSettings {
+    pub folder: PathBuf,
+    pub format: RecordingFormat,
+    /// Начинать новый файл, когда текущий дорос до этого размера.
+    pub max_bytes: Option<u64>,
+    /// Начинать новый файл, когда в текущем набралось столько звука.
+    pub max_duration: Option<Duration>,
+}
+
+impl Default for RecordingSettings {
+    fn default() -> Self {
+        Self { folder: default_folder(), format: RecordingFormat::default(), max_bytes: None, max_duration: None }
+    }
+}
+
+/// Папка «Музыка», а если её нет — папка данных программы.
+pub fn default_folder() -> PathBuf {
+    match dirs::audio_dir() {
+        Some(dir) => dir.join("BT-Audio-Receiver"),
+        None => dirs::data_local_dir().unwrap_or_default().join("BT-Audio-Receiver").join("recordings"),
+    }
+}
+
+/// Запускает запись звука устройства `name` в отдельном потоке: запись на диск блокирующая.
+/// Запись идёт, пока жив возвращённый отвод; когда его не станет, последний файл
+/// дописывается и закрывается.
+pub fn spawn(settings: RecordingSettings, name: String) -> Tap {
+    let (tap, mut rx) = Tap::new(TAP_QUEUE);
+    let dropped = tap.dropped.clone();
+    let writer = std::thread::spawn(move || {
+        let mut recorder = Recorder::new(settings, &name);
+        let mut result = Ok(());
+        while let Some(chunk) = rx.blocking_recv() {
+            result = recorder.write(&chunk);
+            if result.is_err() {
+                break;
+            }
+        }
+        if let Err(e) = result.and(recorder.finish()) {
+            log::error!(target: "recording", "{}", Msg::LogRecordingFailed { name: &name, error: &e });
+        }
+        let dropped = dropped.load(Ordering::Relaxed);
+        if dropped > 0 {
+            log::warn!(target: "recording", "{}", Msg::LogRecordingDropped { name: &name, chunks: &dropped });
+        }
+    });
+    let mut writers = WRITERS.lock().unwrap();
+    writers.retain(|w| !w.is_finished());
+    writers.push(writer);
+    tap
+}
+
+/// Ждёт, пока допишутся все файлы. Записи, чей отправитель ещё жив, не закончатся никогда,
+/// поэтому звать после того, как все устройства отключены.
+pub fn wait_all() {
+    let writers = std::mem::take(&mut *WRITERS.lock().unwrap());
+    for writer in writers {
+        let _ = writer.join();
+    }
+}
+
+/// Пишет звук одного устройства в файлы `<имя> <дата и время>.<wav|flac>`, начиная новый,
+/// когда текущий упирается в предел размера или длительности или меняется формат звука.
+pub struct Recorder {
+    settings: RecordingSettings,
+    name: String,
+    segment: Option<Segment>,
+    files: Vec<PathBuf>,
+}
+
+struct Segment {
+    path: PathBuf,
+    format: AudioFormat,
+    writer: SampleWriter,
+    // Отсчётов на канал
+    frames: u64,
+}
+
+enum SampleWriter {
+    Wav(WavWriter<BufWriter<File>>),
+    Flac(Box<FlacWriter<BufWriter<File>>>),
+}
+
+impl Recorder {
+    pub fn new(settings: RecordingSettings, name: &str) -> Self {
+        Self { settings, name: name.to_string(), segment: None, files: Vec::new() }
+    }
+
+    pub fn write(&mut self, chunk: &AudioChunk) -> Result<()> {
+        if chunk.samples.is_empty() || chunk.format.channels == 0 || chunk.format.sample_rate == 0 {
+            return Ok(());
+        }
+        if self.segment.as_ref().is_some_and(|s| s.format != chunk.format) {
+            self.finish_segment()?;
+        }
+        let segment = match &mut self.segment {
+            Some(segment) => segment,
+            None => self.segment.insert(self.open(chunk.format)?),
+        };
+
+        let samples: Vec<i16> = chunk.samples.iter().map(|s| to_pcm16(*s)).collect();
+        match &mut segment.writer {
+            SampleWriter::Wav(writer) => writer.write(&samples)?,
+            SampleWriter::Flac(writer) => writer.write(&samples)?,
+        }
+        segment.frames += (samples.len() / chunk.format.channels as usize) as u64;
+
+        if self.segment_full() {
+            self.finish_segment()?;
+        }
+        Ok(())
+    }
+
+    /// Закрывает последний файл и возвращает все записанные.
+    pub fn finish(mut self) -> Result<Vec<PathBuf>> {
+        self.finish_segment()?;
+        Ok(self.files)
+    }
+
+    fn open(&self, format: AudioFormat) -> Result<Segment> {
+        std::fs::create_dir_all(&self.settings.folder)
+            .with_context(|| Msg::RecordingFolderFailed { path: &self.settings.folder.display() }.to_string())?;
+        let stamp = chrono::Local::now().format("%Y-%m-%d %H-%M-%S").to_string();
+        let path = unique_path(&self.settings.folder, &file_stem(&self.name, &stamp), self.settings.format.extension());
+
+        let file = File::create(&path).with_context(|| Msg::RecordingFileFailed { path: &path.display() }.to_string())?;
+        let file = BufWriter::new(file);
+        let writer = match self.settings.format {
+            RecordingFormat::Wav => SampleWriter::Wav(WavWriter::new(file, format)?),
+            RecordingFormat::Flac => SampleWriter::Flac(Box::new(FlacWriter::new(file, format)?)),
+        };
+        log::info!(target: "recording", "{}", Msg::LogRecordingStarted { path: &path.display() });
+        Ok(Segment { path, format, writer, frames: 0 })
+    }
+
+    fn segment_full(&self) -> bool {
+        let Some(segment) = &self.segment else { return false };
+        let bytes = match &segment.writer {
+            SampleWriter::Wav(writer) => writer.bytes_written(),
+            SampleWriter::Flac(writer) => writer.bytes_written(),
+        };
+        let duration = Duration::from_secs_f64(segment.frames as f64 / f64::from(segment.format.sample_rate));
+        self.settings.max_bytes.is_some_and(|max| bytes >= max)
+            || self.settings.max_duration.is_some_and(|max| duration >= max)
+            || (matches!(segment.writer, SampleWriter::Wav(_)) && bytes >= WAV_MAX_DATA)
+    }
+
+    fn finish_segment(&mut self) -> Result<()> {
+        let Some(segment) = self.segment.take() else { return Ok(()) };
+        let file = match segment.writer {
+            SampleWriter::Wav(writer) => writer.finish()?,
+            SampleWriter::Flac(writer) => writer.finish()?,
+        };
+        file.into_inner().map_err(|e| e.into_error())?.sync_all()?;
+        log::info!(target: "recording", "{}", Msg::LogRecordingSaved { path: &segment.path.display() });
+        self.files.push(segment.path);
+        Ok(())
+    }
+}
+
+/// WAV (16-битный PCM). Размеры в заголовке проставляются в `finish`.
+pub struct WavWriter<W: Write + Seek> {
+    inner: W,
+    data_bytes: u64,
+}
+
+impl<W: Write + Seek> WavWriter<W> {
+    pub fn new(mut inner: W, format: AudioFormat) -> io::Result<Self> {
+        let block_align = format.channels * BITS_PER_SAMPLE / 8;
+        let mut header = Vec::with_capacity(44);
+        header.extend_from_slice(b"RIFF");
+        header.extend_from_slice(&36u32.to_le_bytes());
+        header.extend_from_slice(b"WAVEfmt ");
+        header.extend_from_slice(&16u32.to_le_bytes());
+        header.extend_from_slice(&1u16.to_le_bytes()); // PCM
+        header.extend_from_slice(&format.channels.to_le_bytes());
+        header.extend_from_slice(&format.sample_rate.to_le_bytes());
+        header.extend_from_slice(&(format.sample_rate * u32::from(block_align)).to_le_bytes());
+        header.extend_from_slice(&block_align.to_le_bytes());
+        header.extend_from_slice(&BITS_PER_SAMPLE.to_le_bytes());
+        header.extend_from_slice(b"data");
+        header.extend_from_slice(&0u32.to_le_bytes());
+        inner.write_all(&header)?;
+        Ok(Self { inner, data_bytes: 0 })
+    }
+
+    pub fn write(&mut self, samples: &[i16]) -> io::Result<()> {
+        let bytes: Vec<u8> = samples.iter().flat_map(|s| s.to_le_bytes()).collect();
+        self.inner.write_all(&bytes)?;
+        self.data_bytes += bytes.len() as u64;
+        Ok(())
+    }
+
+    pub fn bytes_written(&self) -> u64 {
+        44 + self.data_bytes
+    }
+
+    pub fn finish(mut self) -> io::Result<W> {
+        let data = self.data_bytes.min(WAV_MAX_DATA) as u32;
+        self.inner.seek(SeekFrom::Start(4))?;
+        self.inner.write_all(&(36 + data).to_le_bytes())?;
+        self.inner.seek(SeekFrom::Start(40))?;
+        self.inner.write_all(&data.to_le_bytes())?;
+        self.inner.seek(SeekFrom::End(0))?;
+        self.inner.flush()?;
+        Ok(self.inner)
+    }
+}
+
+/// FLAC (16 бит). Кадры кодируются по мере накопления блока, а STREAMINFO с итоговой
+/// длиной переписывается в `finish`. MD5 не считается — по стандарту это допустимо.
+pub struct FlacWriter<W: Write + Seek> {
+    inner: W,
+    config: Verified<Encoder>,
+    // Для короткого последнего блока: без предсказания, только постоянные и «как есть»
+    tail_config: Verified<Encoder>,
+    stream_info: StreamInfo,
+    channels: usize,
+    block: Vec<i32>,
+    frame_buf: FrameBuf,
+    frame_number: usize,
+    bytes: u64,
+}
+
+impl<W: Write + Seek> FlacWriter<W> {
+    pub fn new(mut inner: W, format: AudioFormat) -> Result<Self> {
+        let channels = format.channels as usize;
+        let config = Encoder::default().into_verified().map_err(|(_, e)| flac_error(e))?;
+        let mut tail_config = Encoder::default();
+        tail_config.subframe_coding.use_fixed = false;
+        tail_config.subframe_coding.use_lpc = false;
+        let tail_config = tail_config.into_verified().map_err(|(_, e)| flac_error(e))?;
+        let stream_info = StreamInfo::new(format.sample_rate as usize, channels, BITS_PER_SAMPLE as usize).map_err(flac_error)?;
+        inner.write_all(b"fLaC")?;
+        let mut writer = Self {
+            inner,
+            config,
+            tail_config,
+            stream_info,
+            channels,
+            block: Vec::with_capacity(FLAC_BLOCK_SIZE * channels),
+            frame_buf: FrameBuf::with_size(channels, FLAC_BLOCK_SIZE).map_err(flac_error)?,
+            frame_number: 0,
+            bytes: FLAC_HEADER_BYTES,
+        };
+        writer.write_stream_info()?;
+        Ok(writer)
+    }
+
+    pub fn write(&mut self, samples: &[i16]) -> Result<()> {
+        for sample in samples {
+            self.block.push(i32::from(*sample));
+            if self.block.len() == FLAC_BLOCK_SIZE * self.channels {
+                self.encode_block()?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn bytes_written(&self) -> u64 {
+        self.bytes
+    }
+
+    pub fn finish(mut self) -> Result<W> {
+        // Последний блок — своей длины: короче остальных он может быть по стандарту, а
+        // размеры блоков в STREAMINFO указываются без него, то есть остаются постоянными
+        if !self.block.is_empty() {
+            self.frame_buf.resize(self.block.len() / self.channels);
+            self.encode_block()?;
+            self.stream_info.set_block_sizes(FLAC_BLOCK_SIZE, FLAC_BLOCK_SIZE).map_err(flac_error)?;
+        }
+        self.inner.seek(SeekFrom::Start(4))?;
+        self.write_stream_info()?;
+        self.inner.seek(SeekFrom::End(0))?;
+        self.inner.flush()?;
+        Ok(self.inner)
+    }
+
+    fn encode_block(&mut self) -> Result<()> {
+        self.frame_buf.fill_interleaved(&self.block).map_err(flac_error)?;
+        let config = match self.frame_buf.size() < FLAC_MIN_CODED_BLOCK {
+            true => &self.tail_config,
+            false => &self.config,
+        };
+        let frame = flacenc::encode_fixed_size_frame(config, &self.frame_buf, self.frame_number, &self.stream_info)
+            .map_err(flac_error)?;
+        self.stream_info.update_frame_info(&frame);
+
+        let mut sink = flacenc::bitsink::ByteSink::new();
+        frame.write(&mut sink).map_err(flac_error)?;
+        self.inner.write_all(sink.as_slice())?;
+        self.bytes += sink.as_slice().len() as u64;
+        self.frame_number += 1;
+        self.block.clear();
+        Ok(())
+    }
+
+    // Единственный блок метаданных: флаг «последний», тип 0 (STREAMINFO) и длина 34 байта
+    fn write_stream_info(&mut self) -> Result<()> {
+        let mut sink = flacenc::bitsink::ByteSink::new();
+        self.stream_info.write(&mut sink).map_err(flac_error)?;
+        self.inner.write_all(&[0x80, 0, 0, sink.as_slice().len() as u8])?;
+        self.inner.write_all(sink.as_slice())?;
+        Ok(())
+    }
+}
+
+// Ошибки flacenc не Send, а у части из них нет и Display
+fn flac_error(error: impl std::fmt::Debug) -> anyhow::Error {
+    anyhow::anyhow!("FLAC: {error:?}")
+}
+
+fn to_pcm16(sample: f32) -> i16 {
+    (sample.clamp(-1.0, 1.0) * f32::from(i16::MAX)).round() as i16
+}
+
+// Имя устройства может содержать символы, запрещённые в именах файлов Windows
+fn file_stem(name: &str, stamp: &str) -> String {
+    let name: String = name
+        .trim()
+        .chars()
+        .map(|c| if c.is_control() || r#"<>:"/\|?*"#.contains(c) { '_' } else { c })
+        .collect();
+    match name.is_empty() {
+        true => stamp.to_string(),
+        false => format!("{name} {stamp}"),
+    }
+}
+
+// Два файла за одну секунду (нарезка, переподключение) получают номер
+fn unique_path(folder: &Path, stem: &str, extension: &str) -> PathBuf {
+    let mut path = folder.join(format!("{stem}.{extension}"));
+    let mut number = 2;
+    while path.exists() {
+        path = folder.join(format!("{stem} ({number}).{extension}"));
+        number += 1;
+    }
+    path
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    const STEREO: AudioFormat = AudioFormat { sample_rate: 48_000, channels: 2 };
+
+    // Свой каталог на тест: тесты идут параллельно
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("bt-audio-recording-{}-{name}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    // Пила по кадрам: у каналов разные отсчёты, чтобы перепутанное чередование было видно
+    fn saw(frames: usize) -> Vec<i16> {
+        (0..frames).flat_map(|i| [(i % 2000) as i16 * 16, -((i % 700) as i16) * 40]).collect()
+    }
+
+    fn u32_at(bytes: &[u8], at: usize) -> u32 {
+        u32::from_le_bytes(bytes[at..at + 4].try_into().unwrap())
+    }
+
+    fn decode_flac(bytes: Vec<u8>) -> (claxon::metadata::StreamInfo, Vec<i16>) {
+        let mut reader = claxon::FlacReader::new(Cursor::new(bytes)).unwrap();
+        let info = reader.streaminfo();
+        let samples = reader.samples().map(|s| s.unwrap() as i16).collect();
+        (info, samples)
+    }
+
+    #[test]
+    fn wav_header_has_final_sizes() {
+        let samples = saw(1000);
+        let mut writer = WavWriter::new(Cursor::new(Vec::new()), STEREO).unwrap();
+        writer.write(&samples[..600]).unwrap();
+        writer.write(&samples[600..]).unwrap();
+        assert_eq!(writer.bytes_written(), 44 + 4000);
+        let bytes = writer.finish().unwrap().into_inner();
+
+        assert_eq!(bytes.len(), 44 + 4000);
+        assert_eq!(&bytes[..4], b"RIFF");
+        assert_eq!(u32_at(&bytes, 4), 36 + 4000, "размер RIFF");
+        assert_eq!(&bytes[8..16], b"WAVEfmt ");
+        assert_eq!(u16::from_le_bytes([bytes[22], bytes[23]]), 2, "каналы");
+        assert_eq!(u32_at(&bytes, 24), 48_000, "частота");
+        assert_eq!(u32_at(&bytes, 28), 48_000 * 4, "байт в секунду");
+        assert_eq!(u16::from_le_bytes([bytes[32], bytes[33]]), 4, "байт на кадр");
+        assert_eq!(u16::from_le_bytes([bytes[34], bytes[35]]), 16, "бит на отсчёт");
+        assert_eq!(&bytes[36..40], b"data");
+        assert_eq!(u32_at(&bytes, 40), 4000, "размер данных");
+
+        let data: Vec<i16> = bytes[44..].chunks_exact(2).map(|b| i16::from_le_bytes([b[0], b[1]])).collect();
+        assert_eq!(data, samples);
+    }
+
+    #[test]
+    fn empty_wav_is_valid() {
+        let bytes = WavWriter::new(Cursor::new(Vec::new()), STEREO).unwrap().finish().unwrap().into_inner();
+        assert_eq!(bytes.len(), 44);
+        assert_eq!(u32_at(&bytes, 4), 36);
+        assert_eq!(u32_at(&bytes, 40), 0);
+    }
+
+    #[test]
+    fn flac_keeps_exact_length_and_samples() {
+        // Два полных блока и короткий хвост: хвост не должен дополняться тишиной
+        let frames = FLAC_BLOCK_SIZE * 2 + 5;
+        let samples = saw(frames);
+        let mut writer = FlacWriter::new(Cursor::new(Vec::new()), STEREO).unwrap();
+        for chunk in samples.chunks(960) {
+            writer.write(chunk).unwrap();
+        }
+        let bytes = writer.finish().unwrap().into_inner();
+
+        let (info, decoded) = decode_flac(bytes);
+        assert_eq!(info.samples, Some(frames as u64), "длина в STREAMINFO");
+        assert_eq!(info.sample_rate, 48_000);
+        assert_eq!(info.channels, 2);
+        assert_eq!(info.bits_per_sample, 16);
+        assert_eq!((info.min_block_size, info.max_block_size), (FLAC_BLOCK_SIZE as u16, FLAC_BLOCK_SIZE as u16));
+        assert_eq!(decoded, samples, "без потерь и без добавленной тишины");
+    }
+
+    #[test]
+    fn flac_with_one_short_block() {
+        let samples = saw(3);
+        let mut writer = FlacWriter::new(Cursor::new(Vec::new()), STEREO).unwrap();
+        writer.write(&samples).unwrap();
+        let bytes = writer.finish().unwrap().into_inner();
+
+        let (info, decoded) = decode_flac(bytes);
+        assert_eq!(info.samples, Some(3));
+        assert_eq!(decoded, samples);
+    }
+
+    #[test]
+    fn flac_tail_of_any_length_is_encoded() {
+        for tail in [1, 2, FLAC_MIN_CODED_BLOCK - 1, FLAC_MIN_CODED_BLOCK, FLAC_BLOCK_SIZE - 1] {
+            let samples = saw(FLAC_BLOCK_SIZE + tail);
+            let mut writer = FlacWriter::new(Cursor::new(Vec::new()), STEREO).unwrap();
+            writer.write(&samples).unwrap();
+            let (info, decoded) = decode_flac(writer.finish().unwrap().into_inner());
+            assert_eq!(info.samples, Some((FLAC_BLOCK_SIZE + tail) as u64), "хвост {tail}");
+            assert_eq!(decoded, samples, "хвост {tail}");
+        }
+    }
+
+    #[test]
+    fn flac_bytes_written_matches_output() {
+        let mut writer = FlacWriter::new(Cursor::new(Vec::new()), STEREO).unwrap();
+        assert_eq!(writer.bytes_written(), FLAC_HEADER_BYTES);
+        writer.write(&saw(FLAC_BLOCK_SIZE)).unwrap();
+        let written = writer.bytes_written();
+        let bytes = writer.finish().unwrap().into_inner();
+        assert_eq!(written, bytes.len() as u64);
+    }
+
+    #[test]
+    fn recorder_splits_by_duration() {
+        let dir = temp_dir("split");
+        let format = AudioFormat { sample_rate: 1000, channels: 1 };
+        let settings = RecordingSettings {
+            folder: dir.clone(),
+            format: RecordingFormat::Wav,
+            max_bytes: None,
+            max_duration: Some(Duration::from_secs(1)),
+        };
+        let mut recorder = Recorder::new(settings, "Pixel");
+        // 2,5 секунды кусками по 100 мс
+        for _ in 0..25 {
+            recorder.write(&AudioChunk { format, samples: vec![0.5; 100] }).unwrap();
+        }
+        let files = recorder.finish().unwrap();
+
+        assert_eq!(files.len(), 3, "две полные секунды и остаток");
+        let sizes: Vec<u32> = files.iter().map(|f| u32_at(&std::fs::read(f).unwrap(), 40)).collect();
+        assert_eq!(sizes, [2000, 2000, 1000]);
+        assert!(files.iter().all(|f| f.starts_with(&dir) && f.file_name().unwrap().to_string_lossy().starts_with("Pixel ")));
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn recorder_starts_new_file_when_format_changes() {
+        let dir = temp_dir("format");
+        let settings = RecordingSettings { folder: dir.clone(), format: RecordingFormat::Flac, ..Default::default() };
+        let mut recorder = Recorder::new(settings, "Pixel");
+        recorder.write(&AudioChunk { format: STEREO, samples: vec![0.25; 200] }).unwrap();
+        let mono = AudioFormat { sample_rate: 44_100, channels: 1 };
+        recorder.write(&AudioChunk { format: mono, samples: vec![-0.25; 50] }).unwrap();
+        let files = recorder.finish().unwrap();
+
+        assert_eq!(files.len(), 2);
+        let (first, _) = decode_flac(std::fs::read(&files[0]).unwrap());
+        let (second, _) = decode_flac(std::fs::read(&files[1]).unwrap());
+        assert_eq!((first.channels, first.samples), (2, Some(100)));
+        assert_eq!((second.channels, second.sample_rate, second.samples), (1, 44_100, Some(50)));
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn tap_counts_chunks_it_cannot_queue() {
+        let (tap, mut rx) = Tap::new(2);
+        let chunk = AudioChunk { format: STEREO, samples: vec![0.0; 4] };
+        for _ in 0..5 {
+            assert!(tap.send(chunk.clone()), "переполнение не обрывает запись");
+        }
+        assert_eq!(tap.dropped(), 3);
+        assert!(rx.try_recv().is_ok());
+
+        drop(rx);
+        assert!(!tap.send(chunk), "запись закончилась");
+        assert_eq!(tap.dropped(), 3);
+    }
+
+    #[test]
+    fn pcm_conversion_clamps() {
+        assert_eq!(to_pcm16(0.0), 0);
+        assert_eq!(to_pcm16(1.0), i16::MAX);
+        assert_eq!(to_pcm16(2.0), i16::MAX);
+        assert_eq!(to_pcm16(-1.5), -i16::MAX);
+        assert_eq!(to_pcm16(0.5), 16384);
+    }
+
+    #[test]
+    fn file_names_are_safe_and_unique() {
+        assert_eq!(file_stem(" Galaxy: A/B? ", "2026-01-02 03-04-05"), "Galaxy_ A_B_ 2026-01-02 03-04-05");
+        assert_eq!(file_stem("  ", "2026-01-02 03-04-05"), "2026-01-02 03-04-05");
+
+        let dir = temp_dir("unique");
+        let first = unique_path(&dir, "Pixel", "wav");
+        assert_eq!(first, dir.join("Pixel.wav"));
+        std::fs::write(&first, b"").unwrap();
+        let second = unique_path(&dir, "Pixel", "wav");
+        assert_eq!(second, dir.join("Pixel (2).wav"));
+        std::fs::write(&second, b"").unwrap();
+        assert_eq!(unique_path(&dir, "Pixel", "wav"), dir.join("Pixel (3).wav"));
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
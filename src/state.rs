@@ -0,0 +1,408 @@
+use crate::backend::{AudioOutput, BTDevice};
+use crate::i18n::Msg;
+use crate::media::NowPlaying;
+use crate::volume::Volume;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Состояние соединения с одним устройством.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum ConnectionState {
+    #[default]
+    Idle,
+    Connecting { device: BTDevice },
+    Connected { device: BTDevice },
+    /// `attempt` — номер автоматической попытки; 0 — переподключение по команде пользователя.
+    Reconnecting { device: BTDevice, attempt: u32 },
+    /// `device` — если известно, с каким устройством не вышло (его может и не быть среди
+    /// доступных). Место в приёмнике неудачное соединение не занимает.
+    Failed { device: Option<BTDevice>, reason: String },
+}
+
+/// То, что происходит с соединением. Состояние меняется только через них.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum StateEvent {
+    ConnectRequested(BTDevice),
+    ReconnectRequested(BTDevice),
+    ConnectSucceeded,
+    ConnectFailed(String),
+    /// Соединение оборвалось или очередная попытка не удалась — ждём следующую.
+    RetryScheduled(u32),
+    /// Политика переподключения исчерпана.
+    GaveUp(String),
+    Disconnected,
+}
+
+impl ConnectionState {
+    /// Следующее состояние после события или `None`, если в текущем
+    /// состоянии такое событие ничего не меняет.
+    pub fn on(&self, event: StateEvent) -> Option<ConnectionState> {
+        use ConnectionState::*;
+
+        let next = match (self, event) {
+            (_, StateEvent::ConnectRequested(device)) => Connecting { device },
+            (_, StateEvent::ReconnectRequested(device)) => Reconnecting { device, attempt: 0 },
+
+            (Connecting { device } | Reconnecting { device, .. }, StateEvent::ConnectSucceeded) => {
+                Connected { device: device.clone() }
+            }
+            (Failed { device, .. }, StateEvent::ConnectFailed(reason)) => Failed { device: device.clone(), reason },
+            (_, StateEvent::ConnectFailed(reason)) => Failed { device: self.device().cloned(), reason },
+
+            (Connected { device } | Reconnecting { device, .. }, StateEvent::RetryScheduled(attempt)) => {
+                Reconnecting { device: device.clone(), attempt }
+            }
+            (Connected { device } | Reconnecting { device, .. }, StateEvent::GaveUp(reason)) => {
+                Failed { device: Some(device.clone()), reason }
+            }
+
+            (Idle, StateEvent::Disconnected) => return None,
+            (_, StateEvent::Disconnected) => Idle,
+
+            _ => return None,
+        };
+
+        Some(next)
+    }
+
+    /// Устройство, с которым связано текущее состояние (если есть).
+    pub fn device(&self) -> Option<&BTDevice> {
+        match self {
+            ConnectionState::Connecting { device }
+            | ConnectionState::Connected { device }
+            | ConnectionState::Reconnecting { device, .. } => Some(device),
+            _ => None,
+        }
+    }
+
+    /// Короткое имя состояния для вывода наружу.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            ConnectionState::Idle => "idle",
+            ConnectionState::Connecting { .. } => "connecting",
+            ConnectionState::Connected { .. } => "connected",
+            ConnectionState::Reconnecting { .. } => "reconnecting",
+            ConnectionState::Failed { .. } => "failed",
+        }
+    }
+
+    /// Строка о соединении для людей, одна на трей и командную строку. `name` — как
+    /// показывать устройство (в меню имена одинаковых телефонов различаются). У `Idle` строки нет.
+    pub fn label(&self, name: &str) -> Option<String> {
+        let label = match self {
+            ConnectionState::Idle => return None,
+            ConnectionState::Connecting { .. } => Msg::MenuConnecting { name: &name }.to_string(),
+            ConnectionState::Reconnecting { attempt, .. } if *attempt > 0 => {
+                Msg::MenuReconnectAttempt { name: &name, attempt }.to_string()
+            }
+            ConnectionState::Reconnecting { .. } => format!("🔄 {}", name),
+            ConnectionState::Connected { .. } => format!("✅ {}", name),
+            ConnectionState::Failed { reason, .. } => format!("⚠️ {}", reason),
+        };
+        Some(label)
+    }
+
+    pub fn is_connected(&self) -> bool {
+        matches!(self, ConnectionState::Connected { .. })
+    }
+
+    /// Соединение есть или вот-вот будет: устройство занимает место в приёмнике.
+    pub fn is_active(&self) -> bool {
+        self.device().is_some()
+    }
+}
+
+/// Состояние приёмника целиком, которое воркер рассылает интерфейсу:
+/// по автомату `ConnectionState` на каждое устройство.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ReceiverState {
+    /// Идёт поиск устройств. Это состояние приёмника, а не соединения: поиск не мешает
+    /// ни подключаться, ни оставаться подключённым, поэтому в `ConnectionState` его нет.
+    pub scanning: bool,
+    /// Устройства (по `BTDevice::id`) не в состоянии `Idle`, в порядке появления.
+    pub sessions: Vec<(String, ConnectionState)>,
+    /// Что играет на последнем подключённом телефоне. Заполняет `media::metadata_worker`.
+    pub now_playing: Option<NowPlaying>,
+    /// Громкость устройств (по `BTDevice::id`), выставленная за время работы.
+    pub volumes: BTreeMap<String, Volume>,
+    /// Устройства вывода, доступные на момент последнего сканирования.
+    pub outputs: Vec<AudioOutput>,
+    /// Куда сейчас идёт звук подключённых устройств: `AudioOutput::id` по `BTDevice::id`.
+    /// Нет записи — на выход по умолчанию.
+    pub routes: BTreeMap<String, String>,
+    /// Принятый звук записывается в файлы.
+    pub recording: bool,
+    pub night_mode: bool,
+}
+
+static IDLE: ConnectionState = ConnectionState::Idle;
+
+impl ReceiverState {
+    pub fn get(&self, id: &str) -> &ConnectionState {
+        self.sessions
+            .iter()
+            .find(|(s, _)| s == id)
+            .map_or(&IDLE, |(_, state)| state)
+    }
+
+    /// Применяет событие к автомату устройства `id`. Возвращает `true`, если что-то изменилось.
+    pub fn apply(&mut self, id: &str, event: StateEvent) -> bool {
+        let Some(next) = self.get(id).on(event) else { return false };
+
+        match (self.sessions.iter().position(|(s, _)| s == id), next) {
+            (Some(i), ConnectionState::Idle) => {
+                self.sessions.remove(i);
+            }
+            (Some(i), next) => self.sessions[i].1 = next,
+            (None, ConnectionState::Idle) => {}
+            (None, next) => self.sessions.push((id.to_string(), next)),
+        }
+        true
+    }
+
+    /// Подключённые устройства в порядке подключения.
+    pub fn connected(&self) -> impl Iterator<Item = &BTDevice> {
+        self.sessions
+            .iter()
+            .filter(|(_, state)| state.is_connected())
+            .filter_map(|(_, state)| state.device())
+    }
+
+    /// Устройство вывода, на которое идёт звук `id`; `None` — выход по умолчанию.
+    pub fn route(&self, id: &str) -> Option<&AudioOutput> {
+        let output = self.routes.get(id)?;
+        self.outputs.iter().find(|o| o.id == *output)
+    }
+
+    pub fn active_count(&self) -> usize {
+        self.sessions.iter().filter(|(_, state)| state.is_active()).count()
+    }
+}
+
+/// Состояние приёмника в виде, пригодном для вывода наружу (`status --json`).
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StatusReport {
+    /// Есть ли запущенный экземпляр, у которого спрашивали состояние.
+    pub running: bool,
+    pub scanning: bool,
+    pub sessions: Vec<SessionReport>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub now_playing: Option<NowPlaying>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub recording: bool,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub night_mode: bool,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SessionReport {
+    pub id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// `connecting`, `connected`, `reconnecting` или `failed`.
+    pub state: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub attempt: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub volume: Option<Volume>,
+    /// Имя устройства вывода, если звук идёт не на выход по умолчанию.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output: Option<String>,
+}
+
+impl StatusReport {
+    pub fn new(state: &ReceiverState) -> Self {
+        let sessions = state
+            .sessions
+            .iter()
+            .map(|(id, session)| {
+                let (name, attempt, reason) = match session {
+                    ConnectionState::Reconnecting { device, attempt } => (Some(device.name.clone()), Some(*attempt), None),
+                    ConnectionState::Failed { device, reason } => {
+                        (device.as_ref().map(|d| d.name.clone()), None, Some(reason.clone()))
+                    }
+                    other => (other.device().map(|d| d.name.clone()), None, None),
+                };
+                let volume = state.volumes.get(id).copied();
+                let output = state.route(id).map(|o| o.name.clone());
+                SessionReport { id: id.clone(), name, state: session.kind().to_string(), attempt, reason, volume, output }
+            })
+            .collect();
+        Self {
+            running: true,
+            scanning: state.scanning,
+            sessions,
+            now_playing: state.now_playing.clone(),
+            recording: state.recording,
+            night_mode: state.night_mode,
+        }
+    }
+}
+
+impl StatusReport {
+    /// Состояние построчно для людей: то же, что показывает трей.
+    pub fn lines(&self) -> Vec<String> {
+        let mut lines = Vec::new();
+        if !self.running {
+            lines.push(Msg::CliNotRunning.to_string());
+        } else if self.sessions.is_empty() {
+            lines.push(Msg::CliNoConnections.to_string());
+        }
+        if self.scanning {
+            lines.push(Msg::MenuScanning.to_string());
+        }
+        if self.recording {
+            lines.push(Msg::MenuRecording.to_string());
+        }
+        if self.night_mode {
+            lines.push(Msg::MenuNightMode.to_string());
+        }
+        if let Some(now_playing) = &self.now_playing {
+            lines.push(format!("🎵 {}", now_playing.summary()));
+        }
+        lines.extend(self.sessions.iter().map(SessionReport::label));
+        lines
+    }
+}
+
+impl SessionReport {
+    /// Строка о соединении, как `ConnectionState::label`, с громкостью и выходом.
+    pub fn label(&self) -> String {
+        let name = self.name.as_deref().unwrap_or(&self.id);
+        let device = BTDevice { id: self.id.clone(), name: name.to_string() };
+        let state = match (self.state.as_str(), &self.reason) {
+            ("connecting", _) => ConnectionState::Connecting { device },
+            ("reconnecting", _) => ConnectionState::Reconnecting { device, attempt: self.attempt.unwrap_or(0) },
+            (_, Some(reason)) => ConnectionState::Failed { device: Some(device), reason: reason.clone() },
+            _ => ConnectionState::Connected { device },
+        };
+        let mut line = state.label(name).unwrap_or_default();
+        if let Some(volume) = self.volume {
+            line = format!("{line}  {volume}");
+        }
+        if let Some(output) = &self.output {
+            line = format!("{line}  → {output}");
+        }
+        line
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ConnectionState::*;
+
+    fn phone() -> BTDevice {
+        BTDevice { id: "phone-1".to_string(), name: "Pixel".to_string() }
+    }
+
+    fn connected() -> ConnectionState {
+        Connected { device: phone() }
+    }
+
+    #[test]
+    fn connect_succeeds() {
+        let connecting = Idle.on(StateEvent::ConnectRequested(phone())).unwrap();
+        assert_eq!(connecting, Connecting { device: phone() });
+        assert!(connecting.is_active() && !connecting.is_connected());
+        assert_eq!(connecting.on(StateEvent::ConnectSucceeded), Some(connected()));
+    }
+
+    #[test]
+    fn connect_fails_with_the_device() {
+        let connecting = Connecting { device: phone() };
+        let failed = connecting.on(StateEvent::ConnectFailed("занят".into())).unwrap();
+        assert_eq!(failed, Failed { device: Some(phone()), reason: "занят".into() });
+        assert!(!failed.is_active());
+        // Устройство не нашлось ещё до попытки
+        assert_eq!(Idle.on(StateEvent::ConnectFailed("нет".into())), Some(Failed { device: None, reason: "нет".into() }));
+        // Повторная неудача не забывает устройство
+        assert_eq!(failed.on(StateEvent::ConnectFailed("снова".into())), Some(Failed { device: Some(phone()), reason: "снова".into() }));
+    }
+
+    #[test]
+    fn drop_retries_then_gives_up() {
+        let retrying = connected().on(StateEvent::RetryScheduled(1)).unwrap();
+        assert_eq!(retrying, Reconnecting { device: phone(), attempt: 1 });
+        assert!(retrying.is_active());
+        let retrying = retrying.on(StateEvent::RetryScheduled(2)).unwrap();
+        assert_eq!(retrying, Reconnecting { device: phone(), attempt: 2 });
+        assert_eq!(retrying.on(StateEvent::ConnectSucceeded), Some(connected()));
+
+        let failed = retrying.on(StateEvent::GaveUp("всё".into())).unwrap();
+        assert_eq!(failed, Failed { device: Some(phone()), reason: "всё".into() });
+    }
+
+    #[test]
+    fn manual_reconnect() {
+        let reconnecting = connected().on(StateEvent::ReconnectRequested(phone())).unwrap();
+        assert_eq!(reconnecting, Reconnecting { device: phone(), attempt: 0 });
+        assert_eq!(reconnecting.on(StateEvent::ConnectSucceeded), Some(connected()));
+    }
+
+    #[test]
+    fn events_that_change_nothing() {
+        assert_eq!(Idle.on(StateEvent::Disconnected), None);
+        assert_eq!(Idle.on(StateEvent::ConnectSucceeded), None);
+        assert_eq!(Idle.on(StateEvent::RetryScheduled(1)), None);
+        assert_eq!(Idle.on(StateEvent::GaveUp("".into())), None);
+        assert_eq!(connected().on(StateEvent::ConnectSucceeded), None);
+        assert_eq!(Connecting { device: phone() }.on(StateEvent::RetryScheduled(1)), None);
+        let failed = Failed { device: None, reason: "".into() };
+        assert_eq!(failed.on(StateEvent::ConnectSucceeded), None);
+        assert_eq!(failed.on(StateEvent::GaveUp("".into())), None);
+    }
+
+    #[test]
+    fn disconnect_returns_to_idle() {
+        for state in [Connecting { device: phone() }, connected(), Failed { device: None, reason: "".into() }] {
+            assert_eq!(state.on(StateEvent::Disconnected), Some(Idle));
+        }
+    }
+
+    #[test]
+    fn receiver_keeps_only_non_idle_sessions() {
+        let mut state = ReceiverState::default();
+        assert!(state.apply("phone-1", StateEvent::ConnectRequested(phone())));
+        assert!(state.apply("phone-1", StateEvent::ConnectSucceeded));
+        assert!(!state.apply("phone-1", StateEvent::ConnectSucceeded));
+        assert_eq!(state.connected().collect::<Vec<_>>(), [&phone()]);
+        assert_eq!(state.active_count(), 1);
+
+        assert!(state.apply("phone-1", StateEvent::Disconnected));
+        assert!(state.sessions.is_empty());
+        assert_eq!(*state.get("phone-1"), Idle);
+        assert!(!state.apply("phone-1", StateEvent::Disconnected));
+    }
+
+    #[test]
+    fn report_names_the_failed_device() {
+        let mut state = ReceiverState::default();
+        state.apply("phone-1", StateEvent::ConnectRequested(phone()));
+        state.apply("phone-1", StateEvent::ConnectFailed("занят".into()));
+        let report = StatusReport::new(&state);
+        assert_eq!(report.sessions[0].state, "failed");
+        assert_eq!(report.sessions[0].name.as_deref(), Some("Pixel"));
+        assert_eq!(report.sessions[0].reason.as_deref(), Some("занят"));
+    }
+
+    #[test]
+    fn report_lines_match_state_labels() {
+        let mut state = ReceiverState::default();
+        state.apply("phone-1", StateEvent::ConnectRequested(phone()));
+        state.apply("phone-1", StateEvent::ConnectSucceeded);
+        state.apply("phone-1", StateEvent::RetryScheduled(2));
+        let reconnecting = state.get("phone-1").clone();
+
+        let report = StatusReport::new(&state);
+        assert_eq!(report.lines(), [reconnecting.label("Pixel").unwrap()], "строка одна и та же в трее и в консоли");
+
+        // Строку для отчёта, пришедшего по IPC, собираем так же
+        let json = serde_json::to_string(&report).unwrap();
+        let remote: StatusReport = serde_json::from_str(&json).unwrap();
+        assert_eq!(remote.lines(), report.lines());
+        assert_eq!(Idle.label("Pixel"), None);
+    }
+}
@@ -0,0 +1,484 @@
+use crate::updater::Updater;
+use bt_audio_receiver::backend::{AudioSinkBackend, BTDevice};
+use bt_audio_receiver::config::{remember_last_device, remember_volumes, Config, ConfigStore};
+use bt_audio_receiver::http_api;
+use bt_audio_receiver::i18n::{Lang, Msg};
+use bt_audio_receiver::ipc::{IpcContext, IpcServer};
+use bt_audio_receiver::logging;
+use bt_audio_receiver::media::{media_worker, metadata_worker, tooltip, MediaAction};
+use bt_audio_receiver::media_session::SystemMediaControl;
+use bt_audio_receiver::menu::{self as tray_menu, MenuClick, MenuEntry};
+use bt_audio_receiver::meter::{self, IconState, LevelMeter, MeterDisplay};
+use bt_audio_receiver::recording;
+use bt_audio_receiver::state::ReceiverState;
+use bt_audio_receiver::worker::{background_worker, AppCommand};
+
+use anyhow::Result;
+use tokio::sync::{mpsc, watch};
+
+use winreg::enums::*;
+use winreg::RegKey;
+
+use global_hotkey::hotkey::{Code, HotKey};
+use global_hotkey::{GlobalHotKeyEvent, GlobalHotKeyManager, HotKeyState};
+use std::time::{Duration, Instant};
+use tray_icon::{
+    menu::{CheckMenuItem, IsMenuItem, Menu, MenuEvent, MenuItem, PredefinedMenuItem, Submenu},
+    TrayIconBuilder, TrayIcon,
+};
+use winit::application::ApplicationHandler;
+use winit::event::WindowEvent;
+use winit::event_loop::{ActiveEventLoop, ControlFlow, EventLoop, EventLoopProxy};
+use winit::platform::windows::EventLoopBuilderExtWindows;
+
+// Как часто обновляется индикатор уровня в значке, пока он показывает звук
+const METER_INTERVAL: Duration = Duration::from_millis(100);
+
+// Что будит цикл событий. Сам по себе он спит: о новом в каналах воркера сообщают
+// задачи из `wake_on`
+enum AppEvent {
+    Menu(MenuEvent),
+    HotKey(GlobalHotKeyEvent),
+    // Другой экземпляр просит завершиться (`--replace`)
+    Shutdown,
+    // Новое состояние или список устройств, звук после тишины
+    Wake,
+}
+
+// Значок из `icon.ico` в RGBA; поверх него рисуется индикатор уровня
+struct BaseIcon {
+    rgba: Vec<u8>,
+    width: u32,
+    height: u32,
+}
+
+// Структура приложения для управления состоянием в цикле событий
+struct BTApp {
+    tray: TrayIcon,
+    rx_devices: watch::Receiver<Vec<BTDevice>>,
+    rx_state: watch::Receiver<ReceiverState>,
+    cmd_tx: mpsc::Sender<AppCommand>,
+    media_tx: mpsc::Sender<MediaAction>,
+    media_keys: Option<MediaKeys>,
+    current_devices: Vec<BTDevice>,
+    current_state: ReceiverState,
+    // Отметки в меню устарели после нажатия (см. `MenuClick::changes_menu`)
+    menu_stale: bool,
+    config: ConfigStore,
+    // Индикатор уровня принятого звука в значке
+    meter: LevelMeter,
+    meter_display: MeterDisplay,
+    meter_at: Instant,
+    icon: BaseIcon,
+    icon_state: IconState,
+}
+
+impl BTApp {
+    // Обновляет индикатор, если пора, и говорит, когда обновить его снова. Таймер нужен,
+    // только пока индикатор показывает звук: в тишине цикл разбудит сам звук, а без
+    // подключения — новое состояние
+    fn update_meter(&mut self) -> Option<Instant> {
+        let elapsed = self.meter_at.elapsed();
+        if elapsed >= METER_INTERVAL {
+            self.meter_at = Instant::now();
+            let connected = self.current_state.connected().next().is_some();
+            let state = self.meter_display.update(self.meter.take(), elapsed, connected);
+            if state != self.icon_state {
+                self.icon_state = state;
+                self.redraw_icon();
+            }
+        }
+        match self.icon_state {
+            IconState::Level { .. } => Some(self.meter_at + METER_INTERVAL),
+            IconState::Silent => {
+                self.meter.wake_on_sound();
+                None
+            }
+            IconState::Idle => None,
+        }
+    }
+
+    fn redraw_icon(&self) {
+        let rgba = meter::render(&self.icon.rgba, self.icon.width, self.icon.height, self.icon_state);
+        match tray_icon::Icon::from_rgba(rgba, self.icon.width, self.icon.height) {
+            Ok(icon) => {
+                let _ = self.tray.set_icon(Some(icon));
+            }
+            Err(e) => log::error!(target: "ui", "{}", Msg::LogIconFailed { error: &e }),
+        }
+    }
+
+    fn send_command(&self, command: AppCommand) {
+        if let Err(e) = self.cmd_tx.try_send(command) {
+            log::error!(target: "ui", "{}", Msg::LogCommandFailed { error: &e });
+        }
+    }
+
+    fn save_config(&self, change: impl FnOnce(&mut Config)) {
+        if let Err(e) = self.config.update(change) {
+            log::error!(target: "config", "{}", Msg::LogSettingsSaveFailed { error: &e });
+        }
+    }
+
+    fn send_media(&self, action: MediaAction) {
+        if let Err(e) = self.media_tx.try_send(action) {
+            log::error!(target: "ui", "{}", Msg::LogCommandFailed { error: &e });
+        }
+    }
+
+    // Нажатие в меню трея
+    fn handle_click(&mut self, event_loop: &ActiveEventLoop, event: MenuEvent) {
+        let config = self.config.get();
+        let Some(click) = MenuClick::parse(event.id.as_ref(), &self.current_devices, &self.current_state, &config) else {
+            return;
+        };
+        self.menu_stale |= click.changes_menu();
+        match click {
+            MenuClick::Quit => event_loop.exit(),
+            MenuClick::CheckUpdate => {
+                tokio::spawn(async {
+                    if let Err(e) = Updater::check_and_update(false).await {
+                        log::error!(target: "updater", "{:#}", e);
+                        show_error_dialog(
+                            &Msg::UpdateErrorTitle.to_string(),
+                            &Msg::UpdateErrorText { error: &e }.to_string(),
+                        );
+                    }
+                });
+            }
+            MenuClick::OpenLogs => {
+                if let Some(dir) = logging::log_dir() {
+                    if let Err(e) = std::process::Command::new("explorer").arg(&dir).spawn() {
+                        log::error!(target: "ui", "{}", Msg::LogOpenFailed { path: &dir.display(), error: &e });
+                    }
+                }
+            }
+            MenuClick::ToggleAutostart => {
+                let key_name = config.autostart.key_name;
+                let current = is_autostart_enabled(&key_name);
+                let _ = set_autostart(&key_name, !current);
+            }
+            MenuClick::Language(language) => {
+                self.save_config(|c| c.ui.language = language);
+                Lang::set_current(language.resolve());
+            }
+            MenuClick::Media(action) => self.send_media(action),
+            MenuClick::Command(command) => self.send_command(command),
+            MenuClick::NightMode(enabled) => {
+                self.save_config(|c| c.night_mode.enabled = enabled);
+                self.send_command(AppCommand::SetNightMode(enabled));
+            }
+            MenuClick::AutoConnect(id, enabled) => {
+                self.save_config(|c| c.devices.entry(id.clone()).or_default().auto_connect = enabled);
+                self.send_command(AppCommand::SetAutoConnect(id, enabled));
+            }
+            MenuClick::Output(id, output) => {
+                self.save_config(|c| c.devices.entry(id.clone()).or_default().output = output.clone());
+                self.send_command(AppCommand::SetOutput(id, output));
+            }
+        }
+    }
+}
+
+impl ApplicationHandler<AppEvent> for BTApp {
+    fn resumed(&mut self, _event_loop: &ActiveEventLoop) {}
+
+    fn window_event(&mut self, _event_loop: &ActiveEventLoop, _window_id: winit::window::WindowId, _event: WindowEvent) {}
+
+    fn user_event(&mut self, event_loop: &ActiveEventLoop, event: AppEvent) {
+        match event {
+            AppEvent::Menu(event) => self.handle_click(event_loop, event),
+            AppEvent::HotKey(event) => {
+                if event.state == HotKeyState::Pressed {
+                    if let Some(action) = self.media_keys.as_ref().and_then(|keys| keys.action(event.id)) {
+                        self.send_media(action);
+                    }
+                }
+            }
+            AppEvent::Shutdown => event_loop.exit(),
+            // Новое из каналов заберёт `about_to_wait`
+            AppEvent::Wake => {}
+        }
+    }
+
+    fn about_to_wait(&mut self, event_loop: &ActiveEventLoop) {
+        let mut changed = std::mem::take(&mut self.menu_stale);
+
+        // 1. Получение новых списков устройств из Bluetooth воркера
+        if self.rx_devices.has_changed().unwrap_or(false) {
+            self.current_devices = self.rx_devices.borrow_and_update().clone();
+            changed = true;
+        }
+
+        // 2. Получение статусов подключения и того, что играет
+        if self.rx_state.has_changed().unwrap_or(false) {
+            let state = self.rx_state.borrow_and_update().clone();
+            if state.now_playing != self.current_state.now_playing {
+                let _ = self.tray.set_tooltip(Some(tooltip(state.now_playing.as_ref())));
+            }
+            self.current_state = state;
+            if let Some(keys) = &mut self.media_keys {
+                keys.set_active(self.current_state.connected().next().is_some());
+            }
+            changed = true;
+        }
+
+        // 3. Если что-то изменилось — перерисовываем меню
+        if changed {
+            let new_menu = build_menu(&self.current_devices, &self.current_state, &self.config.get());
+            self.tray.set_menu(Some(Box::new(new_menu)));
+        }
+
+        // 4. Индикатор уровня в значке; по таймеру цикл просыпается, только пока идёт звук
+        let flow = match self.update_meter() {
+            Some(at) => ControlFlow::WaitUntil(at),
+            None => ControlFlow::Wait,
+        };
+        event_loop.set_control_flow(flow);
+    }
+}
+
+/// Создает трей, запускает воркер поверх переданного бэкенда и крутит цикл событий.
+/// `server` — занятая в `main` точка подключения для команд от других процессов.
+pub async fn run<B: AudioSinkBackend + 'static>(backend: B, config: ConfigStore, server: Option<IpcServer>) -> Result<()> {
+    let (tx_devices, rx_devices) = watch::channel(Vec::<BTDevice>::new());
+    let (tx_state, rx_state) = watch::channel(ReceiverState::default());
+    let (cmd_tx, cmd_rx) = mpsc::channel::<AppCommand>(10);
+    let (shutdown_tx, mut shutdown_rx) = mpsc::channel::<()>(1);
+
+    // Создаем трей
+    let icon = load_icon();
+    let tray = TrayIconBuilder::new()
+        .with_menu(Box::new(build_menu(&[], &ReceiverState::default(), &config.get())))
+        .with_tooltip(tooltip(None))
+        .with_icon(tray_icon::Icon::from_rgba(icon.rgba.clone(), icon.width, icon.height)?)
+        .build()?;
+
+    // Клоны для фонового потока
+    let options = config.get().worker_options();
+    tokio::spawn(remember_last_device(config.clone(), tx_state.subscribe()));
+    tokio::spawn(remember_volumes(config.clone(), tx_state.subscribe()));
+
+    // Команды от второго запуска, скриптов, HTTP API и MQTT
+    let ctx = IpcContext {
+        cmd_tx: cmd_tx.clone(),
+        state: tx_state.subscribe(),
+        devices: rx_devices.clone(),
+        shutdown: shutdown_tx,
+    };
+    let http = config.get().http;
+    if http.enabled {
+        let ctx = ctx.clone();
+        tokio::spawn(async move {
+            if let Err(e) = http_api::serve(http, ctx).await {
+                log::error!(target: "http", "{}", Msg::LogHttpFailed { error: &e });
+            }
+        });
+    }
+    let mqtt = config.get().mqtt;
+    if mqtt.enabled {
+        let ctx = ctx.clone();
+        tokio::spawn(async move {
+            if let Err(e) = bt_audio_receiver::mqtt::run(mqtt, ctx).await {
+                log::error!(target: "mqtt", "{}", Msg::LogMqttError { error: &e });
+            }
+        });
+    }
+    if let Some(server) = server {
+        tokio::spawn(async move {
+            if let Err(e) = server.run(ctx).await {
+                log::error!(target: "ipc", "{}", Msg::LogIpcFailed { error: &e });
+            }
+        });
+    }
+
+    // Управление воспроизведением на телефоне
+    let (media_tx, media_rx) = mpsc::channel::<MediaAction>(10);
+    tokio::spawn(media_worker(SystemMediaControl::new(), media_rx, tx_state.subscribe(), cmd_tx.clone()));
+    tokio::spawn(metadata_worker(SystemMediaControl::new(), tx_state.clone()));
+    let media_keys = config.get().media.global_keys.then(MediaKeys::new).flatten();
+
+    // Запуск воркера Bluetooth
+    let level_meter = backend.level_meter();
+    tokio::spawn(async move {
+        let mut receiver = backend;
+        let _ = background_worker(&mut receiver, options, tx_devices, tx_state, cmd_rx).await;
+    });
+
+    // Настройка EventLoop: нажатия и всё новое из каналов приходят в него событиями
+    let event_loop = EventLoop::<AppEvent>::with_user_event().with_any_thread(true).build()?;
+    event_loop.set_control_flow(ControlFlow::Wait);
+    let proxy = event_loop.create_proxy();
+    let menu_proxy = proxy.clone();
+    MenuEvent::set_event_handler(Some(move |event| {
+        let _ = menu_proxy.send_event(AppEvent::Menu(event));
+    }));
+    let hotkey_proxy = proxy.clone();
+    GlobalHotKeyEvent::set_event_handler(Some(move |event| {
+        let _ = hotkey_proxy.send_event(AppEvent::HotKey(event));
+    }));
+    tokio::spawn(wake_on(proxy.clone(), rx_devices.clone()));
+    tokio::spawn(wake_on(proxy.clone(), rx_state.clone()));
+    let shutdown_proxy = proxy.clone();
+    tokio::spawn(async move {
+        if shutdown_rx.recv().await.is_some() {
+            let _ = shutdown_proxy.send_event(AppEvent::Shutdown);
+        }
+    });
+    let meter = level_meter.clone();
+    tokio::spawn(async move {
+        loop {
+            meter.sound().await;
+            if proxy.send_event(AppEvent::Wake).is_err() {
+                break;
+            }
+        }
+    });
+
+    let mut app = BTApp {
+        tray,
+        rx_devices,
+        rx_state,
+        cmd_tx: cmd_tx.clone(),
+        media_tx,
+        media_keys,
+        current_devices: Vec::new(),
+        current_state: ReceiverState::default(),
+        menu_stale: false,
+        config,
+        meter: level_meter,
+        meter_display: MeterDisplay::default(),
+        meter_at: Instant::now(),
+        icon,
+        icon_state: IconState::Idle,
+    };
+
+    event_loop.run_app(&mut app)?;
+
+    // Отпускаем телефоны перед выходом, иначе они ещё какое-то время держат пропавший приёмник
+    let _ = cmd_tx.send(AppCommand::DisconnectAll).await;
+    let idle = app.rx_state.wait_for(|state| state.active_count() == 0);
+    let _ = tokio::time::timeout(Duration::from_secs(5), idle).await;
+    let written = tokio::task::spawn_blocking(recording::wait_all);
+    let _ = tokio::time::timeout(Duration::from_secs(5), written).await;
+
+    Ok(())
+}
+
+// Будит цикл событий, когда в канале воркера появляется новое: сам канал его не будит
+async fn wake_on<T>(proxy: EventLoopProxy<AppEvent>, mut rx: watch::Receiver<T>) {
+    while rx.changed().await.is_ok() {
+        if proxy.send_event(AppEvent::Wake).is_err() {
+            break;
+        }
+    }
+}
+
+// Медиаклавиши перехватываются, только пока подключён телефон: без него они нужны тем,
+// кто играет на компьютере. Громкость не перехватываем вовсе: ею пользуется всё остальное
+struct MediaKeys {
+    manager: GlobalHotKeyManager,
+    keys: Vec<(HotKey, MediaAction)>,
+    active: bool,
+}
+
+impl MediaKeys {
+    fn new() -> Option<Self> {
+        let manager = GlobalHotKeyManager::new()
+            .map_err(|e| log::warn!(target: "media", "{}", Msg::LogHotkeysFailed { error: &e }))
+            .ok()?;
+        let keys = [
+            (Code::MediaPlayPause, MediaAction::PlayPause),
+            (Code::MediaTrackNext, MediaAction::Next),
+            (Code::MediaTrackPrevious, MediaAction::Previous),
+        ];
+        let keys = keys.into_iter().map(|(code, action)| (HotKey::new(None, code), action)).collect();
+        Some(Self { manager, keys, active: false })
+    }
+
+    fn set_active(&mut self, active: bool) {
+        if active == self.active {
+            return;
+        }
+        self.active = active;
+        for (hotkey, _) in &self.keys {
+            let result = match active {
+                true => self.manager.register(*hotkey),
+                false => self.manager.unregister(*hotkey),
+            };
+            if let Err(e) = result {
+                log::warn!(target: "media", "{}", Msg::LogHotkeysFailed { error: &e });
+            }
+        }
+    }
+
+    fn action(&self, id: u32) -> Option<MediaAction> {
+        let (_, action) = self.keys.iter().find(|(hotkey, _)| hotkey.id() == id)?;
+        self.active.then_some(*action)
+    }
+}
+
+pub fn show_error_dialog(title: &str, message: &str) {
+    use native_dialog::{MessageDialog, MessageType};
+    MessageDialog::new()
+        .set_type(MessageType::Error)
+        .set_title(title)
+        .set_text(message)
+        .show_alert()
+        .unwrap();
+}
+
+fn set_autostart(key_name: &str, enable: bool) -> Result<()> {
+    let hkcu = RegKey::predef(HKEY_CURRENT_USER);
+    let path = r"Software\Microsoft\Windows\CurrentVersion\Run";
+    let (key, _) = hkcu.create_subkey(path)?;
+
+    if enable {
+        let current_exe = std::env::current_exe()?;
+        key.set_value(key_name, &current_exe.to_str().unwrap_or(""))?;
+    } else {
+        let _ = key.delete_value(key_name);
+    }
+    Ok(())
+}
+
+fn is_autostart_enabled(key_name: &str) -> bool {
+    let hkcu = RegKey::predef(HKEY_CURRENT_USER);
+    let path = r"Software\Microsoft\Windows\CurrentVersion\Run";
+    if let Ok(key) = hkcu.open_subkey(path) {
+        let val: String = key.get_value(key_name).unwrap_or_default();
+        return !val.is_empty();
+    }
+    false
+}
+
+fn build_menu(devices: &[BTDevice], state: &ReceiverState, config: &Config) -> Menu {
+    let menu = Menu::new();
+    let autostart = is_autostart_enabled(&config.autostart.key_name);
+    for entry in tray_menu::build(devices, state, config, autostart) {
+        let _ = menu.append(menu_item(entry).as_ref());
+    }
+    menu
+}
+
+fn menu_item(entry: MenuEntry) -> Box<dyn IsMenuItem> {
+    match entry {
+        MenuEntry::Item { id, title, enabled } => Box::new(MenuItem::with_id(id, title, enabled, None)),
+        MenuEntry::Check { id, title, checked } => Box::new(CheckMenuItem::with_id(id, title, true, checked, None)),
+        MenuEntry::Separator => Box::new(PredefinedMenuItem::separator()),
+        MenuEntry::Submenu { title, entries } => {
+            let submenu = Submenu::new(title, true);
+            for entry in entries {
+                let _ = submenu.append(menu_item(entry).as_ref());
+            }
+            Box::new(submenu)
+        }
+    }
+}
+
+fn load_icon() -> BaseIcon {
+    let bytes = include_bytes!("icon.ico");
+    let image = image::load_from_memory(bytes).expect("icon.ico error").into_rgba8();
+    let (width, height) = image.dimensions();
+    BaseIcon { rgba: image.into_raw(), width, height }
+}
\ No newline at end of file
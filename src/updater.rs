@@ -1,11 +1,21 @@
 use self_update::cargo_crate_version;
 use native_dialog::{MessageDialog, MessageType};
+use bt_audio_receiver::i18n::Msg;
 
 pub struct Updater;
 
+/// Результат проверки обновлений.
+pub enum UpdateCheck {
+    NoReleases,
+    UpToDate,
+    Available(String),
+}
+
 impl Updater {
-    pub async fn check_and_update(silent: bool) -> anyhow::Result<()> {
+    /// Сравнивает установленную версию с последним релизом на GitHub, ничего не показывая.
+    pub async fn check() -> anyhow::Result<UpdateCheck> {
         let current_ver = cargo_crate_version!();
+        log::info!(target: "updater", "{}", Msg::LogUpdateCheck { version: &current_ver });
 
         // Используем ReleaseList::fetch(), так как он возвращает пустой массив [],
         // если релизов нет, а не ошибку 403/404.
@@ -13,49 +23,64 @@ impl Updater {
             let rels = self_update::backends::github::ReleaseList::configure()
                 .repo_owner("Kovalssky")
                 .repo_name("bluetooth_audio_receiver").build()
-                .map_err(|e| anyhow::anyhow!("Ошибка конфигурации: {}", e))?
+                .map_err(|e| anyhow::anyhow!("{}", Msg::UpdaterConfigError { error: &e }))?
                 .fetch()
-                .map_err(|e| anyhow::anyhow!("Ошибка запроса к GitHub (возможно, лимит запросов): {}", e))?;
+                .map_err(|e| anyhow::anyhow!("{}", Msg::UpdaterRequestError { error: &e }))?;
             Ok(rels)
-        }).await.map_err(|e| anyhow::anyhow!("Ошибка потока: {}", e))??;
+        }).await.map_err(|e| anyhow::anyhow!("{}", Msg::UpdaterThreadError { error: &e }))??;
 
         // --- ОБРАБОТКА ОТСУТСТВИЯ РЕЛИЗОВ ---
-        if releases.is_empty() {
-            if !silent {
-                Self::show_info("Обновления", "На GitHub пока нет доступных выпусков (релизов).");
-            }
-            return Ok(()); // Просто выходим без ошибки
-        }
-
         // Если релизы есть, берем самый свежий (первый в списке)
-        let latest = &releases[0];
+        let Some(latest) = releases.first() else {
+            log::info!(target: "updater", "{}", Msg::LogNoReleases);
+            return Ok(UpdateCheck::NoReleases);
+        };
 
         // Сравниваем версии (v0.1.0 > 0.1.0)
         let is_greater = self_update::version::bump_is_greater(current_ver, &latest.version)
             .unwrap_or(false);
 
         if is_greater {
-            let confirmed = MessageDialog::new()
-                .set_type(MessageType::Info)
-                .set_title("🆙 Доступно обновление")
-                .set_text(&format!(
-                    "Найдена новая версия: v{}\nВаша версия: v{}\n\nЖелаете обновить программу?",
-                    latest.version, current_ver
-                ))
-                .show_confirm()
-                .unwrap_or(false);
+            log::info!(target: "updater", "{}", Msg::LogUpdateAvailable { version: &latest.version });
+            Ok(UpdateCheck::Available(latest.version.clone()))
+        } else {
+            Ok(UpdateCheck::UpToDate)
+        }
+    }
+
+    pub async fn check_and_update(silent: bool) -> anyhow::Result<()> {
+        let current_ver = cargo_crate_version!();
+
+        match Self::check().await? {
+            UpdateCheck::NoReleases => {
+                if !silent {
+                    Self::show_info(&Msg::NoReleasesTitle.to_string(), &Msg::NoReleasesText.to_string());
+                }
+            }
+            UpdateCheck::UpToDate => {
+                if !silent {
+                    Self::show_info(&Msg::UpToDateTitle.to_string(), &Msg::UpToDateText.to_string());
+                }
+            }
+            UpdateCheck::Available(latest) => {
+                let confirmed = MessageDialog::new()
+                    .set_type(MessageType::Info)
+                    .set_title(&Msg::UpdateAvailableTitle.to_string())
+                    .set_text(&Msg::UpdateAvailableText { latest: &latest, current: &current_ver }.to_string())
+                    .show_confirm()
+                    .unwrap_or(false);
 
-            if confirmed {
-                Self::perform_update().await?;
+                if confirmed {
+                    Self::perform_update().await?;
+                    log::info!(target: "updater", "{}", Msg::LogUpdateInstalled { version: &latest });
+                }
             }
-        } else if !silent {
-            Self::show_info("✅ Обновлений нет", "У вас установлена самая последняя версия.");
         }
 
         Ok(())
     }
 
-    async fn perform_update() -> anyhow::Result<()> {
+    pub async fn perform_update() -> anyhow::Result<()> {
         tokio::task::spawn_blocking(|| -> anyhow::Result<()> {
             self_update::backends::github::Update::configure()
                 .repo_owner("Kovalssky")
@@ -65,11 +90,11 @@ impl Updater {
                 .show_download_progress(true)
                 .current_version(cargo_crate_version!())
                 .build()
-                .map_err(|e| anyhow::anyhow!("Ошибка сборки апдейтера: {}", e))?
+                .map_err(|e| anyhow::anyhow!("{}", Msg::UpdaterBuildError { error: &e }))?
                 .update()
-                .map_err(|e| anyhow::anyhow!("Ошибка при замене файла: {}", e))?;
+                .map_err(|e| anyhow::anyhow!("{}", Msg::UpdaterReplaceError { error: &e }))?;
             Ok(())
-        }).await.map_err(|e| anyhow::anyhow!("Критическая ошибка потока: {}", e))??;
+        }).await.map_err(|e| anyhow::anyhow!("{}", Msg::UpdaterFatalThreadError { error: &e }))??;
 
         Ok(())
     }
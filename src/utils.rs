@@ -3,6 +3,7 @@ use winreg::RegKey;
 use std::process::Command;
 use std::env;
 use anyhow::Result;
+use bt_audio_receiver::i18n::Msg;
 
 const SINK_PATH: &str = r"SYSTEM\CurrentControlSet\Control\Bluetooth\Audio\A2dp\Sink";
 const BTHPORT_PATH: &str = r"SYSTEM\CurrentControlSet\Services\BTHPORT\Parameters";
@@ -28,22 +29,19 @@ pub fn ensure_registry_settings() -> Result<()> {
     } else { needs_fix = true; }
 
     if needs_fix {
-        let args: Vec<String> = env::args().collect();
-        if args.contains(&"--fix-registry".to_string()) {
-            run_registry_fix()?;
-        } else {
-            println!("[REG] Настройки неоптимальны. Запрашиваю права администратора...");
-            elevate_self()?;
-            std::process::exit(0);
-        }
+        // Исправляет реестр отдельный процесс с правами администратора (`fix-registry`),
+        // а этот дожидается его и продолжает работу с обычными правами
+        log::warn!(target: "registry", "{}", Msg::RegistryNeedsFix);
+        elevate_self()?;
     } else {
-        println!("[REG] Реестр в порядке (DisableSnoop=1, RemoteWake=1).");
+        log::info!(target: "registry", "{}", Msg::RegistryOk);
     }
 
     Ok(())
 }
 
-fn run_registry_fix() -> Result<()> {
+/// Записывает нужные значения в HKLM. Требует прав администратора.
+pub fn run_registry_fix() -> Result<()> {
     let hklm = RegKey::predef(HKEY_LOCAL_MACHINE);
 
     // ВАЖНО: используем create_subkey_with_flags с KEY_ALL_ACCESS для записи
@@ -62,7 +60,7 @@ fn run_registry_fix() -> Result<()> {
     let (bth_key, _) = hklm.create_subkey_with_flags(BTHA2DP_PATH, KEY_ALL_ACCESS)?;
     bth_key.set_value("DefaultDomainPolicy", &1u32)?;
 
-    println!("[REG] Настройки успешно применены. Изменения вступят в силу после перезапуска Bluetooth.");
+    log::info!(target: "registry", "{}", Msg::RegistryFixed);
     Ok(())
 }
 
@@ -73,13 +71,13 @@ fn elevate_self() -> Result<()> {
     let status = Command::new("powershell")
         .arg("-Command")
         .arg(format!(
-            "Start-Process -FilePath '{}' -ArgumentList '--fix-registry' -Verb RunAs -Wait",
+            "Start-Process -FilePath '{}' -ArgumentList 'fix-registry' -Verb RunAs -Wait",
             current_exe.display()
         ))
         .status()?;
 
     if !status.success() {
-        anyhow::bail!("Пользователь отклонил запрос UAC.");
+        anyhow::bail!("{}", Msg::UacDeclined);
     }
     Ok(())
 }
\ No newline at end of file
@@ -0,0 +1,111 @@
+use crate::i18n::Msg;
+use serde::{Deserialize, Serialize};
+use std::fmt::{self, Display};
+use std::str::FromStr;
+
+/// Громкость принятого звука одного устройства.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Volume {
+    /// Проценты, 0..=100.
+    pub level: u8,
+    pub muted: bool,
+}
+
+impl Default for Volume {
+    fn default() -> Self {
+        Self { level: 100, muted: false }
+    }
+}
+
+impl Volume {
+    /// Коэффициент усиления для графа. Квадратичная шкала ближе к тому, как слышит
+    /// ухо: 50% — заметно тише, а не почти так же громко.
+    pub fn gain(self) -> f64 {
+        if self.muted {
+            return 0.0;
+        }
+        let level = f64::from(self.level.min(100)) / 100.0;
+        level * level
+    }
+}
+
+impl Display for Volume {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.muted {
+            true => write!(f, "🔇 {}%", self.level),
+            false => write!(f, "🔊 {}%", self.level),
+        }
+    }
+}
+
+/// Изменение громкости из трея, командной строки или API.
+///
+/// В текстовом виде: `80` — установить, `+10`/`-10` — шаг, `mute`, `unmute`, `toggle`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum VolumeChange {
+    Set(u8),
+    Step(i16),
+    Mute(bool),
+    ToggleMute,
+}
+
+impl VolumeChange {
+    /// Новая громкость. Изменение уровня снимает «без звука», как в Windows.
+    pub fn apply(self, volume: Volume) -> Volume {
+        match self {
+            VolumeChange::Set(level) => Volume { level: level.min(100), muted: false },
+            VolumeChange::Step(step) => {
+                let level = (i16::from(volume.level) + step).clamp(0, 100) as u8;
+                Volume { level, muted: false }
+            }
+            VolumeChange::Mute(muted) => Volume { muted, ..volume },
+            VolumeChange::ToggleMute => Volume { muted: !volume.muted, ..volume },
+        }
+    }
+}
+
+impl FromStr for VolumeChange {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || Msg::VolumeInvalid { value: &s }.to_string();
+        let s = s.trim();
+        match s {
+            "mute" => Ok(VolumeChange::Mute(true)),
+            "unmute" => Ok(VolumeChange::Mute(false)),
+            "toggle" => Ok(VolumeChange::ToggleMute),
+            _ if s.starts_with(['+', '-']) => s.parse().map(VolumeChange::Step).map_err(|_| invalid()),
+            _ => match s.parse::<u8>() {
+                Ok(level) if level <= 100 => Ok(VolumeChange::Set(level)),
+                _ => Err(invalid()),
+            },
+        }
+    }
+}
+
+impl Display for VolumeChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VolumeChange::Set(level) => write!(f, "{level}"),
+            VolumeChange::Step(step) => write!(f, "{step:+}"),
+            VolumeChange::Mute(true) => f.write_str("mute"),
+            VolumeChange::Mute(false) => f.write_str("unmute"),
+            VolumeChange::ToggleMute => f.write_str("toggle"),
+        }
+    }
+}
+
+impl TryFrom<String> for VolumeChange {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl From<VolumeChange> for String {
+    fn from(change: VolumeChange) -> Self {
+        change.to_string()
+    }
+}
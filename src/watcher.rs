@@ -0,0 +1,135 @@
+use crate::backend::{BTDevice, BackendEvent};
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// Применяет событие наблюдателя устройств к списку.
+/// Возвращает `true`, если список действительно изменился.
+pub fn apply_device_event(devices: &mut Vec<BTDevice>, event: &BackendEvent) -> bool {
+    match event {
+        BackendEvent::DeviceAdded(device) | BackendEvent::DeviceUpdated(device) => {
+            match devices.iter_mut().find(|d| d.id == device.id) {
+                Some(existing) if existing == device => false,
+                Some(existing) => {
+                    *existing = device.clone();
+                    true
+                }
+                // Updated для неизвестного устройства тоже добавляем: значит, Added мы пропустили
+                None => {
+                    devices.push(device.clone());
+                    true
+                }
+            }
+        }
+        BackendEvent::DeviceRemoved(id) => {
+            let before = devices.len();
+            devices.retain(|d| &d.id != id);
+            devices.len() != before
+        }
+        _ => false,
+    }
+}
+
+/// Откладывает действие, пока события не утихнут на `quiet`, но не дольше
+/// `max_wait` с первого события — иначе непрерывный поток событий
+/// не дал бы меню обновиться вообще.
+#[derive(Clone, Debug)]
+pub struct Debouncer {
+    quiet: Duration,
+    max_wait: Duration,
+    first: Option<Instant>,
+    last: Option<Instant>,
+}
+
+impl Debouncer {
+    pub fn new(quiet: Duration, max_wait: Duration) -> Self {
+        Self { quiet, max_wait, first: None, last: None }
+    }
+
+    /// Отмечает очередное событие.
+    pub fn touch(&mut self, now: Instant) {
+        self.first.get_or_insert(now);
+        self.last = Some(now);
+    }
+
+    /// Когда пора выполнить отложенное действие (если что-то накопилось).
+    pub fn deadline(&self) -> Option<Instant> {
+        let (first, last) = (self.first?, self.last?);
+        Some((last + self.quiet).min(first + self.max_wait))
+    }
+
+    /// Сбрасывает накопленное после выполнения действия.
+    pub fn reset(&mut self) {
+        self.first = None;
+        self.last = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn device(id: &str, name: &str) -> BTDevice {
+        BTDevice { id: id.to_string(), name: name.to_string() }
+    }
+
+    fn ms(ms: u64) -> Duration {
+        Duration::from_millis(ms)
+    }
+
+    #[test]
+    fn events_update_the_list() {
+        let mut devices = Vec::new();
+        assert!(apply_device_event(&mut devices, &BackendEvent::DeviceAdded(device("a", "Pixel"))));
+        assert!(!apply_device_event(&mut devices, &BackendEvent::DeviceAdded(device("a", "Pixel"))));
+        assert!(apply_device_event(&mut devices, &BackendEvent::DeviceUpdated(device("a", "Pixel 8"))));
+        // Added пропустили — Updated всё равно добавляет
+        assert!(apply_device_event(&mut devices, &BackendEvent::DeviceUpdated(device("b", "iPhone"))));
+        assert_eq!(devices, [device("a", "Pixel 8"), device("b", "iPhone")]);
+
+        assert!(apply_device_event(&mut devices, &BackendEvent::DeviceRemoved("a".into())));
+        assert!(!apply_device_event(&mut devices, &BackendEvent::DeviceRemoved("a".into())));
+        assert!(!apply_device_event(&mut devices, &BackendEvent::Connected("b".into())));
+        assert_eq!(devices, [device("b", "iPhone")]);
+    }
+
+    #[test]
+    fn nothing_pending_without_events() {
+        let debouncer = Debouncer::new(ms(300), ms(2000));
+        assert_eq!(debouncer.deadline(), None);
+    }
+
+    #[test]
+    fn waits_for_quiet() {
+        let start = Instant::now();
+        let mut debouncer = Debouncer::new(ms(300), ms(2000));
+        debouncer.touch(start);
+        assert_eq!(debouncer.deadline(), Some(start + ms(300)));
+        // Каждое новое событие отодвигает срок
+        debouncer.touch(start + ms(200));
+        assert_eq!(debouncer.deadline(), Some(start + ms(500)));
+    }
+
+    #[test]
+    fn steady_stream_still_fires_after_max_wait() {
+        let start = Instant::now();
+        let mut debouncer = Debouncer::new(ms(300), ms(2000));
+        for i in 0..30 {
+            debouncer.touch(start + ms(i * 100));
+        }
+        assert_eq!(debouncer.deadline(), Some(start + ms(2000)));
+    }
+
+    #[test]
+    fn reset_starts_over() {
+        let start = Instant::now();
+        let mut debouncer = Debouncer::new(ms(300), ms(2000));
+        debouncer.touch(start);
+        debouncer.touch(start + ms(1900));
+        debouncer.reset();
+        assert_eq!(debouncer.deadline(), None);
+
+        // Срок max_wait считается от первого события после сброса
+        debouncer.touch(start + ms(2500));
+        assert_eq!(debouncer.deadline(), Some(start + ms(2800)));
+    }
+}
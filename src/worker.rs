@@ -0,0 +1,667 @@
+use crate::backend::{AudioSinkBackend, BTDevice, BackendEvent};
+use crate::dsp::DspSettings;
+use crate::dynamics::{CompressorSettings, LimiterSettings};
+use crate::i18n::Msg;
+use crate::priority::Priority;
+use crate::reconnect::{Backoff, ReconnectPolicy};
+use crate::recording::{self, RecordingSettings};
+use crate::state::{ConnectionState, ReceiverState, StateEvent};
+use crate::volume::{Volume, VolumeChange};
+use crate::watcher::{apply_device_event, Debouncer};
+use anyhow::Result;
+use std::collections::{HashMap, HashSet};
+use std::time::Duration;
+use tokio::sync::{broadcast, mpsc, watch};
+use tokio::time::{sleep_until, Instant};
+
+/// Команды воркеру. Устройства указываются по `BTDevice::id`, а не по имени:
+/// имена не уникальны и могут меняться.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum AppCommand {
+    /// Подключить устройство. Если свободных мест нет, отключается самое давнее.
+    Connect(String),
+    Disconnect(String),
+    DisconnectAll,
+    Scan,
+    Reconnect(String),
+    /// Убрать сообщение о неудачном соединении, не трогая автоподключение.
+    Dismiss(String),
+    /// Включить/выключить автоподключение устройства.
+    SetAutoConnect(String, bool),
+    /// Изменить громкость устройства; `None` — последнего подключённого.
+    SetVolume(Option<String>, VolumeChange),
+    /// Направить звук устройства на выход `AudioOutput::id`; `None` — на выход по умолчанию.
+    SetOutput(String, Option<String>),
+    /// Начать или закончить запись всех подключённых устройств.
+    SetRecording(bool),
+    /// Включить или выключить ночной режим.
+    SetNightMode(bool),
+}
+
+/// Настройки воркера, не зависящие от бэкенда.
+#[derive(Clone, Debug)]
+pub struct WorkerOptions {
+    pub reconnect: ReconnectPolicy,
+    /// Индивидуальные правила переподключения по `BTDevice::id`.
+    pub device_reconnect: HashMap<String, ReconnectPolicy>,
+    /// Устройства, к которым подключаемся сами, когда приёмник свободен.
+    pub auto_connect: HashSet<String>,
+    /// Последнее подключённое устройство — среди автоподключаемых оно в приоритете.
+    pub last_device: Option<String>,
+    pub auto_connect_retry: Duration,
+    /// Список приоритетов: свободный приёмник выбирает первое доступное устройство из него.
+    pub priority: Vec<String>,
+    /// Переключаться на более приоритетное устройство, когда оно появляется.
+    pub preempt: bool,
+    /// Сколько устройств может быть подключено одновременно.
+    pub max_connections: usize,
+    /// Запомненная громкость по `BTDevice::id`.
+    pub volumes: HashMap<String, Volume>,
+    /// Громкость устройств, для которых ничего не запомнено.
+    pub default_volume: Volume,
+    /// Выбранное устройство вывода (`AudioOutput::id`) по `BTDevice::id`.
+    pub outputs: HashMap<String, String>,
+    pub recording: RecordingSettings,
+    /// Обработка звука (эквалайзер) по `BTDevice::id`.
+    pub dsp: HashMap<String, DspSettings>,
+    /// Обработка звука всего, что играет на выходе, по `AudioOutput::id`.
+    pub output_dsp: HashMap<String, DspSettings>,
+    /// Лимитер для всего принятого звука.
+    pub limiter: Option<LimiterSettings>,
+    pub night_mode: CompressorSettings,
+    /// Ночной режим включён при запуске.
+    pub night_mode_enabled: bool,
+}
+
+impl Default for WorkerOptions {
+    fn default() -> Self {
+        Self {
+            reconnect: ReconnectPolicy::default(),
+            device_reconnect: HashMap::new(),
+            auto_connect: HashSet::new(),
+            last_device: None,
+            auto_connect_retry: Duration::from_secs(30),
+            priority: Vec::new(),
+            preempt: false,
+            max_connections: 1,
+            volumes: HashMap::new(),
+            default_volume: Volume::default(),
+            outputs: HashMap::new(),
+            recording: RecordingSettings::default(),
+            dsp: HashMap::new(),
+            output_dsp: HashMap::new(),
+            limiter: None,
+            night_mode: CompressorSettings::night_mode(),
+            night_mode_enabled: false,
+        }
+    }
+}
+
+/// Фоновый цикл: выполняет команды из трея и следит за событиями бэкенда.
+/// Завершается, когда закрыт канал команд.
+pub async fn background_worker<B: AudioSinkBackend>(
+    receiver: &mut B,
+    options: WorkerOptions,
+    tx_dev: watch::Sender<Vec<BTDevice>>,
+    tx_state: watch::Sender<ReceiverState>,
+    mut cmd_rx: mpsc::Receiver<AppCommand>,
+) -> Result<()> {
+    let mut events = receiver.subscribe();
+    let mut worker = Worker {
+        receiver,
+        options,
+        tx_dev,
+        tx_state,
+        pending: HashMap::new(),
+        devices: Vec::new(),
+        // Меню перестраиваем не чаще, чем события наблюдателя успокоятся
+        devices_debounce: Debouncer::new(Duration::from_millis(300), Duration::from_secs(2)),
+        auto_paused: false,
+        paused: HashSet::new(),
+        manual: HashSet::new(),
+        auto_retry: HashMap::new(),
+        applied_dsp: HashMap::new(),
+    };
+
+    let night_mode = worker.options.night_mode_enabled;
+    worker.tx_state.send_if_modified(|state| std::mem::replace(&mut state.night_mode, night_mode) != night_mode);
+
+    // Начальное сканирование, дальше список поддерживает наблюдатель
+    worker.scan().await;
+    if let Err(e) = worker.receiver.watch_devices() {
+        log::warn!(target: "receiver", "{}", Msg::LogWatchUnavailable { error: &e });
+    }
+    worker.auto_connect().await;
+
+    loop {
+        let retry_at = worker.pending.values().map(|p| p.deadline).min();
+        let publish_at = worker.devices_debounce.deadline();
+        let auto_at = worker.auto_retry.values().min().copied();
+
+        tokio::select! {
+            cmd = cmd_rx.recv() => {
+                let Some(cmd) = cmd else { break };
+                worker.handle_command(cmd).await;
+            }
+            event = events.recv() => match event {
+                Ok(event) => worker.handle_event(event).await,
+                // Пропустили события — проще перечитать список целиком
+                Err(broadcast::error::RecvError::Lagged(_)) => worker.scan().await,
+                // Бэкенд уничтожен — дальше работать не с чем
+                Err(broadcast::error::RecvError::Closed) => break,
+            },
+            _ = sleep_until(retry_at.unwrap_or_else(Instant::now)), if retry_at.is_some() => {
+                worker.retry().await;
+            }
+            _ = sleep_until(publish_at.unwrap_or_else(Instant::now)), if publish_at.is_some() => {
+                worker.publish_devices();
+            }
+            _ = sleep_until(auto_at.unwrap_or_else(Instant::now)), if auto_at.is_some() => {
+                let now = Instant::now();
+                worker.auto_retry.retain(|_, at| *at > now);
+                worker.auto_connect().await;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+struct Worker<'a, B> {
+    receiver: &'a mut B,
+    options: WorkerOptions,
+    tx_dev: watch::Sender<Vec<BTDevice>>,
+    // Текущее состояние хранится прямо в watch-канале: UI всегда видит последнее
+    tx_state: watch::Sender<ReceiverState>,
+    // Запланированные попытки переподключения по `BTDevice::id`
+    pending: HashMap<String, PendingReconnect>,
+    devices: Vec<BTDevice>,
+    devices_debounce: Debouncer,
+    // Пользователь отключил всё — не подключаемся обратно, пока он не попросит
+    auto_paused: bool,
+    // Устройства, которые пользователь отключил по одному
+    paused: HashSet<String>,
+    // Соединения, которые пользователь выбрал сам: их не вытесняют более приоритетные
+    manual: HashSet<String>,
+    // Устройства, к которым автоподключение не смогло подключиться, и когда пробовать снова.
+    // Появление и обновление устройства этот срок не сокращают
+    auto_retry: HashMap<String, Instant>,
+    // Обработка, выставленная подключённым устройствам
+    applied_dsp: HashMap<String, DspSettings>,
+}
+
+// Запланированная автоматическая попытка переподключения
+struct PendingReconnect {
+    device: BTDevice,
+    backoff: Backoff,
+    deadline: Instant,
+}
+
+impl<B: AudioSinkBackend> Worker<'_, B> {
+    async fn handle_command(&mut self, cmd: AppCommand) {
+        match cmd {
+            AppCommand::Scan => self.scan().await,
+            AppCommand::Connect(id) => {
+                // Явное действие пользователя отменяет автоматические попытки
+                self.pending.remove(&id);
+                self.paused.remove(&id);
+                self.auto_retry.remove(&id);
+                self.auto_paused = false;
+                if self.state_of(&id).is_active() {
+                    return;
+                }
+                let Some(target) = self.find_device(&id).await else { return };
+                self.make_room().await;
+                self.connect(target).await;
+                if self.state_of(&id).is_connected() {
+                    self.manual.insert(id);
+                }
+            }
+            AppCommand::Disconnect(id) => {
+                self.paused.insert(id.clone());
+                self.disconnect(&id).await;
+            }
+            AppCommand::DisconnectAll => {
+                self.auto_paused = true;
+                self.auto_retry.clear();
+                let ids: Vec<String> = self.tx_state.borrow().sessions.iter().map(|(id, _)| id.clone()).collect();
+                for id in ids {
+                    self.disconnect(&id).await;
+                }
+            }
+            AppCommand::Reconnect(id) => {
+                self.pending.remove(&id);
+                self.auto_retry.remove(&id);
+                let Some(target) = self.find_device(&id).await else { return };
+                // Переподключение занятого места не требует нового, а для остальных — как Connect
+                if !self.state_of(&id).is_active() {
+                    self.make_room().await;
+                }
+                self.transition(&id, StateEvent::ReconnectRequested(target.clone()));
+                let result = self.receiver.reconnect(&target).await;
+                self.finish_connect(&target, result).await;
+                if self.state_of(&id).is_connected() {
+                    self.manual.insert(id);
+                }
+            }
+            AppCommand::Dismiss(id) => {
+                if matches!(self.state_of(&id), ConnectionState::Failed { .. }) {
+                    self.transition(&id, StateEvent::Disconnected);
+                }
+            }
+            AppCommand::SetAutoConnect(id, enabled) => {
+                if enabled {
+                    self.paused.remove(&id);
+                    self.auto_retry.remove(&id);
+                    self.options.auto_connect.insert(id);
+                    self.auto_paused = false;
+                    self.auto_connect().await;
+                } else {
+                    self.options.auto_connect.remove(&id);
+                }
+            }
+            AppCommand::SetVolume(id, change) => {
+                let id = id.or_else(|| self.tx_state.borrow().connected().last().map(|d| d.id.clone()));
+                let Some(id) = id else { return };
+                let volume = change.apply(self.volume_for(&id));
+                self.options.volumes.insert(id.clone(), volume);
+                self.apply_volume(&id, volume);
+            }
+            AppCommand::SetOutput(id, output) => {
+                match &output {
+                    Some(output) => self.options.outputs.insert(id.clone(), output.clone()),
+                    None => self.options.outputs.remove(&id),
+                };
+                let routed = self.tx_state.borrow().routes.get(&id).cloned();
+                if let ConnectionState::Connected { device } = self.state_of(&id) {
+                    if routed != output {
+                        self.route(&id, output).await;
+                        // С новым графом громкость могла стать доступной или пропасть
+                        self.apply_volume(&id, self.volume_for(&id));
+                        self.apply_dsp(&device).await;
+                    }
+                }
+            }
+            AppCommand::SetRecording(enabled) => {
+                if !self.tx_state.send_if_modified(|state| std::mem::replace(&mut state.recording, enabled) != enabled) {
+                    return;
+                }
+                let connected: Vec<BTDevice> = self.tx_state.borrow().connected().cloned().collect();
+                for device in connected {
+                    match enabled {
+                        true => self.start_recording(&device),
+                        // Отпущенный отправитель сам закроет файл
+                        false => {
+                            let _ = self.receiver.set_tap(&device.id, None);
+                        }
+                    }
+                }
+            }
+            AppCommand::SetNightMode(enabled) => {
+                if self.tx_state.send_if_modified(|state| std::mem::replace(&mut state.night_mode, enabled) != enabled) {
+                    let connected: Vec<BTDevice> = self.tx_state.borrow().connected().cloned().collect();
+                    for device in connected {
+                        self.apply_dsp(&device).await;
+                    }
+                }
+            }
+        }
+    }
+
+    async fn handle_event(&mut self, event: BackendEvent) {
+        if apply_device_event(&mut self.devices, &event) {
+            self.devices_debounce.touch(Instant::now());
+            // Устройство появилось или вернулось в зону — может, пора подключиться
+            if matches!(event, BackendEvent::DeviceAdded(_) | BackendEvent::DeviceUpdated(_)) {
+                self.auto_connect().await;
+                self.maybe_preempt().await;
+            }
+        }
+
+        if let BackendEvent::ConnectionLost(id) = &event {
+            let lost = match self.state_of(id) {
+                state if state.is_connected() => state.device().cloned(),
+                _ => None,
+            };
+            if let Some(device) = lost {
+                log::warn!(target: "receiver", "{}", Msg::ConnectionLost { name: &device.name });
+                let policy = self.policy_for(&device.id);
+                self.schedule_retry(device, Backoff::new(policy)).await;
+            }
+        }
+
+        // Выбранный выход пропал — звук уходит на выход по умолчанию. Выбор при этом
+        // не забываем: при следующем подключении попробуем снова.
+        if let BackendEvent::OutputLost(id) = event {
+            if let ConnectionState::Connected { device } = self.state_of(&id) {
+                log::warn!(target: "receiver", "{}", Msg::LogOutputLost { name: &device.name });
+                self.route(&id, None).await;
+                self.apply_volume(&id, self.volume_for(&id));
+                self.apply_dsp(&device).await;
+            }
+            self.scan_outputs().await;
+        }
+    }
+
+    /// Выполняет ближайшую по времени запланированную попытку.
+    async fn retry(&mut self) {
+        let Some(id) = self.pending.iter().min_by_key(|(_, p)| p.deadline).map(|(id, _)| id.clone()) else {
+            return;
+        };
+        let Some(pending) = self.pending.remove(&id) else { return };
+        log::info!(
+            target: "receiver",
+            "{}",
+            Msg::LogRetryAttempt { attempt: &pending.backoff.attempt(), name: &pending.device.name }
+        );
+
+        match self.receiver.reconnect(&pending.device).await {
+            Ok(()) => self.succeed(&pending.device).await,
+            Err(e) => {
+                log::warn!(target: "receiver", "{}", Msg::LogRetryFailed { error: &e });
+                self.schedule_retry(pending.device, pending.backoff).await;
+            }
+        }
+    }
+
+    async fn schedule_retry(&mut self, device: BTDevice, mut backoff: Backoff) {
+        let delay = backoff.next_delay();
+        if delay.is_none() {
+            // Политика исчерпана, но автоподключение продолжит попытки в своём темпе
+            self.defer_auto_connect(&device.id);
+            // Бэкенд ещё держит граф и соединение — отпускаем, раз больше не ждём
+            self.release(&device.id).await;
+        }
+
+        match delay {
+            Some(delay) => {
+                self.transition(&device.id, StateEvent::RetryScheduled(backoff.attempt()));
+                let id = device.id.clone();
+                self.pending.insert(id, PendingReconnect { device, backoff, deadline: Instant::now() + delay });
+            }
+            None if backoff.max_attempts() == 0 => {
+                self.transition(&device.id, StateEvent::GaveUp(Msg::ConnectionLost { name: &device.name }.to_string()));
+            }
+            None => {
+                let reason = Msg::ReconnectGaveUp { name: &device.name, attempts: &backoff.attempt() };
+                self.transition(&device.id, StateEvent::GaveUp(reason.to_string()));
+            }
+        }
+    }
+
+    fn policy_for(&self, id: &str) -> ReconnectPolicy {
+        self.options.device_reconnect.get(id).unwrap_or(&self.options.reconnect).clone()
+    }
+
+    async fn scan(&mut self) {
+        self.set_scanning(true);
+        if let Ok(devs) = self.receiver.list_devices().await {
+            self.devices = devs;
+            self.publish_devices();
+            // Ошибки о пропавших устройствах больше не к чему относить
+            let stale: Vec<String> = self
+                .tx_state
+                .borrow()
+                .sessions
+                .iter()
+                .filter(|(id, state)| {
+                    matches!(state, ConnectionState::Failed { .. }) && !self.devices.iter().any(|d| d.id == *id)
+                })
+                .map(|(id, _)| id.clone())
+                .collect();
+            for id in stale {
+                self.transition(&id, StateEvent::Disconnected);
+            }
+        }
+        self.scan_outputs().await;
+        self.set_scanning(false);
+    }
+
+    async fn scan_outputs(&mut self) {
+        match self.receiver.list_outputs().await {
+            Ok(outputs) => {
+                self.tx_state.send_if_modified(|state| std::mem::replace(&mut state.outputs, outputs.clone()) != outputs);
+            }
+            Err(e) => log::warn!(target: "receiver", "{}", Msg::LogOutputsUnavailable { error: &e }),
+        }
+    }
+
+    fn publish_devices(&mut self) {
+        self.devices_debounce.reset();
+        self.tx_dev.send_replace(self.devices.clone());
+    }
+
+    async fn find_device(&mut self, id: &str) -> Option<BTDevice> {
+        let devs = self.receiver.list_devices().await.unwrap_or_default();
+        let found = devs.into_iter().find(|d| d.id == id);
+        if found.is_none() {
+            self.transition(id, StateEvent::ConnectFailed(Msg::DeviceUnavailable.to_string()));
+        }
+        found
+    }
+
+    async fn connect(&mut self, target: BTDevice) {
+        self.transition(&target.id, StateEvent::ConnectRequested(target.clone()));
+        let result = self.receiver.connect(&target).await;
+        self.finish_connect(&target, result).await;
+    }
+
+    async fn finish_connect(&mut self, device: &BTDevice, result: Result<()>) {
+        match result {
+            Ok(()) => self.succeed(device).await,
+            Err(e) => {
+                log::error!(target: "receiver", "{}", Msg::LogConnectFailed { name: &device.name, error: &e });
+                self.transition(&device.id, StateEvent::ConnectFailed(e.to_string()));
+            }
+        }
+    }
+
+    async fn succeed(&mut self, device: &BTDevice) {
+        self.options.last_device = Some(device.id.clone());
+        self.auto_retry.remove(&device.id);
+        // Новое соединение бэкенд всегда открывает на выходе по умолчанию и без обработки
+        self.tx_state.send_if_modified(|state| state.routes.remove(&device.id).is_some());
+        self.applied_dsp.remove(&device.id);
+        if let Some(output) = self.options.outputs.get(&device.id).cloned() {
+            self.route(&device.id, Some(output)).await;
+        }
+        self.apply_volume(&device.id, self.volume_for(&device.id));
+        self.apply_dsp(device).await;
+        // Новое соединение — новый файл
+        if self.tx_state.borrow().recording {
+            self.start_recording(device);
+        }
+        self.transition(&device.id, StateEvent::ConnectSucceeded);
+    }
+
+    fn start_recording(&mut self, device: &BTDevice) {
+        let tap = recording::spawn(self.options.recording.clone(), device.name.clone());
+        if let Err(e) = self.receiver.set_tap(&device.id, Some(tap)) {
+            log::warn!(target: "recording", "{}", Msg::LogTapFailed { name: &device.name, error: &e });
+        }
+    }
+
+    // Обработка источника, а за ней — выхода, на котором он сейчас играет, и общая динамика
+    async fn apply_dsp(&mut self, device: &BTDevice) {
+        let (output, night_mode) = {
+            let state = self.tx_state.borrow();
+            let output = state.routes.get(&device.id).and_then(|o| self.options.output_dsp.get(o)).cloned();
+            (output, state.night_mode)
+        };
+        let settings = DspSettings {
+            compressor: night_mode.then_some(self.options.night_mode),
+            limiter: self.options.limiter,
+            ..self.options.dsp.get(&device.id).cloned().unwrap_or_default().then(&output.unwrap_or_default())
+        };
+        if self.applied_dsp.get(&device.id).cloned().unwrap_or_default() == settings {
+            return;
+        }
+        match self.receiver.set_dsp(&device.id, settings.clone()).await {
+            Ok(()) => {
+                self.applied_dsp.insert(device.id.clone(), settings);
+            }
+            Err(e) => log::warn!(target: "receiver", "{}", Msg::LogDspFailed { name: &device.name, error: &e }),
+        }
+    }
+
+    fn volume_for(&self, id: &str) -> Volume {
+        self.options.volumes.get(id).copied().unwrap_or(self.options.default_volume)
+    }
+
+    // Громкость, которую не удалось применить, в состоянии не показываем: меню и API
+    // не должны предлагать то, что не работает. Выбор всё равно запомнен в `options`
+    fn apply_volume(&mut self, id: &str, volume: Volume) {
+        let applied = match self.receiver.set_volume(id, volume) {
+            Ok(()) => Some(volume),
+            Err(e) => {
+                log::warn!(target: "receiver", "{}", Msg::LogVolumeFailed { error: &e });
+                None
+            }
+        };
+        self.tx_state.send_if_modified(|state| match applied {
+            Some(volume) => state.volumes.insert(id.to_string(), volume) != Some(volume),
+            None => state.volumes.remove(id).is_some(),
+        });
+    }
+
+    async fn route(&mut self, id: &str, output: Option<String>) {
+        let routed = match self.receiver.set_output(id, output.as_deref()).await {
+            Ok(()) => output,
+            Err(e) => {
+                log::warn!(target: "receiver", "{}", Msg::LogOutputUnavailable { error: &e });
+                None
+            }
+        };
+        self.tx_state.send_if_modified(|state| match routed {
+            Some(output) => state.routes.insert(id.to_string(), output.clone()) != Some(output),
+            None => state.routes.remove(id).is_some(),
+        });
+    }
+
+    async fn disconnect(&mut self, id: &str) {
+        self.release(id).await;
+        self.transition(id, StateEvent::Disconnected);
+    }
+
+    // Закрывает соединение в бэкенде, не трогая автомат состояния
+    async fn release(&mut self, id: &str) {
+        self.pending.remove(id);
+        self.manual.remove(id);
+        self.receiver.disconnect(id).await;
+        self.tx_state.send_if_modified(|state| state.routes.remove(id).is_some());
+        self.applied_dsp.remove(id);
+    }
+
+    /// Освобождает место под новое соединение, отключая самые давние.
+    async fn make_room(&mut self) {
+        while self.free_slots() == 0 {
+            let oldest = self
+                .tx_state
+                .borrow()
+                .sessions
+                .iter()
+                .find(|(_, state)| state.is_active())
+                .map(|(id, _)| id.clone());
+            let Some(id) = oldest else { return };
+            log::info!(target: "receiver", "{}", Msg::LogNoFreeSlots);
+            self.disconnect(&id).await;
+        }
+    }
+
+    fn free_slots(&self) -> usize {
+        self.options.max_connections.saturating_sub(self.tx_state.borrow().active_count())
+    }
+
+    fn state_of(&self, id: &str) -> ConnectionState {
+        self.tx_state.borrow().get(id).clone()
+    }
+
+    fn priority(&self) -> Priority<'_> {
+        Priority {
+            order: &self.options.priority,
+            auto_connect: &self.options.auto_connect,
+            last_device: self.options.last_device.as_deref(),
+        }
+    }
+
+    /// Доступные устройства, которые можно подключить автоматически: не подключённые,
+    /// не отключённые пользователем вручную и не ждущие повтора после неудачи.
+    fn idle_devices(&self) -> Vec<BTDevice> {
+        let state = self.tx_state.borrow();
+        let now = Instant::now();
+        self.devices
+            .iter()
+            .filter(|d| !state.get(&d.id).is_active() && !self.paused.contains(&d.id))
+            .filter(|d| self.auto_retry.get(&d.id).is_none_or(|at| *at <= now))
+            .cloned()
+            .collect()
+    }
+
+    // Следующая автоматическая попытка для `id` — не раньше чем через `auto_connect_retry`
+    fn defer_auto_connect(&mut self, id: &str) {
+        self.auto_retry.insert(id.to_string(), Instant::now() + self.options.auto_connect_retry);
+    }
+
+    /// Занимает свободные места самыми приоритетными доступными устройствами.
+    /// Устройства перебираются по порядку; кто не подключился, пробуется снова не раньше
+    /// чем через `auto_connect_retry`.
+    async fn auto_connect(&mut self) {
+        if self.auto_paused || self.free_slots() == 0 {
+            return;
+        }
+        if self.options.auto_connect.is_empty() && self.options.priority.is_empty() {
+            return;
+        }
+
+        for target in self.priority().candidates(&self.idle_devices()) {
+            if self.free_slots() == 0 {
+                return;
+            }
+            log::info!(target: "receiver", "{}", Msg::LogAutoConnect { name: &target.name });
+            let id = target.id.clone();
+            self.connect(target).await;
+            if !self.state_of(&id).is_connected() {
+                self.defer_auto_connect(&id);
+            }
+        }
+    }
+
+    /// Когда все места заняты, заменяет наименее важное подключённое устройство более
+    /// приоритетным, если это разрешено настройками. Подключённые пользователем вручную
+    /// не заменяются. Если переключиться не удалось — возвращается к прежнему.
+    async fn maybe_preempt(&mut self) {
+        if !self.options.preempt || self.free_slots() > 0 {
+            return;
+        }
+        let weakest = {
+            let state = self.tx_state.borrow();
+            let priority = self.priority();
+            state
+                .connected()
+                .filter(|d| !self.manual.contains(&d.id))
+                .max_by_key(|d| priority.rank(&d.id).unwrap_or(usize::MAX))
+                .cloned()
+        };
+        let Some(current) = weakest else { return };
+        let Some(target) = self.priority().preempt_target(&self.idle_devices(), &current.id) else { return };
+
+        log::info!(target: "receiver", "{}", Msg::LogPreempt { target: &target.name, current: &current.name });
+        self.disconnect(&current.id).await;
+        let target_id = target.id.clone();
+        self.connect(target).await;
+
+        if !self.state_of(&target_id).is_connected() {
+            log::warn!(target: "receiver", "{}", Msg::LogPreemptFailed { name: &current.name });
+            self.defer_auto_connect(&target_id);
+            self.connect(current).await;
+        }
+    }
+
+    fn set_scanning(&self, scanning: bool) {
+        self.tx_state.send_if_modified(|state| std::mem::replace(&mut state.scanning, scanning) != scanning);
+    }
+
+    fn transition(&self, id: &str, event: StateEvent) {
+        self.tx_state.send_if_modified(|state| state.apply(id, event));
+    }
+}
@@ -0,0 +1,133 @@
+//! Меню трея без трея: что в нём есть и что значат нажатия.
+
+use bt_audio_receiver::backend::{AudioOutput, BTDevice};
+use bt_audio_receiver::config::{Config, DeviceConfig, Language};
+use bt_audio_receiver::media::MediaAction;
+use bt_audio_receiver::menu::{self, MenuClick, MenuEntry};
+use bt_audio_receiver::state::{ReceiverState, StateEvent};
+use bt_audio_receiver::volume::{Volume, VolumeChange};
+use bt_audio_receiver::worker::AppCommand;
+
+fn device(id: &str, name: &str) -> BTDevice {
+    BTDevice { id: id.to_string(), name: name.to_string() }
+}
+
+// Все id пунктов меню, включая вложенные
+fn ids(entries: &[MenuEntry]) -> Vec<String> {
+    entries
+        .iter()
+        .flat_map(|entry| match entry {
+            MenuEntry::Item { id, .. } | MenuEntry::Check { id, .. } => vec![id.clone()],
+            MenuEntry::Submenu { entries, .. } => ids(entries),
+            MenuEntry::Separator => Vec::new(),
+        })
+        .collect()
+}
+
+fn connected(devices: &[BTDevice]) -> ReceiverState {
+    let mut state = ReceiverState::default();
+    for device in devices {
+        state.apply(&device.id, StateEvent::ConnectRequested(device.clone()));
+        state.apply(&device.id, StateEvent::ConnectSucceeded);
+        state.volumes.insert(device.id.clone(), Volume::default());
+    }
+    state
+}
+
+#[test]
+fn every_item_means_something() {
+    let devices = [device("a", "Pixel"), device("b", "Pixel")];
+    let mut state = connected(&devices[..1]);
+    state.outputs.push(AudioOutput { id: "out-1".into(), name: "USB DAC".into() });
+    let config = Config::default();
+
+    let entries = menu::build(&devices, &state, &config, false);
+    for id in ids(&entries) {
+        if matches!(id.as_str(), "status" | "none" | "now_playing") {
+            continue;
+        }
+        assert!(MenuClick::parse(&id, &devices, &state, &config).is_some(), "пункт {id} ничего не делает");
+    }
+}
+
+#[test]
+fn connected_device_gets_its_own_submenu() {
+    let devices = [device("a", "Pixel"), device("b", "iPhone")];
+    let state = connected(&devices[..1]);
+    let entries = menu::build(&devices, &state, &Config::default(), false);
+    let ids = ids(&entries);
+
+    let a = devices[0].menu_key();
+    let b = devices[1].menu_key();
+    assert!(ids.contains(&format!("disconnect:{a}")));
+    assert!(!ids.contains(&format!("dev:{a}")), "подключённое устройство не предлагается снова");
+    assert!(ids.contains(&format!("dev:{b}")));
+    assert!(!ids.contains(&format!("disconnect:{b}")));
+    assert!(ids.contains(&"media:play_pause".to_string()));
+}
+
+#[test]
+fn failed_session_can_be_dismissed() {
+    let devices = [device("a", "Pixel")];
+    let mut state = ReceiverState::default();
+    state.apply("gone", StateEvent::ConnectFailed("нет устройства".into()));
+    let config = Config::default();
+
+    let entries = menu::build(&devices, &state, &config, false);
+    let dismiss = format!("dismiss:{}", bt_audio_receiver::backend::menu_key("gone"));
+    assert!(ids(&entries).contains(&dismiss), "ошибку без устройства можно убрать");
+    assert_eq!(
+        MenuClick::parse(&dismiss, &devices, &state, &config),
+        Some(MenuClick::Command(AppCommand::Dismiss("gone".into())))
+    );
+}
+
+#[test]
+fn idle_menu_has_no_session_items() {
+    let entries = menu::build(&[], &ReceiverState::default(), &Config::default(), true);
+    let ids = ids(&entries);
+    assert!(ids.contains(&"none".to_string()));
+    assert!(!ids.iter().any(|id| id.starts_with("media:") || id == "record" || id == "night_mode"));
+    assert!(entries.contains(&MenuEntry::Check {
+        id: "toggle_autostart".into(),
+        title: bt_audio_receiver::i18n::Msg::MenuAutostart.to_string(),
+        checked: true
+    }));
+}
+
+#[test]
+fn clicks_resolve_full_device_ids() {
+    let devices = [device("BTHENUM\\{long}\\phone", "Pixel")];
+    let mut state = connected(&devices);
+    state.outputs.push(AudioOutput { id: "out-1".into(), name: "USB DAC".into() });
+    let mut config = Config::default();
+    let key = devices[0].menu_key();
+    let parse = |id: &str| MenuClick::parse(id, &devices, &state, &config);
+
+    let id = devices[0].id.clone();
+    assert_eq!(parse(&format!("dev:{key}")), Some(MenuClick::Command(AppCommand::Connect(id.clone()))));
+    assert_eq!(parse(&format!("reconnect:{key}")), Some(MenuClick::Command(AppCommand::Reconnect(id.clone()))));
+    assert_eq!(
+        parse(&format!("volume:{key}:50")),
+        Some(MenuClick::Command(AppCommand::SetVolume(Some(id.clone()), VolumeChange::Set(50))))
+    );
+    assert_eq!(
+        parse(&format!("output:{key}:{}", state.outputs[0].menu_key())),
+        Some(MenuClick::Output(id.clone(), Some("out-1".into())))
+    );
+    assert_eq!(parse(&format!("output:{key}:")), Some(MenuClick::Output(id.clone(), None)));
+    assert_eq!(parse(&format!("auto:{key}")), Some(MenuClick::AutoConnect(id.clone(), true)));
+    assert_eq!(parse("media:next"), Some(MenuClick::Media(MediaAction::Next)));
+    assert_eq!(parse("lang:en"), Some(MenuClick::Language(Language::En)));
+    assert_eq!(parse("record"), Some(MenuClick::Command(AppCommand::SetRecording(true))));
+    assert_eq!(parse("night_mode"), Some(MenuClick::NightMode(true)));
+
+    // Устройство пропало, пока меню было открыто
+    assert_eq!(parse("dev:0000000000000000"), None);
+    assert_eq!(parse(&format!("volume:{key}:громко")), None);
+    assert_eq!(parse("unknown"), None);
+
+    config.devices.insert(id.clone(), DeviceConfig { auto_connect: true, ..Default::default() });
+    let parse = |id: &str| MenuClick::parse(id, &devices, &state, &config);
+    assert_eq!(parse(&format!("auto:{key}")), Some(MenuClick::AutoConnect(id, false)));
+}
@@ -0,0 +1,632 @@
+//! Воркер поверх `FakeBackend`: сценарии подключения без Windows и телефона.
+
+use bt_audio_receiver::backend::BTDevice;
+use bt_audio_receiver::dsp::DspSettings;
+use bt_audio_receiver::dynamics::{CompressorSettings, LimiterSettings};
+use bt_audio_receiver::equalizer::{EqBand, EqPreset, FilterKind};
+use bt_audio_receiver::fake_backend::{FakeBackend, FakeCall};
+use bt_audio_receiver::reconnect::ReconnectPolicy;
+use bt_audio_receiver::recording::{self, AudioChunk, AudioFormat, RecordingSettings};
+use bt_audio_receiver::state::{ConnectionState, ReceiverState};
+use bt_audio_receiver::volume::{Volume, VolumeChange};
+use bt_audio_receiver::worker::{background_worker, AppCommand, WorkerOptions};
+use std::time::Duration;
+use tokio::sync::{mpsc, watch};
+use tokio::time::Instant;
+
+struct Harness {
+    backend: FakeBackend,
+    cmd: mpsc::Sender<AppCommand>,
+    state: watch::Receiver<ReceiverState>,
+    devices: watch::Receiver<Vec<BTDevice>>,
+}
+
+impl Harness {
+    fn start(backend: &FakeBackend, options: WorkerOptions) -> Self {
+        let (tx_dev, devices) = watch::channel(Vec::new());
+        let (tx_state, state) = watch::channel(ReceiverState::default());
+        let (cmd, cmd_rx) = mpsc::channel(10);
+        let mut worker_backend = backend.clone();
+        tokio::spawn(async move { background_worker(&mut worker_backend, options, tx_dev, tx_state, cmd_rx).await });
+        Self { backend: backend.clone(), cmd, state, devices }
+    }
+
+    async fn send(&self, command: AppCommand) {
+        self.cmd.send(command).await.unwrap();
+    }
+
+    async fn wait_state(&mut self, what: &str, done: impl FnMut(&ReceiverState) -> bool) -> ReceiverState {
+        let waited = tokio::time::timeout(Duration::from_secs(600), self.state.wait_for(done)).await;
+        match waited.map(|state| state.unwrap().clone()) {
+            Ok(state) => state,
+            Err(_) => panic!("не дождались: {what}; состояние {:?}", *self.state.borrow()),
+        }
+    }
+
+    async fn wait_devices(&mut self, what: &str, done: impl FnMut(&Vec<BTDevice>) -> bool) -> Vec<BTDevice> {
+        let waited = tokio::time::timeout(Duration::from_secs(600), self.devices.wait_for(done)).await;
+        waited.unwrap_or_else(|_| panic!("не дождались: {what}")).unwrap().clone()
+    }
+
+    fn count(&self, call: &FakeCall) -> usize {
+        self.backend.calls().iter().filter(|c| *c == call).count()
+    }
+}
+
+fn phone() -> FakeBackend {
+    FakeBackend::with_devices(&[("phone-1", "Pixel")])
+}
+
+#[tokio::test(start_paused = true)]
+async fn connect_succeeds() {
+    let mut h = Harness::start(&phone(), WorkerOptions::default());
+    h.wait_devices("список устройств", |d| !d.is_empty()).await;
+
+    h.send(AppCommand::Connect("phone-1".into())).await;
+    let state = h.wait_state("подключение", |s| s.get("phone-1").is_connected()).await;
+
+    assert_eq!(state.connected().map(|d| d.name.as_str()).collect::<Vec<_>>(), ["Pixel"]);
+    assert_eq!(h.backend.connected(), ["phone-1"]);
+    assert_eq!(h.count(&FakeCall::Connect("phone-1".into())), 1);
+    // Громкость выставляется сразу после подключения
+    assert!(state.volumes.contains_key("phone-1"));
+    assert!(h.backend.volume("phone-1").is_some());
+}
+
+#[tokio::test(start_paused = true)]
+async fn fixed_volume_is_not_reported() {
+    let backend = phone();
+    backend.fix_volume("phone-1");
+    let mut h = Harness::start(&backend, WorkerOptions::default());
+    h.wait_devices("список устройств", |d| !d.is_empty()).await;
+
+    h.send(AppCommand::Connect("phone-1".into())).await;
+    let state = h.wait_state("подключение", |s| s.get("phone-1").is_connected()).await;
+    assert!(!state.volumes.contains_key("phone-1"), "громкость, которую не изменить, не показываем");
+
+    let volume = Volume { level: 50, muted: false };
+    h.send(AppCommand::SetVolume(Some("phone-1".into()), VolumeChange::Set(50))).await;
+    tokio::time::sleep(Duration::from_secs(1)).await;
+    assert_eq!(h.count(&FakeCall::SetVolume("phone-1".into(), volume)), 1, "попытка всё же была");
+    assert!(!h.state.borrow().volumes.contains_key("phone-1"));
+    assert_eq!(h.backend.volume("phone-1"), None);
+}
+
+#[tokio::test(start_paused = true)]
+async fn connect_fails() {
+    let backend = phone();
+    backend.fail_next_connect("телефон занят");
+    let mut h = Harness::start(&backend, WorkerOptions::default());
+
+    h.send(AppCommand::Connect("phone-1".into())).await;
+    let state = h.wait_state("ошибка", |s| matches!(s.get("phone-1"), ConnectionState::Failed { .. })).await;
+
+    assert_eq!(*state.get("phone-1"), ConnectionState::Failed { device: Some(device("phone-1", "Pixel")), reason: "телефон занят".into() });
+    assert!(h.backend.connected().is_empty());
+    // Ручное подключение не переподключается само
+    tokio::time::sleep(Duration::from_secs(120)).await;
+    assert_eq!(h.count(&FakeCall::Reconnect("phone-1".into())), 0);
+}
+
+#[tokio::test(start_paused = true)]
+async fn connect_unknown_device_fails() {
+    let mut h = Harness::start(&phone(), WorkerOptions::default());
+
+    h.send(AppCommand::Connect("nope".into())).await;
+    h.wait_state("ошибка", |s| matches!(s.get("nope"), ConnectionState::Failed { .. })).await;
+    assert_eq!(h.count(&FakeCall::Connect("nope".into())), 0);
+}
+
+#[tokio::test(start_paused = true)]
+async fn failures_are_dismissed_or_dropped_on_scan() {
+    let backend = phone();
+    backend.fail_next_connect("телефон занят");
+    let mut h = Harness::start(&backend, WorkerOptions::default());
+
+    h.send(AppCommand::Connect("phone-1".into())).await;
+    h.send(AppCommand::Connect("nope".into())).await;
+    h.wait_state("две ошибки", |s| s.sessions.len() == 2).await;
+
+    // Ошибка о неизвестном устройстве уходит при сканировании, о знакомом — остаётся
+    h.send(AppCommand::Scan).await;
+    let state = h.wait_state("сканирование", |s| s.sessions.len() == 1).await;
+    assert!(matches!(state.get("phone-1"), ConnectionState::Failed { .. }));
+
+    h.send(AppCommand::Dismiss("phone-1".into())).await;
+    h.wait_state("ошибка убрана", |s| s.sessions.is_empty()).await;
+    assert_eq!(h.count(&FakeCall::Disconnect("phone-1".into())), 0);
+}
+
+#[tokio::test(start_paused = true)]
+async fn devices_appear_and_disappear() {
+    let backend = FakeBackend::new();
+    let mut h = Harness::start(&backend, WorkerOptions::default());
+    h.wait_state("конец сканирования", |s| !s.scanning).await;
+    assert!(h.devices.borrow().is_empty());
+
+    backend.add_device("phone-1", "Pixel");
+    backend.add_device("phone-2", "iPhone");
+    let devices = h.wait_devices("появление", |d| d.len() == 2).await;
+    assert_eq!(devices.iter().map(|d| d.id.as_str()).collect::<Vec<_>>(), ["phone-1", "phone-2"]);
+
+    backend.add_device("phone-2", "iPhone 15");
+    h.wait_devices("переименование", |d| d.iter().any(|d| d.name == "iPhone 15")).await;
+
+    backend.remove_device("phone-1");
+    let devices = h.wait_devices("исчезновение", |d| d.len() == 1).await;
+    assert_eq!(devices[0].id, "phone-2");
+    assert_eq!(h.count(&FakeCall::WatchDevices), 1);
+}
+
+#[tokio::test(start_paused = true)]
+async fn device_appearing_is_auto_connected() {
+    let backend = FakeBackend::new();
+    let options = WorkerOptions { auto_connect: ["phone-1".to_string()].into(), ..Default::default() };
+    let mut h = Harness::start(&backend, options);
+    h.wait_state("конец сканирования", |s| !s.scanning).await;
+
+    backend.add_device("phone-1", "Pixel");
+    h.wait_state("автоподключение", |s| s.get("phone-1").is_connected()).await;
+    assert_eq!(h.count(&FakeCall::Connect("phone-1".into())), 1);
+}
+
+#[tokio::test(start_paused = true)]
+async fn dropped_connection_is_restored() {
+    let mut h = Harness::start(&phone(), WorkerOptions::default());
+    h.send(AppCommand::Connect("phone-1".into())).await;
+    h.wait_state("подключение", |s| s.get("phone-1").is_connected()).await;
+
+    h.backend.drop_connection("phone-1");
+    let state = h.wait_state("переподключение", |s| matches!(s.get("phone-1"), ConnectionState::Reconnecting { .. })).await;
+    assert!(matches!(state.get("phone-1"), ConnectionState::Reconnecting { attempt: 1, .. }));
+
+    h.wait_state("восстановление", |s| s.get("phone-1").is_connected()).await;
+    assert_eq!(h.count(&FakeCall::Reconnect("phone-1".into())), 1);
+    assert_eq!(h.backend.connected(), ["phone-1"]);
+}
+
+#[tokio::test(start_paused = true)]
+async fn dropped_connection_retries_until_success() {
+    let mut h = Harness::start(&phone(), WorkerOptions::default());
+    h.send(AppCommand::Connect("phone-1".into())).await;
+    h.wait_state("подключение", |s| s.get("phone-1").is_connected()).await;
+
+    h.backend.fail_next_connect("нет ответа");
+    h.backend.fail_next_connect("нет ответа");
+    h.backend.drop_connection("phone-1");
+    h.wait_state("третья попытка", |s| matches!(s.get("phone-1"), ConnectionState::Reconnecting { attempt: 3, .. })).await;
+    h.wait_state("восстановление", |s| s.get("phone-1").is_connected()).await;
+    assert_eq!(h.count(&FakeCall::Reconnect("phone-1".into())), 3);
+}
+
+#[tokio::test(start_paused = true)]
+async fn user_disconnect_is_not_a_drop() {
+    let mut h = Harness::start(&phone(), WorkerOptions::default());
+    h.send(AppCommand::Connect("phone-1".into())).await;
+    h.wait_state("подключение", |s| s.get("phone-1").is_connected()).await;
+
+    h.send(AppCommand::Disconnect("phone-1".into())).await;
+    let state = h.wait_state("отключение", |s| s.sessions.is_empty()).await;
+    assert_eq!(state.active_count(), 0);
+    tokio::time::sleep(Duration::from_secs(120)).await;
+    assert_eq!(h.count(&FakeCall::Reconnect("phone-1".into())), 0);
+    assert!(h.backend.connected().is_empty());
+}
+
+fn device(id: &str, name: &str) -> BTDevice {
+    BTDevice { id: id.to_string(), name: name.to_string() }
+}
+
+#[tokio::test(start_paused = true)]
+async fn backoff_gives_up_and_releases_the_connection() {
+    let policy = ReconnectPolicy {
+        initial_delay: Duration::from_secs(1),
+        max_delay: Duration::from_secs(60),
+        multiplier: 2.0,
+        jitter: 0.0,
+        max_attempts: 3,
+    };
+    let options = WorkerOptions { reconnect: policy, ..Default::default() };
+    let mut h = Harness::start(&phone(), options);
+    h.send(AppCommand::Connect("phone-1".into())).await;
+    h.wait_state("подключение", |s| s.get("phone-1").is_connected()).await;
+
+    for _ in 0..3 {
+        h.backend.fail_next_connect("нет ответа");
+    }
+    let start = Instant::now();
+    h.backend.drop_connection("phone-1");
+    let mut attempts = Vec::new();
+    for attempt in 2..=3 {
+        h.wait_state("попытка", |s| matches!(s.get("phone-1"), ConnectionState::Reconnecting { attempt: a, .. } if *a == attempt)).await;
+        attempts.push(start.elapsed().as_secs());
+    }
+    let state = h.wait_state("отказ", |s| matches!(s.get("phone-1"), ConnectionState::Failed { .. })).await;
+    let gave_up = start.elapsed().as_secs();
+
+    // Попытки через 1, 1 + 2 и 1 + 2 + 4 секунды после обрыва
+    assert_eq!(attempts, [1, 3]);
+    assert_eq!(gave_up, 7);
+    assert_eq!(h.count(&FakeCall::Reconnect("phone-1".into())), 3);
+    assert!(matches!(state.get("phone-1"), ConnectionState::Failed { device: Some(d), .. } if d.id == "phone-1"));
+    assert!(!state.get("phone-1").is_active());
+    // Сдавшись, воркер отпускает соединение в бэкенде
+    assert_eq!(h.count(&FakeCall::Disconnect("phone-1".into())), 1);
+    assert!(h.backend.connected().is_empty());
+}
+
+#[tokio::test(start_paused = true)]
+async fn policy_without_attempts_gives_up_at_once() {
+    let policy = ReconnectPolicy { max_attempts: 0, ..Default::default() };
+    let options = WorkerOptions { reconnect: policy, ..Default::default() };
+    let mut h = Harness::start(&phone(), options);
+    h.send(AppCommand::Connect("phone-1".into())).await;
+    h.wait_state("подключение", |s| s.get("phone-1").is_connected()).await;
+
+    h.backend.drop_connection("phone-1");
+    h.wait_state("отказ", |s| matches!(s.get("phone-1"), ConnectionState::Failed { .. })).await;
+    assert_eq!(h.count(&FakeCall::Reconnect("phone-1".into())), 0);
+    assert_eq!(h.count(&FakeCall::Disconnect("phone-1".into())), 1);
+}
+
+#[tokio::test(start_paused = true)]
+async fn per_device_policy_wins() {
+    let options = WorkerOptions {
+        device_reconnect: [("phone-1".to_string(), ReconnectPolicy { max_attempts: 0, ..Default::default() })].into(),
+        ..Default::default()
+    };
+    let mut h = Harness::start(&phone(), options);
+    h.send(AppCommand::Connect("phone-1".into())).await;
+    h.wait_state("подключение", |s| s.get("phone-1").is_connected()).await;
+
+    h.backend.drop_connection("phone-1");
+    h.wait_state("отказ", |s| matches!(s.get("phone-1"), ConnectionState::Failed { .. })).await;
+}
+
+#[tokio::test(start_paused = true)]
+async fn manual_reconnect_respects_max_connections() {
+    let backend = FakeBackend::with_devices(&[("phone-1", "Pixel"), ("phone-2", "iPhone")]);
+    let mut h = Harness::start(&backend, WorkerOptions::default());
+    h.send(AppCommand::Connect("phone-1".into())).await;
+    h.wait_state("подключение", |s| s.get("phone-1").is_connected()).await;
+
+    h.send(AppCommand::Reconnect("phone-2".into())).await;
+    let state = h.wait_state("переподключение", |s| s.get("phone-2").is_connected()).await;
+    assert_eq!(state.active_count(), 1);
+    assert_eq!(h.backend.connected(), ["phone-2"]);
+    assert_eq!(h.count(&FakeCall::Disconnect("phone-1".into())), 1);
+
+    // Переподключение того, что уже подключено, никого не вытесняет
+    h.send(AppCommand::Reconnect("phone-2".into())).await;
+    tokio::time::sleep(Duration::from_secs(1)).await;
+    assert!(h.state.borrow().get("phone-2").is_connected());
+    assert_eq!(h.count(&FakeCall::Reconnect("phone-2".into())), 2);
+    assert_eq!(h.count(&FakeCall::Disconnect("phone-2".into())), 0);
+}
+
+fn three_phones() -> FakeBackend {
+    FakeBackend::with_devices(&[("phone-1", "Pixel"), ("phone-2", "iPhone"), ("phone-3", "Galaxy")])
+}
+
+#[tokio::test(start_paused = true)]
+async fn two_sessions_share_the_receiver() {
+    let options = WorkerOptions { max_connections: 2, ..Default::default() };
+    let mut h = Harness::start(&three_phones(), options);
+    h.send(AppCommand::Connect("phone-1".into())).await;
+    h.send(AppCommand::Connect("phone-2".into())).await;
+    let state = h.wait_state("два подключения", |s| s.connected().count() == 2).await;
+    assert_eq!(state.connected().map(|d| d.id.as_str()).collect::<Vec<_>>(), ["phone-1", "phone-2"]);
+    assert!(state.volumes.contains_key("phone-1") && state.volumes.contains_key("phone-2"));
+
+    // Третьему не хватает места — уступает самое давнее соединение
+    h.send(AppCommand::Connect("phone-3".into())).await;
+    let state = h.wait_state("замена", |s| s.get("phone-3").is_connected()).await;
+    assert_eq!(state.active_count(), 2);
+    assert_eq!(h.backend.connected(), ["phone-2", "phone-3"]);
+    assert_eq!(h.count(&FakeCall::Disconnect("phone-1".into())), 1);
+    assert_eq!(h.count(&FakeCall::Disconnect("phone-2".into())), 0);
+}
+
+#[tokio::test(start_paused = true)]
+async fn one_session_reconnects_without_touching_the_other() {
+    let options = WorkerOptions { max_connections: 2, ..Default::default() };
+    let mut h = Harness::start(&three_phones(), options);
+    h.send(AppCommand::Connect("phone-1".into())).await;
+    h.send(AppCommand::Connect("phone-2".into())).await;
+    h.wait_state("два подключения", |s| s.connected().count() == 2).await;
+
+    h.backend.drop_connection("phone-1");
+    let state = h.wait_state("переподключение", |s| matches!(s.get("phone-1"), ConnectionState::Reconnecting { .. })).await;
+    assert!(state.get("phone-2").is_connected());
+    assert_eq!(state.active_count(), 2, "переподключаемое место остаётся занятым");
+
+    h.wait_state("восстановление", |s| s.connected().count() == 2).await;
+    assert_eq!(h.count(&FakeCall::Reconnect("phone-1".into())), 1);
+    assert_eq!(h.count(&FakeCall::Reconnect("phone-2".into())), 0);
+    assert_eq!(h.count(&FakeCall::Disconnect("phone-2".into())), 0);
+}
+
+#[tokio::test(start_paused = true)]
+async fn disconnect_all_closes_every_session() {
+    let options = WorkerOptions {
+        max_connections: 2,
+        auto_connect: ["phone-1".to_string(), "phone-2".to_string()].into(),
+        ..Default::default()
+    };
+    let mut h = Harness::start(&three_phones(), options);
+    h.wait_state("автоподключение", |s| s.connected().count() == 2).await;
+
+    h.send(AppCommand::DisconnectAll).await;
+    h.wait_state("отключение", |s| s.sessions.is_empty()).await;
+    assert!(h.backend.connected().is_empty());
+    assert_eq!(h.count(&FakeCall::Disconnect("phone-1".into())), 1);
+    assert_eq!(h.count(&FakeCall::Disconnect("phone-2".into())), 1);
+    // И само обратно не подключается
+    tokio::time::sleep(Duration::from_secs(120)).await;
+    assert_eq!(h.count(&FakeCall::Connect("phone-1".into())), 1);
+}
+
+#[tokio::test(start_paused = true)]
+async fn priority_picks_and_preempts() {
+    let backend = FakeBackend::with_devices(&[("phone-2", "iPhone")]);
+    let options = WorkerOptions {
+        priority: vec!["phone-1".to_string(), "phone-2".to_string()],
+        preempt: true,
+        ..Default::default()
+    };
+    let mut h = Harness::start(&backend, options);
+    h.wait_state("автоподключение", |s| s.get("phone-2").is_connected()).await;
+
+    // Появился более важный телефон — приёмник переключается на него
+    backend.add_device("phone-1", "Pixel");
+    let state = h.wait_state("переключение", |s| s.get("phone-1").is_connected()).await;
+    assert_eq!(state.active_count(), 1);
+    assert_eq!(h.backend.connected(), ["phone-1"]);
+    assert_eq!(h.count(&FakeCall::Disconnect("phone-2".into())), 1);
+}
+
+#[tokio::test(start_paused = true)]
+async fn failed_preemption_goes_back() {
+    let backend = FakeBackend::with_devices(&[("phone-2", "iPhone")]);
+    let options = WorkerOptions {
+        priority: vec!["phone-1".to_string(), "phone-2".to_string()],
+        preempt: true,
+        ..Default::default()
+    };
+    let mut h = Harness::start(&backend, options);
+    h.wait_state("автоподключение", |s| s.get("phone-2").is_connected()).await;
+
+    backend.fail_next_connect("занят");
+    backend.add_device("phone-1", "Pixel");
+    h.wait_state("возврат", |s| matches!(s.get("phone-1"), ConnectionState::Failed { .. }) && s.get("phone-2").is_connected()).await;
+    assert_eq!(h.count(&FakeCall::Connect("phone-2".into())), 2);
+}
+
+#[tokio::test(start_paused = true)]
+async fn device_updates_do_not_hurry_a_failed_auto_connect() {
+    let backend = phone();
+    backend.fail_next_connect("занят");
+    let options = WorkerOptions { auto_connect: ["phone-1".to_string()].into(), ..Default::default() };
+    let retry = options.auto_connect_retry;
+    let mut h = Harness::start(&backend, options);
+    h.wait_state("ошибка", |s| matches!(s.get("phone-1"), ConnectionState::Failed { .. })).await;
+
+    // Переименование и повторное появление — не повод пробовать раньше срока
+    backend.add_device("phone-1", "Pixel 8");
+    tokio::time::sleep(retry / 2).await;
+    assert_eq!(h.count(&FakeCall::Connect("phone-1".into())), 1);
+
+    h.wait_state("повтор по сроку", |s| s.get("phone-1").is_connected()).await;
+    assert_eq!(h.count(&FakeCall::Connect("phone-1".into())), 2);
+}
+
+#[tokio::test(start_paused = true)]
+async fn manual_connection_is_not_preempted() {
+    let backend = FakeBackend::with_devices(&[("phone-2", "iPhone")]);
+    // phone-2 нет в списке: подключить его может только пользователь
+    let options = WorkerOptions { priority: vec!["phone-1".to_string()], preempt: true, ..Default::default() };
+    let mut h = Harness::start(&backend, options);
+    h.send(AppCommand::Connect("phone-2".into())).await;
+    h.wait_state("ручное подключение", |s| s.get("phone-2").is_connected()).await;
+
+    backend.add_device("phone-1", "Pixel");
+    h.wait_devices("появление", |d| d.len() == 2).await;
+    assert_eq!(h.backend.connected(), ["phone-2"]);
+    assert_eq!(h.count(&FakeCall::Connect("phone-1".into())), 0);
+}
+
+#[tokio::test(start_paused = true)]
+async fn without_preempt_the_current_device_stays() {
+    let backend = FakeBackend::with_devices(&[("phone-2", "iPhone")]);
+    let options = WorkerOptions { priority: vec!["phone-1".to_string(), "phone-2".to_string()], ..Default::default() };
+    let mut h = Harness::start(&backend, options);
+    h.wait_state("автоподключение", |s| s.get("phone-2").is_connected()).await;
+
+    backend.add_device("phone-1", "Pixel");
+    h.wait_devices("появление", |d| d.len() == 2).await;
+    assert_eq!(h.backend.connected(), ["phone-2"]);
+    assert_eq!(h.count(&FakeCall::Connect("phone-1".into())), 0);
+}
+
+fn phone_and_dac() -> FakeBackend {
+    let backend = phone();
+    backend.add_output("dac", "USB DAC");
+    backend
+}
+
+#[tokio::test(start_paused = true)]
+async fn output_choice_routes_a_connected_phone() {
+    let mut h = Harness::start(&phone_and_dac(), WorkerOptions::default());
+    h.send(AppCommand::Connect("phone-1".into())).await;
+    let state = h.wait_state("подключение", |s| s.get("phone-1").is_connected()).await;
+    assert_eq!(state.outputs.len(), 1, "выходы известны со сканирования");
+    assert_eq!(h.backend.output("phone-1"), None);
+
+    h.send(AppCommand::SetOutput("phone-1".into(), Some("dac".into()))).await;
+    let state = h.wait_state("выбор выхода", |s| s.route("phone-1").is_some()).await;
+    assert_eq!(state.route("phone-1").map(|o| o.name.as_str()), Some("USB DAC"));
+    assert_eq!(h.backend.output("phone-1").as_deref(), Some("dac"));
+
+    h.send(AppCommand::SetOutput("phone-1".into(), None)).await;
+    h.wait_state("выход по умолчанию", |s| s.routes.is_empty()).await;
+    assert_eq!(h.backend.output("phone-1"), None);
+}
+
+#[tokio::test(start_paused = true)]
+async fn remembered_output_is_applied_on_connect() {
+    let options = WorkerOptions { outputs: [("phone-1".to_string(), "dac".to_string())].into(), ..Default::default() };
+    let mut h = Harness::start(&phone_and_dac(), options);
+    h.send(AppCommand::Connect("phone-1".into())).await;
+    let state = h.wait_state("подключение", |s| s.get("phone-1").is_connected()).await;
+    assert_eq!(state.routes.get("phone-1").map(String::as_str), Some("dac"), "запомненный выход выбран при подключении");
+    assert_eq!(h.backend.output("phone-1").as_deref(), Some("dac"));
+}
+
+#[tokio::test(start_paused = true)]
+async fn lost_output_falls_back_and_is_remembered() {
+    let options = WorkerOptions { outputs: [("phone-1".to_string(), "dac".to_string())].into(), ..Default::default() };
+    let mut h = Harness::start(&phone_and_dac(), options);
+    h.send(AppCommand::Connect("phone-1".into())).await;
+    h.wait_state("подключение", |s| s.routes.contains_key("phone-1")).await;
+
+    h.backend.remove_output("dac");
+    let state = h.wait_state("потеря выхода", |s| s.routes.is_empty() && s.outputs.is_empty()).await;
+    assert!(state.get("phone-1").is_connected(), "звук продолжается на выходе по умолчанию");
+    assert_eq!(h.count(&FakeCall::SetOutput("phone-1".into(), None)), 1);
+
+    // Выход вернулся — при следующем подключении звук снова идёт на него
+    h.backend.add_output("dac", "USB DAC");
+    h.send(AppCommand::Disconnect("phone-1".into())).await;
+    h.send(AppCommand::Connect("phone-1".into())).await;
+    h.wait_state("снова на выбранном выходе", |s| s.routes.contains_key("phone-1")).await;
+    assert_eq!(h.backend.output("phone-1").as_deref(), Some("dac"));
+}
+
+fn chunk() -> AudioChunk {
+    AudioChunk { format: AudioFormat { sample_rate: 48000, channels: 2 }, samples: vec![0.25; 960] }
+}
+
+#[tokio::test(start_paused = true)]
+async fn recording_taps_connected_and_new_phones() {
+    let folder = std::env::temp_dir().join(format!("bt-audio-worker-{}-recording", std::process::id()));
+    let _ = std::fs::remove_dir_all(&folder);
+    let options = WorkerOptions {
+        max_connections: 2,
+        recording: RecordingSettings { folder: folder.clone(), ..Default::default() },
+        ..Default::default()
+    };
+    let backend = FakeBackend::with_devices(&[("phone-1", "Pixel"), ("phone-2", "iPhone")]);
+    let mut h = Harness::start(&backend, options);
+    h.send(AppCommand::Connect("phone-1".into())).await;
+    h.wait_state("подключение", |s| s.get("phone-1").is_connected()).await;
+    assert!(!backend.play("phone-1", chunk()), "без записи отвода нет");
+
+    h.send(AppCommand::SetRecording(true)).await;
+    h.wait_state("запись", |s| s.recording).await;
+    assert!(backend.play("phone-1", chunk()), "уже подключённый пишется");
+    assert_eq!(h.count(&FakeCall::SetTap("phone-1".into(), true)), 1);
+
+    h.send(AppCommand::Connect("phone-2".into())).await;
+    h.wait_state("второе подключение", |s| s.get("phone-2").is_connected()).await;
+    assert!(backend.play("phone-2", chunk()), "подключившийся во время записи тоже пишется");
+
+    h.send(AppCommand::SetRecording(false)).await;
+    h.wait_state("конец записи", |s| !s.recording).await;
+    assert!(!backend.play("phone-1", chunk()));
+    assert!(!backend.play("phone-2", chunk()));
+    assert_eq!(h.count(&FakeCall::SetTap("phone-2".into(), false)), 1);
+
+    recording::wait_all();
+    let files = std::fs::read_dir(&folder).unwrap().count();
+    assert_eq!(files, 2, "по файлу на телефон");
+    let _ = std::fs::remove_dir_all(&folder);
+}
+
+fn preset(preamp: f64, kind: FilterKind, freq: f64, gain: f64) -> DspSettings {
+    let band = EqBand { kind, freq, gain, q: 0.7 };
+    DspSettings { equalizer: Some(EqPreset { file: None, preamp, bands: vec![band] }), ..Default::default() }
+}
+
+#[tokio::test(start_paused = true)]
+async fn output_equalizer_follows_the_source_one() {
+    let source = preset(-3.0, FilterKind::Peaking, 100.0, 4.0);
+    let output = preset(-1.0, FilterKind::HighShelf, 8000.0, -2.0);
+    let options = WorkerOptions {
+        dsp: [("phone-1".to_string(), source.clone())].into(),
+        output_dsp: [("dac".to_string(), output.clone())].into(),
+        ..Default::default()
+    };
+    let mut h = Harness::start(&phone_and_dac(), options);
+    h.send(AppCommand::Connect("phone-1".into())).await;
+    h.wait_state("подключение", |s| s.get("phone-1").is_connected()).await;
+    assert_eq!(h.backend.dsp("phone-1"), Some(source.clone()), "пресет устройства");
+
+    h.send(AppCommand::SetOutput("phone-1".into(), Some("dac".into()))).await;
+    h.wait_state("выбор выхода", |s| s.routes.contains_key("phone-1")).await;
+    tokio::time::sleep(Duration::from_secs(1)).await;
+    let equalizer = h.backend.dsp("phone-1").and_then(|dsp| dsp.equalizer).unwrap();
+    assert_eq!(equalizer.preamp, -4.0);
+    let source_bands = source.equalizer.as_ref().unwrap().bands.iter();
+    let bands: Vec<EqBand> = source_bands.chain(&output.equalizer.as_ref().unwrap().bands).copied().collect();
+    assert_eq!(equalizer.bands, bands, "сначала пресет источника, потом выхода");
+
+    h.send(AppCommand::SetOutput("phone-1".into(), None)).await;
+    h.wait_state("выход по умолчанию", |s| s.routes.is_empty()).await;
+    tokio::time::sleep(Duration::from_secs(1)).await;
+    assert_eq!(h.backend.dsp("phone-1"), Some(source));
+}
+
+#[tokio::test(start_paused = true)]
+async fn output_equalizer_applies_on_connect() {
+    let output = preset(-1.0, FilterKind::HighShelf, 8000.0, -2.0);
+    let options = WorkerOptions {
+        outputs: [("phone-1".to_string(), "dac".to_string())].into(),
+        output_dsp: [("dac".to_string(), output.clone())].into(),
+        ..Default::default()
+    };
+    let mut h = Harness::start(&phone_and_dac(), options);
+    h.send(AppCommand::Connect("phone-1".into())).await;
+    h.wait_state("подключение", |s| s.get("phone-1").is_connected()).await;
+    assert_eq!(h.backend.dsp("phone-1"), Some(output), "без пресета источника — только выход");
+}
+
+#[tokio::test(start_paused = true)]
+async fn night_mode_and_limiter_reach_connected_phones() {
+    let limiter = LimiterSettings { ceiling_db: -2.0, ..Default::default() };
+    let night_mode = CompressorSettings { threshold_db: -25.0, ..CompressorSettings::night_mode() };
+    let options = WorkerOptions { max_connections: 2, limiter: Some(limiter), night_mode, ..Default::default() };
+    let backend = FakeBackend::with_devices(&[("phone-1", "Pixel"), ("phone-2", "iPhone")]);
+    let mut h = Harness::start(&backend, options);
+    h.send(AppCommand::Connect("phone-1".into())).await;
+    h.wait_state("подключение", |s| s.get("phone-1").is_connected()).await;
+    let dsp = backend.dsp("phone-1").unwrap();
+    assert_eq!((dsp.limiter, dsp.compressor), (Some(limiter), None));
+
+    h.send(AppCommand::SetNightMode(true)).await;
+    h.wait_state("ночной режим", |s| s.night_mode).await;
+    tokio::time::sleep(Duration::from_secs(1)).await;
+    assert_eq!(backend.dsp("phone-1").unwrap().compressor, Some(night_mode), "включается и у подключённых");
+
+    h.send(AppCommand::Connect("phone-2".into())).await;
+    h.wait_state("второе подключение", |s| s.get("phone-2").is_connected()).await;
+    let dsp = backend.dsp("phone-2").unwrap();
+    assert_eq!((dsp.limiter, dsp.compressor), (Some(limiter), Some(night_mode)));
+
+    h.send(AppCommand::SetNightMode(false)).await;
+    h.wait_state("без ночного режима", |s| !s.night_mode).await;
+    tokio::time::sleep(Duration::from_secs(1)).await;
+    for id in ["phone-1", "phone-2"] {
+        let dsp = backend.dsp(id).unwrap();
+        assert_eq!((dsp.limiter, dsp.compressor), (Some(limiter), None), "{id}: лимитер остаётся");
+    }
+}
+
+#[tokio::test(start_paused = true)]
+async fn night_mode_from_settings_applies_on_connect() {
+    let options = WorkerOptions { night_mode_enabled: true, ..Default::default() };
+    let mut h = Harness::start(&phone(), options);
+    h.send(AppCommand::Connect("phone-1".into())).await;
+    let state = h.wait_state("подключение", |s| s.get("phone-1").is_connected()).await;
+    assert!(state.night_mode);
+    assert_eq!(h.backend.dsp("phone-1").unwrap().compressor, Some(CompressorSettings::night_mode()));
+}